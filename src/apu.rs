@@ -0,0 +1,255 @@
+//! The audio processing unit: 4 channels mixed into a stereo PCM buffer the host pulls from.
+//!
+//! ```text
+//! Channel 1 | 0xFF10-0xFF14 | square wave with frequency sweep
+//! Channel 2 | 0xFF16-0xFF19 | square wave
+//! Channel 3 | 0xFF1A-0xFF1E | wave RAM (0xFF30-0xFF3F) playback
+//! Channel 4 | 0xFF20-0xFF23 | noise
+//! 0xFF24    | NR50          | 6-4 left volume, 2-0 right volume
+//! 0xFF25    | NR51          | 7-4 channel 4-1 to the left, 3-0 channel 4-1 to the right
+//! 0xFF26    | NR52          | 7 APU on (writable), 3-0 channel 4-1 on (read only)
+//! ```
+//!
+//! The frame sequencer steps at 512 Hz and clocks the length counters (256 Hz), channel 1's
+//! sweep (128 Hz) and the envelopes (64 Hz).
+//!
+//! With the APU off all of its registers read back as if they were 0 and only NR52 and wave
+//! RAM can be written.
+//!
+//! Samples are only made once the host picked a sample rate with [`Apu::set_sample_rate`], and
+//! pile up until it takes them, so nothing needs an audio device.
+
+mod channel;
+mod noise;
+mod square;
+mod wave;
+#[cfg(test)]
+mod tests;
+
+use noise::Noise;
+use square::Square;
+use wave::{ Wave, WAVE_RAM_SIZE };
+
+pub const NR10_ADDRESS: u16 = 0xFF10;
+pub const NR14_ADDRESS: u16 = 0xFF14;
+pub const NR21_ADDRESS: u16 = 0xFF16;
+pub const NR24_ADDRESS: u16 = 0xFF19;
+pub const NR30_ADDRESS: u16 = 0xFF1A;
+pub const NR34_ADDRESS: u16 = 0xFF1E;
+pub const NR41_ADDRESS: u16 = 0xFF20;
+pub const NR44_ADDRESS: u16 = 0xFF23;
+pub const NR50_ADDRESS: u16 = 0xFF24;
+pub const NR51_ADDRESS: u16 = 0xFF25;
+pub const NR52_ADDRESS: u16 = 0xFF26;
+pub const WAVE_RAM_START: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = WAVE_RAM_START + WAVE_RAM_SIZE as u16 - 1;
+
+/// NR10-NR51, the registers that read back what was written to them (with `READ_MASKS`)
+const REGISTER_COUNT: usize = (NR51_ADDRESS - NR10_ADDRESS) as usize + 1;
+/// What's ORed into reads of NR10-NR51: write-only and unused bits read as 1
+const READ_MASKS: [u8; REGISTER_COUNT] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // (unused), NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // (unused), NR41-NR44
+    0x00, 0x00, // NR50, NR51
+];
+
+const NR52_POWER: u8 = 1 << 7;
+/// Bits 6-4 of NR52 aren't wired to anything
+const NR52_UNUSED_BITS: u8 = 0b0111_0000;
+
+const T_CYCLES_PER_SECOND: u64 = 4_194_304;
+/// The frame sequencer steps at 512 Hz
+const T_CYCLES_PER_FRAME_SEQUENCER_STEP: u32 = 8192;
+/// Everything moves a whole M-cycle at a time
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+
+pub struct Apu {
+    powered: bool,
+    /// The last value written to each of NR10-NR51
+    registers: [u8; REGISTER_COUNT],
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    frame_sequencer_cycles: u32,
+    /// 0-7, see `clock_frame_sequencer`
+    frame_sequencer_step: u8,
+    /// Samples per second per side, `None` for not making samples at all
+    sample_rate: Option<u32>,
+    /// Counts up by the sample rate every T-cycle, a sample is due every `T_CYCLES_PER_SECOND`
+    sample_clock: u64,
+    /// Interleaved left/right samples the host hasn't taken yet
+    samples: Vec<i16>,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            powered: false,
+            registers: [0; REGISTER_COUNT],
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            frame_sequencer_cycles: 0,
+            frame_sequencer_step: 0,
+            sample_rate: None,
+            sample_clock: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Starts making samples at `sample_rate` per second (per side), or stops with `None`
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.sample_rate = sample_rate;
+        self.sample_clock = 0;
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// The samples nobody took yet, interleaved left/right
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Hands over the samples made since the last call, interleaved left/right
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Throws away the oldest `count` samples (counting left and right separately)
+    pub fn drop_samples(&mut self, count: usize) {
+        self.samples.drain(..count.min(self.samples.len()));
+    }
+
+    pub fn tick(&mut self, t_cycles: u32) {
+        for _ in 0..t_cycles / T_CYCLES_PER_M_CYCLE {
+            self.step_m_cycle();
+        }
+    }
+
+    fn step_m_cycle(&mut self) {
+        if self.powered {
+            self.frame_sequencer_cycles += T_CYCLES_PER_M_CYCLE;
+            if self.frame_sequencer_cycles == T_CYCLES_PER_FRAME_SEQUENCER_STEP {
+                self.frame_sequencer_cycles = 0;
+                self.clock_frame_sequencer();
+            }
+            self.square1.step(T_CYCLES_PER_M_CYCLE);
+            self.square2.step(T_CYCLES_PER_M_CYCLE);
+            self.wave.step(T_CYCLES_PER_M_CYCLE);
+            self.noise.step(T_CYCLES_PER_M_CYCLE);
+        }
+
+        if let Some(sample_rate) = self.sample_rate {
+            self.sample_clock += sample_rate as u64 * T_CYCLES_PER_M_CYCLE as u64;
+            while self.sample_clock >= T_CYCLES_PER_SECOND {
+                self.sample_clock -= T_CYCLES_PER_SECOND;
+                let (left, right) = self.mix();
+                self.samples.push(left);
+                self.samples.push(right);
+            }
+        }
+    }
+
+    /// Step 0: length, 2: length and sweep, 4: length, 6: length and sweep, 7: envelopes
+    fn clock_frame_sequencer(&mut self) {
+        if self.frame_sequencer_step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_sequencer_step % 4 == 2 {
+            self.square1.clock_sweep();
+        }
+        if self.frame_sequencer_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_sequencer_step = (self.frame_sequencer_step + 1) % 8;
+    }
+
+    /// The left and right output right now, going through NR51 panning and NR50 volume
+    fn mix(&self) -> (i16, i16) {
+        let outputs = [self.square1.output(), self.square2.output(), self.wave.output(), self.noise.output()];
+        let panning = self.registers[(NR51_ADDRESS - NR10_ADDRESS) as usize];
+        let volume = self.registers[(NR50_ADDRESS - NR10_ADDRESS) as usize];
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for (channel, output) in outputs.into_iter().enumerate() {
+            // the DAC maps 0-15 to 1.0 to -1.0, channels with the DAC off are silent
+            let Some(output) = output else { continue };
+            let analog = 1.0 - output as f32 / 7.5;
+            if panning & (0x10 << channel) != 0 {
+                left += analog;
+            }
+            if panning & (0x01 << channel) != 0 {
+                right += analog;
+            }
+        }
+
+        let scale = |sum: f32, volume: u8| (sum / 4.0 * (volume + 1) as f32 / 8.0 * i16::MAX as f32) as i16;
+        (scale(left, (volume >> 4) & 0b111), scale(right, volume & 0b111))
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            NR10_ADDRESS..=NR51_ADDRESS => {
+                let index = (address - NR10_ADDRESS) as usize;
+                self.registers[index] | READ_MASKS[index]
+            }
+            NR52_ADDRESS => {
+                let channels = [self.square1.is_enabled(), self.square2.is_enabled(), self.wave.is_enabled(), self.noise.is_enabled()];
+                let channel_bits = channels.into_iter().enumerate()
+                    .fold(0, |bits, (channel, enabled)| bits | (enabled as u8) << channel);
+                NR52_UNUSED_BITS | if self.powered { NR52_POWER } else { 0 } | channel_bits
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.read_ram(address - WAVE_RAM_START),
+            // 0xFF27-0xFF2F
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            NR52_ADDRESS => self.write_power(value & NR52_POWER != 0),
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.write_ram(address - WAVE_RAM_START, value),
+            _ if !self.powered => {}
+            NR10_ADDRESS..=NR51_ADDRESS => {
+                self.registers[(address - NR10_ADDRESS) as usize] = value;
+                match address {
+                    NR10_ADDRESS..=NR14_ADDRESS => self.square1.write(address - NR10_ADDRESS, value),
+                    NR21_ADDRESS..=NR24_ADDRESS => self.square2.write(address - NR21_ADDRESS + 1, value),
+                    NR30_ADDRESS..=NR34_ADDRESS => self.wave.write(address - NR30_ADDRESS, value),
+                    NR41_ADDRESS..=NR44_ADDRESS => self.noise.write(address - NR41_ADDRESS + 1, value),
+                    // NR50/NR51 are only read back (by the mixer too), and 0xFF15/0xFF1F do nothing
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Turning the APU off clears every register but NR52, turning it on restarts the frame
+    /// sequencer
+    fn write_power(&mut self, on: bool) {
+        if !on && self.powered {
+            self.registers = [0; REGISTER_COUNT];
+            self.square1 = Square::new(true);
+            self.square2 = Square::new(false);
+            self.wave.power_off();
+            self.noise = Noise::new();
+        }
+        if on && !self.powered {
+            self.frame_sequencer_cycles = 0;
+            self.frame_sequencer_step = 0;
+        }
+        self.powered = on;
+    }
+}
@@ -0,0 +1,111 @@
+//! The pieces the channels share: the length counter, the volume envelope and the frequency
+//! timer.
+
+/// A channel's length counter: with length enabled the channel turns itself off once it's been
+/// clocked (at 256 Hz) `max - loaded value` times
+pub(super) struct Length {
+    counter: u16,
+    max: u16,
+    enabled: bool,
+}
+
+impl Length {
+    pub(super) fn new(max: u16) -> Self {
+        Self { counter: 0, max, enabled: false }
+    }
+
+    /// From the length bits of NRx1
+    pub(super) fn load(&mut self, value: u16) {
+        self.counter = self.max - value;
+    }
+
+    /// From bit 6 of NRx4
+    pub(super) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// A triggered channel with an expired length starts over with the whole length
+    pub(super) fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Clocks the counter and returns whether it just ran out (so the channel goes off)
+    pub(super) fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+/// The volume envelope of NRx2: 7-4 initial volume, 3 direction (1 is up), 2-0 period
+pub(super) struct Envelope {
+    register: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub(super) fn new() -> Self {
+        Self { register: 0, volume: 0, timer: 0 }
+    }
+
+    pub(super) fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    /// The DAC is on as long as any of the upper 5 bits of NRx2 are set
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub(super) fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    fn period(&self) -> u8 {
+        self.register & 0b111
+    }
+
+    pub(super) fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    /// Clocked at 64 Hz, moves the volume a step every `period` clocks (period 0 never does)
+    pub(super) fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period();
+        if self.register & 0b1000 != 0 {
+            self.volume = (self.volume + 1).min(15);
+        } else {
+            self.volume = self.volume.saturating_sub(1);
+        }
+    }
+}
+
+/// Runs a frequency timer with `remaining` T-cycles left for `cycles` T-cycles, reloading it with
+/// `period` every time it runs out. Returns how many times it ran out.
+pub(super) fn run_timer(remaining: &mut u32, cycles: u32, period: u32) -> u32 {
+    if *remaining == 0 {
+        *remaining = period;
+    }
+    let mut cycles = cycles;
+    let mut ticks = 0;
+    while cycles >= *remaining {
+        cycles -= *remaining;
+        *remaining = period;
+        ticks += 1;
+    }
+    *remaining -= cycles;
+    ticks
+}
@@ -0,0 +1,114 @@
+//! Channel 4: noise out of a 15-bit linear feedback shift register (LFSR).
+//!
+//! ```text
+//! NR41 | 5-0 length (64 - n)
+//! NR42 | envelope
+//! NR43 | 7-4 clock shift, 3 7-bit LFSR, 2-0 divisor code
+//! NR44 | 7 trigger, 6 length enable
+//! ```
+
+use super::channel::{ run_timer, Envelope, Length };
+
+/// T-cycles per LFSR shift before the clock shift, by divisor code
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+pub(super) struct Noise {
+    enabled: bool,
+    /// NR43
+    polynomial: u8,
+    timer: u32,
+    lfsr: u16,
+    length: Length,
+    envelope: Envelope,
+}
+
+impl Noise {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            polynomial: 0,
+            timer: 0,
+            lfsr: 0,
+            length: Length::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// T-cycles per LFSR shift
+    fn period(&self) -> u32 {
+        DIVISORS[(self.polynomial & 0b111) as usize] << (self.polynomial >> 4)
+    }
+
+    /// Write to NR41-NR44, by `register` number
+    pub(super) fn write(&mut self, register: u16, value: u8) {
+        match register {
+            1 => self.length.load(value as u16 & 0x3F),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.polynomial = value,
+            4 => {
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("NR4{register} is not a noise channel register"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.envelope.trigger();
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        let period = self.period();
+        for _ in 0..run_timer(&mut self.timer, cycles, period) {
+            self.shift_lfsr();
+        }
+    }
+
+    /// XORs the lowest 2 bits into bit 14 (and bit 6 too in 7-bit mode) while shifting right
+    fn shift_lfsr(&mut self) {
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | feedback << 14;
+        if self.polynomial & 0b1000 != 0 {
+            self.lfsr = (self.lfsr & !(1 << 6)) | feedback << 6;
+        }
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn envelope_volume(&self) -> u8 {
+        self.envelope.volume()
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// The digital output (0-15), `None` while the channel or its DAC is off
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.enabled || !self.envelope.dac_enabled() {
+            return None;
+        }
+        // a 0 in bit 0 is high
+        Some((!self.lfsr & 1) as u8 * self.envelope.volume())
+    }
+}
@@ -0,0 +1,192 @@
+//! Channels 1 and 2: square waves with 4 duty cycles, a volume envelope and (channel 1 only) a
+//! frequency sweep.
+//!
+//! ```text
+//! NRx0 | 6-4 sweep period, 3 sweep down, 2-0 sweep shift (channel 1 only)
+//! NRx1 | 7-6 duty, 5-0 length (64 - n)
+//! NRx2 | envelope
+//! NRx3 | frequency low 8 bits
+//! NRx4 | 7 trigger, 6 length enable, 2-0 frequency high 3 bits
+//! ```
+
+use super::channel::{ run_timer, Envelope, Length };
+
+/// Which of the 8 steps of the wave are high, by duty (12.5%, 25%, 50% and 75%)
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+const MAX_FREQUENCY: u16 = 0x7FF;
+
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    /// The frequency the sweep works off, copied on trigger
+    shadow: u16,
+    enabled: bool,
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Self { period: 0, negate: false, shift: 0, timer: 0, shadow: 0, enabled: false }
+    }
+
+    fn write(&mut self, value: u8) {
+        self.period = (value >> 4) & 0b111;
+        self.negate = value & 0b1000 != 0;
+        self.shift = value & 0b111;
+    }
+
+    /// A period of 0 still clocks the timer as if it was 8
+    fn reload_timer(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    fn next_frequency(&self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate { self.shadow.wrapping_sub(delta) } else { self.shadow + delta }
+    }
+
+    /// Returns false if the overflow check on trigger turns the channel off
+    fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow = frequency;
+        self.reload_timer();
+        self.enabled = self.period != 0 || self.shift != 0;
+        self.shift == 0 || self.next_frequency() <= MAX_FREQUENCY
+    }
+
+    /// Clocked at 128 Hz, updates `frequency` when the timer runs out. Returns false if the
+    /// frequency overflowed, which turns the channel off.
+    fn clock(&mut self, frequency: &mut u16) -> bool {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return true;
+        }
+        self.reload_timer();
+        if !self.enabled || self.period == 0 {
+            return true;
+        }
+
+        let next = self.next_frequency();
+        if next > MAX_FREQUENCY {
+            return false;
+        }
+        if self.shift != 0 {
+            self.shadow = next;
+            *frequency = next;
+            // and it checks once more with the new frequency
+            return self.next_frequency() <= MAX_FREQUENCY;
+        }
+        true
+    }
+}
+
+pub(super) struct Square {
+    enabled: bool,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    timer: u32,
+    length: Length,
+    envelope: Envelope,
+    /// Only channel 1 has one
+    sweep: Option<Sweep>,
+}
+
+impl Square {
+    pub(super) fn new(with_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            duty: 0,
+            duty_step: 0,
+            frequency: 0,
+            timer: 0,
+            length: Length::new(64),
+            envelope: Envelope::new(),
+            sweep: with_sweep.then(Sweep::new),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// T-cycles per duty step
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 4
+    }
+
+    /// Write to NRx0-NRx4, by `register` number
+    pub(super) fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.write(value);
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(value as u16 & 0x3F);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (value as u16 & 0b111) << 8;
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("NRx{register} is not a square channel register"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.timer = self.period();
+        self.envelope.trigger();
+        if let Some(sweep) = &mut self.sweep {
+            if !sweep.trigger(self.frequency) {
+                self.enabled = false;
+            }
+        }
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        let period = self.period();
+        let ticks = run_timer(&mut self.timer, cycles, period);
+        self.duty_step = ((self.duty_step as u32 + ticks) % 8) as u8;
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub(super) fn clock_sweep(&mut self) {
+        if let Some(sweep) = &mut self.sweep {
+            if !sweep.clock(&mut self.frequency) {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// The digital output (0-15), `None` while the channel or its DAC is off
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.enabled || !self.envelope.dac_enabled() {
+            return None;
+        }
+        let high = DUTY_PATTERNS[self.duty as usize] >> self.duty_step & 1;
+        Some(high * self.envelope.volume())
+    }
+}
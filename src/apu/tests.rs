@@ -0,0 +1,190 @@
+use super::*;
+
+const NR11_ADDRESS: u16 = 0xFF11;
+const NR12_ADDRESS: u16 = 0xFF12;
+const NR13_ADDRESS: u16 = 0xFF13;
+const NR22_ADDRESS: u16 = 0xFF17;
+const NR32_ADDRESS: u16 = 0xFF1C;
+const NR42_ADDRESS: u16 = 0xFF21;
+const NR43_ADDRESS: u16 = 0xFF22;
+
+fn powered_apu() -> Apu {
+    let mut apu = Apu::new();
+    apu.write_register(NR52_ADDRESS, NR52_POWER);
+    // everything to both sides at full volume
+    apu.write_register(NR50_ADDRESS, 0x77);
+    apu.write_register(NR51_ADDRESS, 0xFF);
+    apu
+}
+
+/// Channel 2 at full volume, 50% duty and the given frequency
+fn trigger_square2(apu: &mut Apu, frequency: u16) {
+    apu.write_register(NR21_ADDRESS, 0b1000_0000);
+    apu.write_register(NR22_ADDRESS, 0xF0);
+    apu.write_register(NR21_ADDRESS + 2, frequency as u8);
+    apu.write_register(NR24_ADDRESS, 0x80 | (frequency >> 8) as u8);
+}
+
+#[test]
+fn registers_read_back_with_unused_bits_set() {
+    let mut apu = powered_apu();
+    apu.write_register(NR10_ADDRESS, 0x00);
+    apu.write_register(NR11_ADDRESS, 0x00);
+    apu.write_register(NR13_ADDRESS, 0x12);
+    apu.write_register(NR32_ADDRESS, 0x60);
+
+    assert_eq!(0x80, apu.read_register(NR10_ADDRESS));
+    assert_eq!(0x3F, apu.read_register(NR11_ADDRESS), "only the duty reads back");
+    assert_eq!(0xFF, apu.read_register(NR13_ADDRESS), "frequencies are write only");
+    assert_eq!(0xFF, apu.read_register(NR32_ADDRESS));
+    assert_eq!(0x77, apu.read_register(NR50_ADDRESS));
+    assert_eq!(0xFF, apu.read_register(0xFF27));
+    assert_eq!(0xF0, apu.read_register(NR52_ADDRESS));
+}
+
+#[test]
+fn power_off_clears_registers_and_ignores_writes() {
+    let mut apu = powered_apu();
+    trigger_square2(&mut apu, 0x700);
+    apu.write_register(WAVE_RAM_START, 0x12);
+
+    apu.write_register(NR52_ADDRESS, 0x00);
+    assert_eq!(0x70, apu.read_register(NR52_ADDRESS), "channels go off with the APU");
+    assert_eq!(0x00, apu.read_register(NR50_ADDRESS));
+    apu.write_register(NR50_ADDRESS, 0x77);
+    assert_eq!(0x00, apu.read_register(NR50_ADDRESS));
+
+    apu.write_register(WAVE_RAM_START + 1, 0x34);
+    assert_eq!(0x12, apu.read_register(WAVE_RAM_START), "wave RAM survives power off");
+    assert_eq!(0x34, apu.read_register(WAVE_RAM_START + 1));
+}
+
+#[test]
+fn trigger_turns_channels_on_unless_the_dac_is_off() {
+    let mut apu = powered_apu();
+    trigger_square2(&mut apu, 0x700);
+    assert_eq!(0xF2, apu.read_register(NR52_ADDRESS));
+
+    apu.write_register(NR22_ADDRESS, 0x00);
+    assert_eq!(0xF0, apu.read_register(NR52_ADDRESS), "turning the DAC off turns the channel off");
+
+    apu.write_register(NR42_ADDRESS, 0x08);
+    apu.write_register(NR44_ADDRESS, 0x80);
+    assert_eq!(0xF8, apu.read_register(NR52_ADDRESS), "envelope going up from 0 keeps the DAC on");
+
+    apu.write_register(NR30_ADDRESS, 0x00);
+    apu.write_register(NR34_ADDRESS, 0x80);
+    assert_eq!(0xF8, apu.read_register(NR52_ADDRESS));
+}
+
+#[test]
+fn length_counter_turns_the_channel_off() {
+    let mut apu = powered_apu();
+    trigger_square2(&mut apu, 0x700);
+    // 64 - 62 = 2 length clocks, with length enabled
+    apu.write_register(NR21_ADDRESS, 62);
+    apu.write_register(NR24_ADDRESS, 0x47);
+
+    // the length clocks are on frame sequencer steps 0 and 2, 8192 T-cycles each
+    apu.tick(8192);
+    assert_eq!(0xF2, apu.read_register(NR52_ADDRESS));
+    apu.tick(8192 * 2);
+    assert_eq!(0xF0, apu.read_register(NR52_ADDRESS));
+}
+
+#[test]
+fn sweep_overflow_turns_channel_1_off() {
+    let mut apu = powered_apu();
+    apu.write_register(NR12_ADDRESS, 0xF0);
+    // shift 1 up from 0x600: 0x600 + 0x300 overflows right on trigger
+    apu.write_register(NR10_ADDRESS, 0x11);
+    apu.write_register(NR13_ADDRESS, 0x00);
+    apu.write_register(NR14_ADDRESS, 0x86);
+    assert_eq!(0xF0, apu.read_register(NR52_ADDRESS));
+
+    // from 0x400 the first sweep goes to 0x600 and then the check after it overflows
+    apu.write_register(NR14_ADDRESS, 0x84);
+    assert_eq!(0xF1, apu.read_register(NR52_ADDRESS));
+    apu.tick(8192 * 3);
+    assert_eq!(0xF0, apu.read_register(NR52_ADDRESS));
+}
+
+#[test]
+fn envelope_steps_the_volume() {
+    let mut apu = powered_apu();
+    // volume 2, going down every envelope clock
+    apu.write_register(NR42_ADDRESS, 0x21);
+    apu.write_register(NR44_ADDRESS, 0x80);
+    assert_eq!(2, apu.noise.envelope_volume());
+
+    // envelopes are clocked on step 7
+    apu.tick(8192 * 8);
+    assert_eq!(1, apu.noise.envelope_volume());
+    apu.tick(8192 * 8 * 4);
+    assert_eq!(0, apu.noise.envelope_volume());
+}
+
+#[test]
+fn seven_bit_noise_repeats_every_127_shifts() {
+    let mut apu = powered_apu();
+    apu.write_register(NR42_ADDRESS, 0xF0);
+    // the shortest period: divisor 8, no shift, 7-bit mode
+    apu.write_register(NR43_ADDRESS, 0x08);
+    apu.write_register(NR44_ADDRESS, 0x80);
+
+    let mut outputs = Vec::new();
+    for _ in 0..127 * 2 {
+        apu.tick(8);
+        outputs.push(apu.noise.output().unwrap());
+    }
+    assert_eq!(outputs[..127], outputs[127..]);
+    assert!(outputs.contains(&0) && outputs.contains(&15));
+}
+
+#[test]
+fn square_duty_sets_the_high_time() {
+    let mut apu = powered_apu();
+    // 0x700: (2048 - 0x700) * 4 = 1024 T-cycles per duty step
+    trigger_square2(&mut apu, 0x700);
+
+    let mut high = 0;
+    for _ in 0..8 {
+        apu.tick(1024);
+        if apu.square2.output() == Some(15) {
+            high += 1;
+        }
+    }
+    assert_eq!(4, high, "50% duty");
+}
+
+#[test]
+fn no_samples_without_a_sample_rate() {
+    let mut apu = powered_apu();
+    trigger_square2(&mut apu, 0x700);
+    apu.tick(T_CYCLES_PER_SECOND as u32 / 10);
+    assert!(apu.take_samples().is_empty());
+}
+
+#[test]
+fn samples_come_at_the_sample_rate_in_stereo() {
+    let mut apu = powered_apu();
+    apu.set_sample_rate(Some(32_768));
+    apu.tick(T_CYCLES_PER_SECOND as u32 / 64);
+
+    assert_eq!(512 * 2, apu.take_samples().len());
+    assert!(apu.take_samples().is_empty(), "taking samples empties the buffer");
+}
+
+#[test]
+fn panning_picks_the_sides() {
+    let mut apu = powered_apu();
+    apu.set_sample_rate(Some(48_000));
+    // channel 2 on the left only
+    apu.write_register(NR51_ADDRESS, 0x20);
+    trigger_square2(&mut apu, 0x700);
+    apu.tick(T_CYCLES_PER_SECOND as u32 / 100);
+
+    let samples = apu.take_samples();
+    assert!(samples.chunks(2).all(|frame| frame[1] == 0), "nothing on the right");
+    assert!(samples.chunks(2).any(|frame| frame[0] > 0) && samples.chunks(2).any(|frame| frame[0] < 0));
+}
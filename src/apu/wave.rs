@@ -0,0 +1,117 @@
+//! Channel 3: plays the 32 4-bit samples in wave RAM (0xFF30-0xFF3F, upper nibble first).
+//!
+//! ```text
+//! NR30 | 7 DAC on
+//! NR31 | length (256 - n)
+//! NR32 | 6-5 volume (00: mute, 01: 100%, 10: 50%, 11: 25%)
+//! NR33 | frequency low 8 bits
+//! NR34 | 7 trigger, 6 length enable, 2-0 frequency high 3 bits
+//! ```
+
+use super::channel::{ run_timer, Length };
+
+pub(super) const WAVE_RAM_SIZE: usize = 16;
+
+pub(super) struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    /// Which of the 32 samples is playing
+    position: u8,
+    length: Length,
+    ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Wave {
+    pub(super) fn new() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            length: Length::new(256),
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    /// Turning the APU off leaves wave RAM alone
+    pub(super) fn power_off(&mut self) {
+        *self = Self { ram: self.ram, ..Self::new() };
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// T-cycles per sample
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    pub(super) fn read_ram(&self, index: u16) -> u8 {
+        self.ram[index as usize]
+    }
+
+    pub(super) fn write_ram(&mut self, index: u16, value: u8) {
+        self.ram[index as usize] = value;
+    }
+
+    /// Write to NR30-NR34, by `register` number
+    pub(super) fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value as u16),
+            2 => self.volume_code = (value >> 5) & 0b11,
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (value as u16 & 0b111) << 8;
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!("NR3{register} is not a wave channel register"),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        let period = self.period();
+        let ticks = run_timer(&mut self.timer, cycles, period);
+        self.position = ((self.position as u32 + ticks) % 32) as u8;
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// The digital output (0-15), `None` while the channel or its DAC is off
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.enabled || !self.dac_enabled {
+            return None;
+        }
+        let byte = self.ram[self.position as usize / 2];
+        let sample = if self.position.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+        Some(match self.volume_code {
+            0 => 0,
+            code => sample >> (code - 1),
+        })
+    }
+}
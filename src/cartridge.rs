@@ -0,0 +1,254 @@
+//! The cartridge in the slot: its ROM, its external RAM, and the memory bank controller
+//! between them and the memory bus.
+//!
+//! The bus maps a [`Cartridge`] at 0x0000-0x7FFF (ROM) and 0xA000-0xBFFF (external RAM) and
+//! hands it addresses relative to the start of those regions.
+
+mod header;
+mod mbc;
+mod rtc;
+#[cfg(test)]
+mod tests;
+
+pub use header::*;
+pub use mbc::*;
+pub use rtc::{ TimeSource, SystemTimeSource };
+
+use std::path::Path;
+
+/// What fills out an image smaller than its header says, the byte ROMs are usually padded with
+const ROM_PADDING: u8 = 0xFF;
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The file couldn't be read
+    Io(std::io::Error),
+    /// The image (of the given length) is too small to even hold a header
+    TooSmall(usize),
+    /// The header checksum at 0x014D doesn't match the header
+    HeaderChecksumMismatch { expected: u8, computed: u8 },
+    /// The global checksum at 0x014E-0x014F doesn't match the ROM
+    GlobalChecksumMismatch { expected: u16, computed: u16 },
+    /// The ROM size code at 0x0148 isn't one of the defined ones
+    InvalidRomSize(u8),
+    /// The RAM size code at 0x0149 isn't one of the defined ones
+    InvalidRamSize(u8),
+    /// The cartridge type byte at 0x0147 names a controller this emulator doesn't implement
+    UnsupportedCartridgeType(u8),
+    /// Save data doesn't fit the cartridge: it should be the RAM size, plus the clock block on
+    /// cartridges with a clock
+    SaveSizeMismatch { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CartridgeError::Io(err) => write!(f, "Couldn't read the ROM: {err}"),
+            CartridgeError::TooSmall(len) => write!(f, "ROM is {len} bytes which is too small to contain a header"),
+            CartridgeError::HeaderChecksumMismatch { expected, computed } => {
+                write!(f, "Header checksum is {expected:#04X} but the header sums to {computed:#04X}")
+            }
+            CartridgeError::GlobalChecksumMismatch { expected, computed } => {
+                write!(f, "Global checksum is {expected:#06X} but the ROM sums to {computed:#06X}")
+            }
+            CartridgeError::InvalidRomSize(code) => write!(f, "ROM size code {code:#04X} is invalid"),
+            CartridgeError::InvalidRamSize(code) => write!(f, "RAM size code {code:#04X} is invalid"),
+            CartridgeError::UnsupportedCartridgeType(code) => write!(f, "Cartridge type {code:#04X} is not supported"),
+            CartridgeError::SaveSizeMismatch { expected, actual } => {
+                write!(f, "Save data is {actual} bytes but this cartridge saves {expected} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CartridgeError {
+    fn from(err: std::io::Error) -> Self {
+        CartridgeError::Io(err)
+    }
+}
+
+pub struct Cartridge {
+    header: CartridgeHeader,
+    controller: MemoryBankController,
+    rom: Vec<u8>,
+    /// External RAM, sized by the header (or the controller if the RAM is built into it)
+    ram: Vec<u8>,
+    /// Wall-clock time for the real-time clock's save timestamps
+    time_source: Box<dyn TimeSource>,
+    /// Whether RAM (or the clock) was written since the save data was last marked as saved
+    unsaved_changes: bool,
+}
+
+impl Cartridge {
+    /// Loads a `.gb`/`.gbc` file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        Self::from_vec(std::fs::read(path)?)
+    }
+
+    /// Loads a ROM image from memory
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CartridgeError> {
+        Self::from_vec(bytes.to_vec())
+    }
+
+    fn from_vec(mut rom: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = CartridgeHeader::parse(&rom)?;
+
+        // trimmed and overdumped images are common and the hardware doesn't care what size the
+        // header says, so rather than refusing them the image is padded out to the header's size,
+        // or to a power of two when it's bigger as the bank mapping wraps around the ROM
+        let size = header.rom_size.max(rom.len().next_power_of_two());
+        rom.resize(size, ROM_PADDING);
+
+        let controller = MemoryBankController::from_header(&header, &rom)?;
+        let ram = vec![0; controller.ram_size(&header)];
+
+        Ok(Self { header, controller, rom, ram, time_source: Box::new(SystemTimeSource), unsaved_changes: false })
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// Checks the global checksum, which real hardware ignores (so loading doesn't enforce it)
+    pub fn verify_global_checksum(&self) -> Result<(), CartridgeError> {
+        let computed = CartridgeHeader::compute_global_checksum(&self.rom);
+        if computed == self.header.global_checksum {
+            Ok(())
+        } else {
+            Err(CartridgeError::GlobalChecksumMismatch { expected: self.header.global_checksum, computed })
+        }
+    }
+
+    pub fn controller(&self) -> &MemoryBankController {
+        &self.controller
+    }
+
+    /// Whether the game is running the rumble motor (only MBC5 rumble cartridges have one)
+    pub fn rumble_active(&self) -> bool {
+        match &self.controller {
+            MemoryBankController::Mbc5(mbc) => mbc.rumble_active(),
+            _ => false,
+        }
+    }
+
+    /// Replaces where wall-clock time comes from (the host's clock by default)
+    pub fn set_time_source(&mut self, time_source: impl TimeSource + 'static) {
+        self.time_source = Box::new(time_source);
+    }
+
+    /// Runs the cartridge's own hardware (the real-time clock) for `t_cycles` of emulated time
+    pub fn tick(&mut self, t_cycles: u32) {
+        self.controller.tick(t_cycles);
+    }
+
+    fn rtc(&self) -> Option<&rtc::Rtc> {
+        match &self.controller {
+            MemoryBankController::Mbc3(mbc) => mbc.rtc(),
+            _ => None,
+        }
+    }
+
+    /// Whether the cartridge keeps its RAM (and clock) alive with a battery, i.e. whether it has anything to save
+    pub fn has_battery(&self) -> bool {
+        self.header.cartridge_type.has_battery
+    }
+
+    /// Whether the game wrote to RAM or the clock since [`Cartridge::mark_saved`]
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.unsaved_changes
+    }
+
+    /// Notes that the current [`Cartridge::save_data`] made it somewhere safe
+    pub fn mark_saved(&mut self) {
+        self.unsaved_changes = false;
+    }
+
+    /// The state a battery keeps alive, in the layout other emulators use for `.sav` files:
+    /// the external RAM, followed by the clock (stamped with the current time) if there is one
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = self.rtc() {
+            data.extend_from_slice(&rtc.to_save_bytes(self.time_source.unix_time()));
+        }
+        data
+    }
+
+    /// Restores what [`Cartridge::save_data`] produced. The clock catches up on the time passed
+    /// since it was saved; a save without the clock block leaves the clock alone.
+    pub fn load_save_data(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        let ram_size = self.ram.len();
+        let has_rtc = self.rtc().is_some();
+        let size_mismatch = || CartridgeError::SaveSizeMismatch {
+            expected: if has_rtc { ram_size + rtc::SAVE_SIZE } else { ram_size },
+            actual: data.len(),
+        };
+
+        if data.len() < ram_size || (!has_rtc && data.len() != ram_size) {
+            return Err(size_mismatch());
+        }
+        let (ram, clock) = data.split_at(ram_size);
+
+        if !clock.is_empty() {
+            let now = self.time_source.unix_time();
+            let rtc = rtc::Rtc::from_save_bytes(clock, now).ok_or_else(size_mismatch)?;
+            if let MemoryBankController::Mbc3(mbc) = &mut self.controller {
+                *mbc.rtc_mut().expect("checked the cartridge has a clock") = rtc;
+            }
+        }
+        self.ram.copy_from_slice(ram);
+        Ok(())
+    }
+
+    /// Reads from the ROM area through the controller's bank mapping, `address` being 0x0000-0x7FFF
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.controller.read_rom(&self.rom, address)
+    }
+
+    /// Writes to the ROM area, `address` being 0x0000-0x7FFF. These never change ROM, they
+    /// program the memory bank controller.
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        self.controller.write_rom(address, value);
+    }
+
+    /// Reads from external RAM through the controller, `address` being relative to 0xA000
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.controller.read_ram(&self.ram, address)
+    }
+
+    /// Writes to external RAM through the controller, `address` being relative to 0xA000
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        // writes to disabled RAM count too, it's not worth asking the controller for a maybe-extra save
+        self.unsaved_changes = true;
+        self.controller.write_ram(&mut self.ram, address, value);
+    }
+}
+
+/// Fills in the header of a raw ROM image (type, sizes, destination and checksums) so tests
+/// can build cartridges out of hand written programs. The ROM size code comes from `rom.len()`,
+/// which must be 32 KiB times a power of two.
+#[cfg(test)]
+pub(crate) fn with_valid_header(mut rom: Vec<u8>, cartridge_type: u8, ram_size_code: u8) -> Vec<u8> {
+    let rom_size_code = (rom.len() / (2 * ROM_BANK_SIZE)).trailing_zeros() as u8;
+    assert_eq!((2 * ROM_BANK_SIZE) << rom_size_code, rom.len(), "test ROM has an invalid size");
+
+    rom[0x0147] = cartridge_type;
+    rom[0x0148] = rom_size_code;
+    rom[0x0149] = ram_size_code;
+    rom[0x014A] = 0x01;
+    rom[0x014D] = CartridgeHeader::compute_header_checksum(&rom);
+
+    let [high, low] = CartridgeHeader::compute_global_checksum(&rom).to_be_bytes();
+    rom[0x014E] = high;
+    rom[0x014F] = low;
+
+    rom
+}
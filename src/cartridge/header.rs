@@ -0,0 +1,218 @@
+//! Parsing of the cartridge header, the metadata at 0x0100-0x014F of every ROM.
+
+use super::CartridgeError;
+
+/// Last byte of the header, so a ROM must be at least this + 1 bytes long to have one
+pub const HEADER_END: usize = 0x014F;
+
+const TITLE_START: usize = 0x0134;
+/// On CGB era cartridges the title shrinks to make room for the manufacturer code and CGB flag
+const TITLE_END_OLD: usize = 0x0143;
+const TITLE_END_CGB: usize = 0x013E;
+const CGB_FLAG_ADDRESS: usize = 0x0143;
+const SGB_FLAG_ADDRESS: usize = 0x0146;
+const CARTRIDGE_TYPE_ADDRESS: usize = 0x0147;
+const ROM_SIZE_ADDRESS: usize = 0x0148;
+const RAM_SIZE_ADDRESS: usize = 0x0149;
+const DESTINATION_CODE_ADDRESS: usize = 0x014A;
+const HEADER_CHECKSUM_ADDRESS: usize = 0x014D;
+const GLOBAL_CHECKSUM_ADDRESS: usize = 0x014E;
+
+/// Every ROM bank is 16 KiB
+pub const ROM_BANK_SIZE: usize = 0x4000;
+/// Every external RAM bank is 8 KiB
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The parsed cartridge header
+#[cfg_attr(test, derive(Debug))]
+pub struct CartridgeHeader {
+    /// The game's title in upper case ASCII
+    pub title: String,
+    pub cgb_support: CgbSupport,
+    /// Whether the game supports Super Game Boy functions
+    pub sgb_support: bool,
+    pub cartridge_type: CartridgeType,
+    /// ROM size in bytes
+    pub rom_size: usize,
+    /// External RAM size in bytes (MBC2's built-in RAM is not counted here, it reports 0)
+    pub ram_size: usize,
+    pub destination: Destination,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+/// Value of the CGB flag (0x0143)
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum CgbSupport {
+    /// A DMG game (the byte is part of the title)
+    None,
+    /// 0x80 -- works on DMG but has CGB enhancements
+    Compatible,
+    /// 0xC0 -- only runs on CGB
+    Exclusive,
+}
+
+/// Value of the destination code (0x014A)
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Destination {
+    /// 0x00
+    Japanese,
+    /// 0x01
+    Overseas,
+    /// Anything else. The hardware never looks at this byte, so homebrew and test ROMs with junk
+    /// in it still load.
+    Unknown(u8),
+}
+
+/// The memory bank controller family a cartridge type byte names
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum ControllerKind {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    /// Anything this emulator doesn't implement (MMM01, MBC6, MBC7, HuC1, HuC3, the camera, ...)
+    Unsupported,
+}
+
+/// The cartridge type byte (0x0147) split into the hardware it describes
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub struct CartridgeType {
+    /// The raw byte from the header
+    pub code: u8,
+    pub controller: ControllerKind,
+    pub has_ram: bool,
+    pub has_battery: bool,
+    pub has_timer: bool,
+    pub has_rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Self {
+        use ControllerKind::*;
+
+        // (controller, ram, battery, timer, rumble)
+        let (controller, has_ram, has_battery, has_timer, has_rumble) = match code {
+            0x00 => (RomOnly, false, false, false, false),
+            0x01 => (Mbc1, false, false, false, false),
+            0x02 => (Mbc1, true, false, false, false),
+            0x03 => (Mbc1, true, true, false, false),
+            // MBC2's RAM is inside the controller itself
+            0x05 => (Mbc2, true, false, false, false),
+            0x06 => (Mbc2, true, true, false, false),
+            0x08 => (RomOnly, true, false, false, false),
+            0x09 => (RomOnly, true, true, false, false),
+            0x0F => (Mbc3, false, true, true, false),
+            0x10 => (Mbc3, true, true, true, false),
+            0x11 => (Mbc3, false, false, false, false),
+            0x12 => (Mbc3, true, false, false, false),
+            0x13 => (Mbc3, true, true, false, false),
+            0x19 => (Mbc5, false, false, false, false),
+            0x1A => (Mbc5, true, false, false, false),
+            0x1B => (Mbc5, true, true, false, false),
+            0x1C => (Mbc5, false, false, false, true),
+            0x1D => (Mbc5, true, false, false, true),
+            0x1E => (Mbc5, true, true, false, true),
+            _ => (Unsupported, false, false, false, false),
+        };
+
+        Self { code, controller, has_ram, has_battery, has_timer, has_rumble }
+    }
+}
+
+impl CartridgeHeader {
+    /// Parses the header out of a full ROM image.
+    ///
+    /// Only the header is validated here (its checksum, and that its size codes exist);
+    /// checking the header against the rest of the image is up to the caller.
+    pub fn parse(rom: &[u8]) -> Result<Self, CartridgeError> {
+        if rom.len() <= HEADER_END {
+            return Err(CartridgeError::TooSmall(rom.len()));
+        }
+
+        let header_checksum = rom[HEADER_CHECKSUM_ADDRESS];
+        let computed = Self::compute_header_checksum(rom);
+        if computed != header_checksum {
+            return Err(CartridgeError::HeaderChecksumMismatch { expected: header_checksum, computed });
+        }
+
+        let cgb_support = match rom[CGB_FLAG_ADDRESS] {
+            0xC0 => CgbSupport::Exclusive,
+            // bit 6 is ignored by the CGB, so only bit 7 decides compatibility
+            flag if flag & 0x80 != 0 => CgbSupport::Compatible,
+            _ => CgbSupport::None,
+        };
+
+        let title_end = match cgb_support {
+            CgbSupport::None => TITLE_END_OLD,
+            CgbSupport::Compatible | CgbSupport::Exclusive => TITLE_END_CGB,
+        };
+        let title = rom[TITLE_START..=title_end]
+            .iter()
+            .take_while(|byte| **byte != 0x00)
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '?' })
+            .collect();
+
+        let rom_size = match rom[ROM_SIZE_ADDRESS] {
+            // 32 KiB << code, i.e. 2 << code banks
+            code @ 0x00..=0x08 => (2 * ROM_BANK_SIZE) << code,
+            code => return Err(CartridgeError::InvalidRomSize(code)),
+        };
+
+        let ram_size = match rom[RAM_SIZE_ADDRESS] {
+            0x00 => 0,
+            // unofficial, used by a few homebrew/PD ROMs
+            0x01 => 0x800,
+            0x02 => RAM_BANK_SIZE,
+            0x03 => 4 * RAM_BANK_SIZE,
+            0x04 => 16 * RAM_BANK_SIZE,
+            0x05 => 8 * RAM_BANK_SIZE,
+            code => return Err(CartridgeError::InvalidRamSize(code)),
+        };
+
+        let destination = match rom[DESTINATION_CODE_ADDRESS] {
+            0x00 => Destination::Japanese,
+            0x01 => Destination::Overseas,
+            code => Destination::Unknown(code),
+        };
+
+        Ok(Self {
+            title,
+            cgb_support,
+            sgb_support: rom[SGB_FLAG_ADDRESS] == 0x03,
+            cartridge_type: CartridgeType::from_code(rom[CARTRIDGE_TYPE_ADDRESS]),
+            rom_size,
+            ram_size,
+            destination,
+            header_checksum,
+            // the only big endian value in the header
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM_ADDRESS], rom[GLOBAL_CHECKSUM_ADDRESS + 1]]),
+        })
+    }
+
+    /// The checksum over 0x0134-0x014C the boot ROM verifies (and locks up on if it doesn't match)
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[TITLE_START..HEADER_CHECKSUM_ADDRESS]
+            .iter()
+            .fold(0_u8, |checksum, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+    }
+
+    /// The 16-bit sum of every byte of the ROM except the global checksum's own two bytes.
+    /// Real hardware never verifies this.
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|(address, _)| *address != GLOBAL_CHECKSUM_ADDRESS && *address != GLOBAL_CHECKSUM_ADDRESS + 1)
+            .fold(0_u16, |checksum, (_, byte)| checksum.wrapping_add(*byte as u16))
+    }
+
+    /// Number of 16 KiB ROM banks
+    pub fn rom_banks(&self) -> usize {
+        self.rom_size / ROM_BANK_SIZE
+    }
+}
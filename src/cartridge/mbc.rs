@@ -0,0 +1,152 @@
+//! Memory bank controllers (MBCs), the chips on a cartridge that decide which ROM/RAM bank
+//! the CPU sees. Writes into the ROM area program them, and reads go through their current mapping.
+//!
+//! Each controller only holds its own registers; the ROM and RAM live in the [`Cartridge`]
+//! and are handed in on every access.
+//!
+//! [`Cartridge`]: super::Cartridge
+
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+#[cfg(test)]
+mod tests;
+
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+
+use super::{ CartridgeError, CartridgeHeader, ControllerKind, ROM_BANK_SIZE, RAM_BANK_SIZE };
+
+/// What reads of disabled or missing cartridge RAM return
+const OPEN_BUS_READ_VALUE: u8 = 0xFF;
+
+/// The controller on a cartridge, chosen by the cartridge type byte of its header
+pub enum MemoryBankController {
+    /// No controller, 32 KiB of ROM (and optionally 8 KiB of RAM) wired straight to the bus
+    RomOnly,
+    Mbc1(Mbc1),
+    Mbc2(Mbc2),
+    Mbc3(Mbc3),
+    Mbc5(Mbc5),
+}
+
+impl MemoryBankController {
+    /// Picks the controller the header describes. `rom` is needed to tell MBC1 multicarts apart.
+    pub fn from_header(header: &CartridgeHeader, rom: &[u8]) -> Result<Self, CartridgeError> {
+        Ok(match header.cartridge_type.controller {
+            ControllerKind::RomOnly => MemoryBankController::RomOnly,
+            ControllerKind::Mbc1 => MemoryBankController::Mbc1(Mbc1::new(Mbc1::is_multicart(rom))),
+            ControllerKind::Mbc2 => MemoryBankController::Mbc2(Mbc2::new()),
+            ControllerKind::Mbc3 => MemoryBankController::Mbc3(Mbc3::new(header.cartridge_type.has_timer)),
+            ControllerKind::Mbc5 => MemoryBankController::Mbc5(Mbc5::new(header.cartridge_type.has_rumble)),
+            ControllerKind::Unsupported => {
+                return Err(CartridgeError::UnsupportedCartridgeType(header.cartridge_type.code));
+            }
+        })
+    }
+
+    /// How much RAM the cartridge needs allocated; this differs from the header for MBC2 as its
+    /// RAM is inside the controller and the header reports none
+    pub fn ram_size(&self, header: &CartridgeHeader) -> usize {
+        match self {
+            MemoryBankController::Mbc2(_) => mbc2::RAM_SIZE,
+            _ => header.ram_size,
+        }
+    }
+
+    /// Runs anything on the cartridge that keeps time on its own (MBC3's clock) for `t_cycles`
+    pub fn tick(&mut self, t_cycles: u32) {
+        if let MemoryBankController::Mbc3(mbc) = self {
+            mbc.tick(t_cycles);
+        }
+    }
+
+    /// Reads from the ROM area, `address` being 0x0000-0x7FFF
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match self {
+            MemoryBankController::RomOnly => rom.get(address as usize).copied().unwrap_or(OPEN_BUS_READ_VALUE),
+            MemoryBankController::Mbc1(mbc) => mbc.read_rom(rom, address),
+            MemoryBankController::Mbc2(mbc) => mbc.read_rom(rom, address),
+            MemoryBankController::Mbc3(mbc) => mbc.read_rom(rom, address),
+            MemoryBankController::Mbc5(mbc) => mbc.read_rom(rom, address),
+        }
+    }
+
+    /// Writes to the ROM area (i.e. programs the controller), `address` being 0x0000-0x7FFF
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match self {
+            MemoryBankController::RomOnly => {}
+            MemoryBankController::Mbc1(mbc) => mbc.write_rom(address, value),
+            MemoryBankController::Mbc2(mbc) => mbc.write_rom(address, value),
+            MemoryBankController::Mbc3(mbc) => mbc.write_rom(address, value),
+            MemoryBankController::Mbc5(mbc) => mbc.write_rom(address, value),
+        }
+    }
+
+    /// Reads from external RAM, `address` being relative to 0xA000
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match self {
+            MemoryBankController::RomOnly => ram_byte(ram, 0, address).copied().unwrap_or(OPEN_BUS_READ_VALUE),
+            MemoryBankController::Mbc1(mbc) => mbc.read_ram(ram, address),
+            MemoryBankController::Mbc2(mbc) => mbc.read_ram(ram, address),
+            MemoryBankController::Mbc3(mbc) => mbc.read_ram(ram, address),
+            MemoryBankController::Mbc5(mbc) => mbc.read_ram(ram, address),
+        }
+    }
+
+    /// Writes to external RAM, `address` being relative to 0xA000
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        match self {
+            MemoryBankController::RomOnly => {
+                if let Some(byte) = ram_byte_mut(ram, 0, address) {
+                    *byte = value;
+                }
+            }
+            MemoryBankController::Mbc1(mbc) => mbc.write_ram(ram, address, value),
+            MemoryBankController::Mbc2(mbc) => mbc.write_ram(ram, address, value),
+            MemoryBankController::Mbc3(mbc) => mbc.write_ram(ram, address, value),
+            MemoryBankController::Mbc5(mbc) => mbc.write_ram(ram, address, value),
+        }
+    }
+}
+
+/// Reads `address` (0x0000-0x7FFF) with `bank` mapped into the 16 KiB window the address falls in.
+///
+/// ROM sizes are powers of two, so wrapping around the ROM is the same as the cartridge
+/// simply not wiring up the bank bits it doesn't need.
+fn rom_byte(rom: &[u8], bank: usize, address: u16) -> u8 {
+    if rom.is_empty() {
+        return OPEN_BUS_READ_VALUE;
+    }
+    let offset = bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE);
+    rom[offset % rom.len()]
+}
+
+/// The byte `address` (relative to 0xA000) points to with `bank` mapped in, wrapping around
+/// RAM smaller than the bank count implies. `None` if the cartridge has no RAM.
+fn ram_byte(ram: &[u8], bank: usize, address: u16) -> Option<&u8> {
+    if ram.is_empty() {
+        return None;
+    }
+    let offset = bank * RAM_BANK_SIZE + (address as usize % RAM_BANK_SIZE);
+    ram.get(offset % ram.len())
+}
+
+/// Mutable version of [`ram_byte`]
+fn ram_byte_mut(ram: &mut [u8], bank: usize, address: u16) -> Option<&mut u8> {
+    if ram.is_empty() {
+        return None;
+    }
+    let offset = bank * RAM_BANK_SIZE + (address as usize % RAM_BANK_SIZE);
+    let len = ram.len();
+    ram.get_mut(offset % len)
+}
+
+/// Whether a value written to a RAM enable register enables RAM (any value with 0xA in the
+/// lower nibble does, anything else disables it)
+fn enables_ram(value: u8) -> bool {
+    value & 0x0F == 0x0A
+}
@@ -0,0 +1,117 @@
+//! MBC1: up to 2 MiB of ROM and 32 KiB of RAM.
+//!
+//! Registers (selected by the address written to):
+//! ```text
+//! 0x0000-0x1FFF | RAM enable  | 0xA in the lower nibble enables RAM
+//! 0x2000-0x3FFF | BANK1       | 5-bit ROM bank number, 0 is treated as 1
+//! 0x4000-0x5FFF | BANK2       | 2 bits, upper ROM bank bits or the RAM bank
+//! 0x6000-0x7FFF | Mode        | 0: BANK2 only affects 0x4000-0x7FFF
+//!                               1: BANK2 also banks 0x0000-0x3FFF and RAM (large ROM/RAM mode)
+//! ```
+//!
+//! MBC1 multicarts (MBC1M) wire BANK2 one bit lower, so only 4 bits of BANK1 are used and
+//! BANK2 picks which of the four 256 KiB games is mapped.
+
+use super::*;
+
+/// The Nintendo logo every game has at 0x0104, which the boot ROM checks
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+const LOGO_OFFSET: usize = 0x0104;
+/// Multicarts are 1 MiB, i.e. four 256 KiB games of 16 banks each
+const MULTICART_ROM_SIZE: usize = 64 * ROM_BANK_SIZE;
+const MULTICART_GAME_BANKS: usize = 16;
+
+pub struct Mbc1 {
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    /// The banking mode select, true being mode 1
+    advanced_banking: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            bank1: 0x01,
+            bank2: 0x00,
+            advanced_banking: false,
+            multicart,
+        }
+    }
+
+    /// There is no header flag for multicarts; they are recognised by being 1 MiB and having
+    /// a second game (so a second Nintendo logo) starting at bank 0x10
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        let second_logo = MULTICART_GAME_BANKS * ROM_BANK_SIZE + LOGO_OFFSET;
+        rom.len() == MULTICART_ROM_SIZE
+            && rom[second_logo..second_logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+    }
+
+    /// How far BANK2 is shifted to form the upper ROM bank bits
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    /// The bank mapped at 0x0000-0x3FFF, which is only ever non-zero in mode 1
+    fn lower_rom_bank(&self) -> usize {
+        if self.advanced_banking {
+            (self.bank2 as usize) << self.bank2_shift()
+        } else {
+            0
+        }
+    }
+
+    /// The bank mapped at 0x4000-0x7FFF
+    fn upper_rom_bank(&self) -> usize {
+        let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+        ((self.bank2 as usize) << self.bank2_shift()) | bank1 as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.advanced_banking { self.bank2 as usize } else { 0 }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom_byte(rom, self.lower_rom_bank(), address),
+            _ => rom_byte(rom, self.upper_rom_bank(), address),
+        }
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = enables_ram(value),
+            0x2000..=0x3FFF => {
+                // the zero check looks at all 5 bits, even on multicarts where bit 4 isn't wired
+                self.bank1 = match value & 0x1F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            0x4000..=0x5FFF => self.bank2 = value & 0b11,
+            _ => self.advanced_banking = value & 0b1 == 1,
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_READ_VALUE;
+        }
+        ram_byte(ram, self.ram_bank(), address).copied().unwrap_or(OPEN_BUS_READ_VALUE)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(byte) = ram_byte_mut(ram, self.ram_bank(), address) {
+            *byte = value;
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! MBC2: up to 256 KiB of ROM and 512 half-bytes of RAM built into the controller.
+//!
+//! Both registers live at 0x0000-0x3FFF and bit 8 of the address picks which one is written:
+//! ```text
+//! bit 8 = 0 | RAM enable | 0xA in the lower nibble enables RAM
+//! bit 8 = 1 | ROM bank   | 4-bit ROM bank number, 0 is treated as 1
+//! ```
+//!
+//! Only the lower 9 bits of a RAM address are decoded, so the 512 bytes repeat across all of
+//! 0xA000-0xBFFF, and only the lower nibble of each byte exists.
+
+use super::*;
+
+/// 512 half-bytes, stored one per byte
+pub const RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self { ram_enabled: false, rom_bank: 0x01 }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address),
+        }
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF if address & 0x0100 == 0 => self.ram_enabled = enables_ram(value),
+            0x0000..=0x3FFF => {
+                self.rom_bank = match value & 0x0F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            // 0x4000-0x7FFF isn't connected to anything
+            _ => {}
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_READ_VALUE;
+        }
+        // the upper nibble isn't there, so it floats high
+        ram[address as usize % RAM_SIZE] | 0xF0
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if self.ram_enabled {
+            ram[address as usize % RAM_SIZE] = value & 0x0F;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! MBC3: up to 2 MiB of ROM, 32 KiB of RAM and optionally a real-time clock.
+//!
+//! Registers (selected by the address written to):
+//! ```text
+//! 0x0000-0x1FFF | RAM enable | 0xA in the lower nibble enables RAM (and the clock registers)
+//! 0x2000-0x3FFF | ROM bank   | 7-bit ROM bank number, 0 is treated as 1
+//! 0x4000-0x5FFF | RAM bank   | 0x00-0x03 map a RAM bank, 0x08-0x0C map a clock register
+//! 0x6000-0x7FFF | Latch      | writing 0x00 then 0x01 latches the clock
+//! ```
+
+use super::*;
+use crate::cartridge::rtc::{ Rtc, SECONDS_REGISTER, DAY_HIGH_REGISTER };
+
+pub struct Mbc3 {
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// Either a RAM bank or a clock register number
+    ram_bank: u8,
+    /// Only the MBC3+TIMER cartridge types have one
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(has_timer: bool) -> Self {
+        Self { ram_enabled: false, rom_bank: 0x01, ram_bank: 0x00, rtc: has_timer.then(Rtc::new) }
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+
+    /// Runs the clock (if there is one) for `t_cycles`
+    pub fn tick(&mut self, t_cycles: u32) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(t_cycles);
+        }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address),
+        }
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = enables_ram(value),
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x7F {
+                    0 => 1,
+                    bank => bank,
+                };
+            }
+            0x4000..=0x5FFF => self.ram_bank = value,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_READ_VALUE;
+        }
+        match (self.ram_bank, &self.rtc) {
+            (0x00..=0x03, _) => ram_byte(ram, self.ram_bank as usize, address).copied().unwrap_or(OPEN_BUS_READ_VALUE),
+            (SECONDS_REGISTER..=DAY_HIGH_REGISTER, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => OPEN_BUS_READ_VALUE,
+        }
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        match (self.ram_bank, &mut self.rtc) {
+            (0x00..=0x03, _) => {
+                if let Some(byte) = ram_byte_mut(ram, self.ram_bank as usize, address) {
+                    *byte = value;
+                }
+            }
+            (SECONDS_REGISTER..=DAY_HIGH_REGISTER, Some(rtc)) => rtc.write(self.ram_bank, value),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! MBC5: up to 8 MiB of ROM and 128 KiB of RAM.
+//!
+//! Registers (selected by the address written to):
+//! ```text
+//! 0x0000-0x1FFF | RAM enable    | 0xA in the lower nibble enables RAM
+//! 0x2000-0x2FFF | ROM bank low  | lower 8 bits of the 9-bit ROM bank number (bank 0 is allowed)
+//! 0x3000-0x3FFF | ROM bank high | bit 0 is bit 8 of the ROM bank number
+//! 0x4000-0x5FFF | RAM bank      | 0x00-0x0F; on rumble cartridges bit 3 drives the motor instead
+//! ```
+
+use super::*;
+
+pub struct Mbc5 {
+    ram_enabled: bool,
+    /// 9-bit ROM bank number
+    rom_bank: u16,
+    ram_bank: u8,
+    /// Rumble cartridges use bit 3 of the RAM bank register for the motor
+    has_rumble: bool,
+    rumble_active: bool,
+}
+
+impl Mbc5 {
+    pub fn new(has_rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x001,
+            ram_bank: 0x00,
+            has_rumble,
+            rumble_active: false,
+        }
+    }
+
+    /// Whether the game is currently running the rumble motor
+    pub fn rumble_active(&self) -> bool {
+        self.rumble_active
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address),
+        }
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = enables_ram(value),
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x0FF) | ((value as u16 & 0b1) << 8),
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    self.rumble_active = value & 0b1000 != 0;
+                    self.ram_bank = value & 0b0111;
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return OPEN_BUS_READ_VALUE;
+        }
+        ram_byte(ram, self.ram_bank as usize, address).copied().unwrap_or(OPEN_BUS_READ_VALUE)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(byte) = ram_byte_mut(ram, self.ram_bank as usize, address) {
+            *byte = value;
+        }
+    }
+}
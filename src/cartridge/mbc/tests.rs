@@ -0,0 +1,344 @@
+use crate::cartridge::{ Cartridge, with_valid_header };
+use super::*;
+
+/// A ROM of `banks` 16 KiB banks where the first two bytes of every bank hold its bank number
+/// (LS-byte first), so reading 0x0000/0x4000 tells which bank is mapped
+fn banked_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        let [low, high] = (bank as u16).to_le_bytes();
+        rom[bank * ROM_BANK_SIZE] = low;
+        rom[bank * ROM_BANK_SIZE + 1] = high;
+    }
+    rom
+}
+
+fn cartridge(rom: Vec<u8>, cartridge_type: u8, ram_size_code: u8) -> Cartridge {
+    Cartridge::from_bytes(&with_valid_header(rom, cartridge_type, ram_size_code)).expect("test ROM should load")
+}
+
+/// The bank mapped at 0x0000-0x3FFF and 0x4000-0x7FFF respectively
+fn mapped_banks(cartridge: &Cartridge) -> (u16, u16) {
+    let read_bank = |address| u16::from_le_bytes([cartridge.read_rom(address), cartridge.read_rom(address + 1)]);
+    (read_bank(0x0000), read_bank(0x4000))
+}
+
+#[test]
+fn unsupported_type_is_an_error() {
+    let rom = with_valid_header(banked_rom(2), 0xFC, 0x00);
+
+    assert!(matches!(Cartridge::from_bytes(&rom), Err(CartridgeError::UnsupportedCartridgeType(0xFC))));
+}
+
+#[test]
+fn rom_only_ignores_writes() {
+    let mut cartridge = cartridge(banked_rom(2), 0x00, 0x00);
+
+    cartridge.write_rom(0x2000, 0x05);
+
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+    assert!(matches!(cartridge.controller(), MemoryBankController::RomOnly));
+}
+
+#[test]
+fn mbc1_rom_banking() {
+    // 2 MiB
+    let mut cartridge = cartridge(banked_rom(128), 0x01, 0x00);
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+
+    cartridge.write_rom(0x2000, 0x05);
+    assert_eq!((0, 5), mapped_banks(&cartridge));
+
+    // bank 0 can't be selected in the upper window
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+
+    // only 5 bits are kept
+    cartridge.write_rom(0x3FFF, 0xE3);
+    assert_eq!((0, 3), mapped_banks(&cartridge));
+
+    // BANK2 supplies bits 5-6, and the 0 -> 1 fix only looks at BANK1 so 0x20 becomes 0x21
+    cartridge.write_rom(0x4000, 0x01);
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!((0, 0x21), mapped_banks(&cartridge));
+
+    // mode 1 also applies BANK2 to the lower window
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!((0x20, 0x21), mapped_banks(&cartridge));
+
+    cartridge.write_rom(0x4000, 0x03);
+    assert_eq!((0x60, 0x61), mapped_banks(&cartridge));
+}
+
+#[test]
+fn mbc1_bank_numbers_wrap_around_small_roms() {
+    // 256 KiB, so only bits 0-3 of the bank number are wired
+    let mut cartridge = cartridge(banked_rom(16), 0x01, 0x00);
+
+    cartridge.write_rom(0x2000, 0x12);
+
+    assert_eq!((0, 2), mapped_banks(&cartridge));
+}
+
+#[test]
+fn mbc1_ram_enable_and_banking() {
+    // MBC1+RAM+BATTERY with 32 KiB of RAM
+    let mut cartridge = cartridge(banked_rom(4), 0x03, 0x03);
+
+    cartridge.write_ram(0x0000, 0x12);
+    assert_eq!(0xFF, cartridge.read_ram(0x0000), "RAM is disabled at power on");
+
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0x0000, 0x12);
+    assert_eq!(0x12, cartridge.read_ram(0x0000));
+
+    // in mode 0 BANK2 doesn't touch RAM
+    cartridge.write_rom(0x4000, 0x02);
+    assert_eq!(0x12, cartridge.read_ram(0x0000));
+
+    // mode 1 banks RAM with BANK2
+    cartridge.write_rom(0x6000, 0x01);
+    assert_eq!(0x00, cartridge.read_ram(0x0000));
+    cartridge.write_ram(0x0000, 0x34);
+    cartridge.write_rom(0x4000, 0x00);
+    assert_eq!(0x12, cartridge.read_ram(0x0000));
+    cartridge.write_rom(0x4000, 0x02);
+    assert_eq!(0x34, cartridge.read_ram(0x0000));
+
+    // anything without 0xA in the lower nibble disables RAM again
+    cartridge.write_rom(0x1FFF, 0x00);
+    assert_eq!(0xFF, cartridge.read_ram(0x0000));
+}
+
+#[test]
+fn mbc1_multicart_wiring() {
+    let mut rom = banked_rom(64);
+    let logo = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+        0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+        0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E];
+    rom[0x0104..0x0134].copy_from_slice(&logo);
+    rom[0x40104..0x40134].copy_from_slice(&logo);
+    assert!(Mbc1::is_multicart(&rom));
+    assert!(!Mbc1::is_multicart(&banked_rom(64)), "a plain 1 MiB ROM isn't a multicart");
+
+    let mut cartridge = cartridge(rom, 0x01, 0x00);
+
+    // BANK2 is shifted by 4 instead of 5 and bit 4 of BANK1 is ignored
+    cartridge.write_rom(0x4000, 0x01);
+    cartridge.write_rom(0x2000, 0x13);
+    assert_eq!((0, 0x13), mapped_banks(&cartridge));
+
+    // mode 1 maps the first bank of the selected game at 0x0000
+    cartridge.write_rom(0x6000, 0x01);
+    cartridge.write_rom(0x4000, 0x02);
+    assert_eq!((0x20, 0x23), mapped_banks(&cartridge));
+
+    // 0x10 isn't 0, so no 0 -> 1 fix, even though only 0x00 of it is wired
+    cartridge.write_rom(0x2000, 0x10);
+    assert_eq!((0x20, 0x20), mapped_banks(&cartridge));
+}
+
+#[test]
+fn mbc2_register_select_and_rom_banking() {
+    let mut cartridge = cartridge(banked_rom(16), 0x05, 0x00);
+
+    // bit 8 set selects the ROM bank register
+    cartridge.write_rom(0x2100, 0x07);
+    assert_eq!((0, 7), mapped_banks(&cartridge));
+    cartridge.write_rom(0x0100, 0xF0);
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+
+    // bit 8 reset is RAM enable, which doesn't touch the ROM bank
+    cartridge.write_rom(0x2000, 0x0A);
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+    cartridge.write_ram(0x0000, 0x5A);
+    assert_eq!(0xFA, cartridge.read_ram(0x0000));
+}
+
+#[test]
+fn mbc2_built_in_half_byte_ram() {
+    // the header reports no RAM, the controller brings its own
+    let mut cartridge = cartridge(banked_rom(2), 0x06, 0x00);
+
+    cartridge.write_ram(0x0000, 0x0B);
+    assert_eq!(0xFF, cartridge.read_ram(0x0000), "RAM is disabled at power on");
+
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0x0000, 0xAB);
+    cartridge.write_ram(0x01FF, 0x0C);
+
+    assert_eq!(0xFB, cartridge.read_ram(0x0000), "only the lower nibble is stored");
+    assert_eq!(0xFC, cartridge.read_ram(0x01FF));
+    // 512 bytes repeat through the whole region
+    assert_eq!(0xFB, cartridge.read_ram(0x0200));
+    assert_eq!(0xFC, cartridge.read_ram(0x1FFF));
+}
+
+#[test]
+fn mbc3_rom_and_ram_banking() {
+    // 2 MiB, 32 KiB of RAM
+    let mut cartridge = cartridge(banked_rom(128), 0x13, 0x03);
+
+    cartridge.write_rom(0x2000, 0x7F);
+    assert_eq!((0, 0x7F), mapped_banks(&cartridge));
+    cartridge.write_rom(0x2000, 0x00);
+    assert_eq!((0, 1), mapped_banks(&cartridge));
+    cartridge.write_rom(0x2000, 0xC5);
+    assert_eq!((0, 0x45), mapped_banks(&cartridge));
+
+    cartridge.write_rom(0x0000, 0x0A);
+    for bank in 0..4 {
+        cartridge.write_rom(0x4000, bank);
+        cartridge.write_ram(0x1000, 0x10 + bank);
+    }
+    for bank in 0..4 {
+        cartridge.write_rom(0x4000, bank);
+        assert_eq!(0x10 + bank, cartridge.read_ram(0x1000));
+    }
+}
+
+#[test]
+fn mbc5_nine_bit_rom_bank() {
+    // 8 MiB
+    let mut cartridge = cartridge(banked_rom(512), 0x19, 0x00);
+
+    cartridge.write_rom(0x2000, 0xFF);
+    assert_eq!((0, 0xFF), mapped_banks(&cartridge));
+
+    cartridge.write_rom(0x3000, 0x01);
+    assert_eq!((0, 0x1FF), mapped_banks(&cartridge));
+
+    // unlike the other controllers bank 0 can be mapped at 0x4000
+    cartridge.write_rom(0x2000, 0x00);
+    cartridge.write_rom(0x3000, 0x00);
+    assert_eq!((0, 0), mapped_banks(&cartridge));
+
+    cartridge.write_rom(0x2FFF, 0x12);
+    cartridge.write_rom(0x3FFF, 0xFF);
+    assert_eq!((0, 0x112), mapped_banks(&cartridge));
+}
+
+#[test]
+fn mbc5_ram_banks_and_rumble() {
+    // MBC5+RUMBLE+RAM+BATTERY with 128 KiB of RAM
+    let mut cartridge = cartridge(banked_rom(4), 0x1E, 0x04);
+    cartridge.write_rom(0x0000, 0x0A);
+
+    cartridge.write_rom(0x4000, 0x03);
+    cartridge.write_ram(0x0000, 0x33);
+    assert!(!cartridge.rumble_active());
+
+    // bit 3 runs the motor instead of selecting a bank
+    cartridge.write_rom(0x4000, 0x0B);
+    assert!(cartridge.rumble_active());
+    assert_eq!(0x33, cartridge.read_ram(0x0000));
+
+    cartridge.write_rom(0x4000, 0x03);
+    assert!(!cartridge.rumble_active());
+
+    // without rumble all 4 bits select the bank
+    let mut cartridge = cartridge_without_rumble();
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_rom(0x4000, 0x0F);
+    cartridge.write_ram(0x0000, 0xF0);
+    cartridge.write_rom(0x4000, 0x07);
+    assert_eq!(0x00, cartridge.read_ram(0x0000));
+    cartridge.write_rom(0x4000, 0x0F);
+    assert_eq!(0xF0, cartridge.read_ram(0x0000));
+    assert!(!cartridge.rumble_active());
+}
+
+fn cartridge_without_rumble() -> Cartridge {
+    cartridge(banked_rom(4), 0x1B, 0x04)
+}
+
+/// A wall clock tests can move by hand
+#[derive(Clone)]
+struct FakeTimeSource(std::rc::Rc<std::cell::Cell<u64>>);
+
+impl crate::cartridge::TimeSource for FakeTimeSource {
+    fn unix_time(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+#[test]
+fn mbc3_clock_registers_through_the_ram_bank_register() {
+    // MBC3+TIMER+RAM+BATTERY
+    let mut cartridge = cartridge(banked_rom(4), 0x10, 0x03);
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0x0000, 0x77);
+
+    // seconds
+    cartridge.write_rom(0x4000, 0x08);
+    cartridge.write_ram(0x0000, 30);
+    cartridge.tick(4_194_304 * 2);
+    assert_eq!(0x00, cartridge.read_ram(0x0000), "nothing latched yet");
+
+    cartridge.write_rom(0x6000, 0x00);
+    cartridge.write_rom(0x6000, 0x01);
+    // every address in the window reads the selected register
+    assert_eq!(32, cartridge.read_ram(0x0000));
+    assert_eq!(32, cartridge.read_ram(0x1FFF));
+
+    // back to RAM
+    cartridge.write_rom(0x4000, 0x00);
+    assert_eq!(0x77, cartridge.read_ram(0x0000));
+
+    // 0x04-0x07 and 0x0D+ map nothing
+    cartridge.write_rom(0x4000, 0x0D);
+    assert_eq!(0xFF, cartridge.read_ram(0x0000));
+}
+
+#[test]
+fn mbc3_without_timer_has_no_clock() {
+    let mut cartridge = cartridge(banked_rom(4), 0x13, 0x03);
+    cartridge.write_rom(0x0000, 0x0A);
+
+    cartridge.write_rom(0x4000, 0x08);
+    cartridge.write_ram(0x0000, 30);
+
+    assert_eq!(0xFF, cartridge.read_ram(0x0000));
+    assert_eq!(0x2000 * 4, cartridge.save_data().len());
+}
+
+#[test]
+fn mbc3_clock_is_saved_with_ram_and_catches_up() {
+    let clock = FakeTimeSource(Default::default());
+    clock.0.set(1_600_000_000);
+
+    let mut cartridge = cartridge(banked_rom(4), 0x10, 0x03);
+    cartridge.set_time_source(clock.clone());
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0x0123, 0x45);
+    // minutes
+    cartridge.write_rom(0x4000, 0x09);
+    cartridge.write_ram(0x0000, 10);
+
+    let save = cartridge.save_data();
+    assert_eq!(0x2000 * 4 + 48, save.len(), "the clock goes after the RAM");
+
+    // the emulator is closed for an hour and a half
+    clock.0.set(1_600_000_000 + 90 * 60);
+    let mut reloaded = self::cartridge(banked_rom(4), 0x10, 0x03);
+    reloaded.set_time_source(clock.clone());
+    reloaded.load_save_data(&save).unwrap();
+
+    reloaded.write_rom(0x0000, 0x0A);
+    reloaded.write_rom(0x4000, 0x00);
+    assert_eq!(0x45, reloaded.read_ram(0x0123));
+    reloaded.write_rom(0x6000, 0x00);
+    reloaded.write_rom(0x6000, 0x01);
+    reloaded.write_rom(0x4000, 0x09);
+    assert_eq!(40, reloaded.read_ram(0x0000));
+    reloaded.write_rom(0x4000, 0x0A);
+    assert_eq!(1, reloaded.read_ram(0x0000));
+
+    // a save without the clock only restores RAM
+    let mut ram_only = self::cartridge(banked_rom(4), 0x10, 0x03);
+    ram_only.load_save_data(&save[..0x2000 * 4]).unwrap();
+    assert!(matches!(
+        ram_only.load_save_data(&save[..0x2000 * 4 + 12]),
+        Err(CartridgeError::SaveSizeMismatch { expected: 0x8030, actual: 0x800C })
+    ));
+}
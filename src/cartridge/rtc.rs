@@ -0,0 +1,261 @@
+//! The real-time clock some MBC3 cartridges carry (Pokémon Gold/Silver/Crystal and friends).
+//!
+//! It counts seconds, minutes, hours and a 9-bit day counter off its own crystal. The CPU sees
+//! it through the RAM bank register (0x08-0x0C select a clock register instead of a RAM bank),
+//! but it only ever reads a latched copy of the registers, which is refreshed by writing 0x00
+//! then 0x01 to 0x6000-0x7FFF. Writes go straight to the live registers.
+//!
+//! While the emulator runs, the clock is ticked from emulated cycles so it stays in step with the
+//! game (fast-forward included). Across sessions it is stored with a wall-clock timestamp, and
+//! catches up on the time that passed while the emulator was closed when it's loaded again.
+
+#[cfg(test)]
+mod tests;
+
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+/// The CPU's clock speed, which the clock is ticked by
+const T_CYCLES_PER_SECOND: u32 = 4_194_304;
+
+/// The register numbers written to the RAM bank register to select a clock register
+pub const SECONDS_REGISTER: u8 = 0x08;
+pub const MINUTES_REGISTER: u8 = 0x09;
+pub const HOURS_REGISTER: u8 = 0x0A;
+pub const DAY_LOW_REGISTER: u8 = 0x0B;
+pub const DAY_HIGH_REGISTER: u8 = 0x0C;
+
+/// Bits of the day high register
+const DAY_HIGH_DAY_BIT_8: u8 = 0b0000_0001;
+const DAY_HIGH_HALT: u8 = 0b0100_0000;
+const DAY_HIGH_CARRY: u8 = 0b1000_0000;
+
+/// Size of the clock block appended to `.sav` files: the live and latched registers as 10
+/// little endian u32s followed by a little endian u64 UNIX timestamp. This is the layout BGB,
+/// VBA-M, mGBA and SameBoy share.
+pub const SAVE_SIZE: usize = 48;
+/// Older VBA builds wrote a 32-bit timestamp
+const LEGACY_SAVE_SIZE: usize = 44;
+
+/// Where the current wall-clock time comes from, so tests can swap in a fake one
+pub trait TimeSource {
+    /// Seconds since the UNIX epoch
+    fn unix_time(&self) -> u64;
+}
+
+/// The host's clock
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn unix_time(&self) -> u64 {
+        // a clock set before 1970 just counts as 1970
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// The five clock registers
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    day_low: u8,
+    /// Bit 0 is bit 8 of the day counter, bit 6 halts the clock and bit 7 is the day counter's carry
+    day_high: u8,
+}
+
+impl RtcRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            SECONDS_REGISTER => self.seconds,
+            MINUTES_REGISTER => self.minutes,
+            HOURS_REGISTER => self.hours,
+            DAY_LOW_REGISTER => self.day_low,
+            DAY_HIGH_REGISTER => self.day_high,
+            _ => unreachable!("{register:#04X} is not a clock register"),
+        }
+    }
+
+    fn days(&self) -> u16 {
+        ((self.day_high & DAY_HIGH_DAY_BIT_8) as u16) << 8 | self.day_low as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        let [high, low] = days.to_be_bytes();
+        self.day_low = low;
+        self.day_high = (self.day_high & !DAY_HIGH_DAY_BIT_8) | (high & DAY_HIGH_DAY_BIT_8);
+    }
+
+    /// Whether every counter is in its normal range. The registers can be written with
+    /// anything that fits their bits, and out of range values count up to the register's
+    /// bit width before wrapping to 0 (without carrying into the next register).
+    fn in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /// Counts one second the way the hardware's counters do, out of range values included
+    fn tick_second(&mut self) {
+        let carried = count_up(&mut self.seconds, 60, 0x3F)
+            && count_up(&mut self.minutes, 60, 0x3F)
+            && count_up(&mut self.hours, 24, 0x1F);
+        if carried {
+            self.add_days(1);
+        }
+    }
+
+    /// Moves the clock forward by `seconds` at once, which is what catching up after a
+    /// session does (ticking a few months one second at a time would take a while)
+    fn advance(&mut self, mut seconds: u64) {
+        // out of range values don't follow the usual arithmetic, so tick through them first
+        while seconds > 0 && !self.in_range() {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let time_of_day = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + seconds;
+        self.seconds = (time_of_day % 60) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.hours = (time_of_day / 3600 % 24) as u8;
+        self.add_days(time_of_day / 86400);
+    }
+
+    /// Adds to the 9-bit day counter, setting the carry bit if it overflows. The carry stays
+    /// set until the game clears it.
+    fn add_days(&mut self, days: u64) {
+        let days = self.days() as u64 + days;
+        if days > 0x1FF {
+            self.day_high |= DAY_HIGH_CARRY;
+        }
+        self.set_days((days & 0x1FF) as u16);
+    }
+
+    fn to_save_words(self) -> [u32; 5] {
+        [self.seconds, self.minutes, self.hours, self.day_low, self.day_high].map(u32::from)
+    }
+
+    fn from_save_words(words: [u32; 5]) -> Self {
+        let [seconds, minutes, hours, day_low, day_high] = words.map(|word| word as u8);
+        Self {
+            seconds: seconds & 0x3F,
+            minutes: minutes & 0x3F,
+            hours: hours & 0x1F,
+            day_low,
+            day_high: day_high & (DAY_HIGH_DAY_BIT_8 | DAY_HIGH_HALT | DAY_HIGH_CARRY),
+        }
+    }
+}
+
+/// Increments a counter that carries at `limit`. Values past the limit (which the game can
+/// write) count up to `mask` and wrap to 0 without carrying. Returns whether it carried.
+fn count_up(counter: &mut u8, limit: u8, mask: u8) -> bool {
+    if *counter == limit - 1 {
+        *counter = 0;
+        true
+    } else {
+        *counter = (*counter + 1) & mask;
+        false
+    }
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Rtc {
+    /// The counting registers
+    live: RtcRegisters,
+    /// The copy the CPU reads
+    latched: RtcRegisters,
+    /// T-cycles into the current second
+    sub_second_cycles: u32,
+    /// Whether the last write to the latch register was 0x00, so a 0x01 would latch
+    latch_armed: bool,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            live: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            sub_second_cycles: 0,
+            latch_armed: false,
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.live.day_high & DAY_HIGH_HALT != 0
+    }
+
+    /// Advances the clock by `t_cycles` of emulated time
+    pub fn tick(&mut self, t_cycles: u32) {
+        if self.halted() {
+            return;
+        }
+        self.sub_second_cycles += t_cycles;
+        while self.sub_second_cycles >= T_CYCLES_PER_SECOND {
+            self.sub_second_cycles -= T_CYCLES_PER_SECOND;
+            self.live.tick_second();
+        }
+    }
+
+    /// A write to 0x6000-0x7FFF; writing 0x00 then 0x01 copies the live registers to the latched ones
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.latched = self.live;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Reads the latched copy of `register` (0x08-0x0C)
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    /// Writes the live `register` (0x08-0x0C). Only the bits the hardware has are kept.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            SECONDS_REGISTER => {
+                self.live.seconds = value & 0x3F;
+                // writing the seconds restarts the current second
+                self.sub_second_cycles = 0;
+            }
+            MINUTES_REGISTER => self.live.minutes = value & 0x3F,
+            HOURS_REGISTER => self.live.hours = value & 0x1F,
+            DAY_LOW_REGISTER => self.live.day_low = value,
+            DAY_HIGH_REGISTER => self.live.day_high = value & (DAY_HIGH_DAY_BIT_8 | DAY_HIGH_HALT | DAY_HIGH_CARRY),
+            _ => unreachable!("{register:#04X} is not a clock register"),
+        }
+    }
+
+    /// The clock in the 48 byte save format, stamped with `now` (UNIX seconds)
+    pub fn to_save_bytes(&self, now: u64) -> [u8; SAVE_SIZE] {
+        let mut bytes = [0; SAVE_SIZE];
+        let words = self.live.to_save_words().into_iter().chain(self.latched.to_save_words());
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes[40..].copy_from_slice(&now.to_le_bytes());
+        bytes
+    }
+
+    /// Restores a clock saved by [`Rtc::to_save_bytes`] (or the older 44 byte layout) and
+    /// advances it by the time between its timestamp and `now`. `None` if `bytes` is neither size.
+    pub fn from_save_bytes(bytes: &[u8], now: u64) -> Option<Self> {
+        let saved_at = match bytes.len() {
+            SAVE_SIZE => u64::from_le_bytes(bytes[40..48].try_into().unwrap()),
+            LEGACY_SAVE_SIZE => u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as u64,
+            _ => return None,
+        };
+
+        let mut words = bytes[..40].chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+        let live = RtcRegisters::from_save_words(std::array::from_fn(|_| words.next().unwrap()));
+        let latched = RtcRegisters::from_save_words(std::array::from_fn(|_| words.next().unwrap()));
+
+        let mut rtc = Self { live, latched, sub_second_cycles: 0, latch_armed: false };
+        if !rtc.halted() {
+            // a clock that went backwards (or a save from the future) just doesn't catch up
+            rtc.live.advance(now.saturating_sub(saved_at));
+        }
+        Some(rtc)
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+fn set_time(rtc: &mut Rtc, days: u16, hours: u8, minutes: u8, seconds: u8) {
+    rtc.write(SECONDS_REGISTER, seconds);
+    rtc.write(MINUTES_REGISTER, minutes);
+    rtc.write(HOURS_REGISTER, hours);
+    rtc.write(DAY_LOW_REGISTER, days as u8);
+    rtc.write(DAY_HIGH_REGISTER, (days >> 8) as u8 & DAY_HIGH_DAY_BIT_8);
+}
+
+fn latch(rtc: &mut Rtc) {
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x01);
+}
+
+/// (days, hours, minutes, seconds) as latched
+fn latched_time(rtc: &mut Rtc) -> (u16, u8, u8, u8) {
+    latch(rtc);
+    let days = ((rtc.read(DAY_HIGH_REGISTER) & DAY_HIGH_DAY_BIT_8) as u16) << 8 | rtc.read(DAY_LOW_REGISTER) as u16;
+    (days, rtc.read(HOURS_REGISTER), rtc.read(MINUTES_REGISTER), rtc.read(SECONDS_REGISTER))
+}
+
+#[test]
+fn ticks_from_emulated_cycles() {
+    let mut rtc = Rtc::new();
+
+    rtc.tick(T_CYCLES_PER_SECOND - 4);
+    assert_eq!((0, 0, 0, 0), latched_time(&mut rtc));
+
+    rtc.tick(4);
+    assert_eq!((0, 0, 0, 1), latched_time(&mut rtc));
+
+    for _ in 0..60 {
+        rtc.tick(T_CYCLES_PER_SECOND);
+    }
+    assert_eq!((0, 0, 1, 1), latched_time(&mut rtc));
+}
+
+#[test]
+fn counters_roll_over_into_the_next() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0x0FF, 23, 59, 59);
+
+    rtc.tick(T_CYCLES_PER_SECOND);
+
+    assert_eq!((0x100, 0, 0, 0), latched_time(&mut rtc));
+    assert_eq!(0, rtc.read(DAY_HIGH_REGISTER) & DAY_HIGH_CARRY);
+}
+
+#[test]
+fn day_counter_overflow_sets_carry() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0x1FF, 23, 59, 59);
+
+    rtc.tick(T_CYCLES_PER_SECOND);
+    assert_eq!((0, 0, 0, 0), latched_time(&mut rtc));
+    assert_ne!(0, rtc.read(DAY_HIGH_REGISTER) & DAY_HIGH_CARRY);
+
+    // it sticks until the game clears it
+    rtc.tick(T_CYCLES_PER_SECOND);
+    latch(&mut rtc);
+    assert_ne!(0, rtc.read(DAY_HIGH_REGISTER) & DAY_HIGH_CARRY);
+    rtc.write(DAY_HIGH_REGISTER, 0x00);
+    latch(&mut rtc);
+    assert_eq!(0, rtc.read(DAY_HIGH_REGISTER) & DAY_HIGH_CARRY);
+}
+
+#[test]
+fn out_of_range_values_wrap_at_their_bit_width() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0, 0, 0, 63);
+
+    rtc.tick(T_CYCLES_PER_SECOND);
+
+    // 63 wraps to 0 without carrying into the minutes
+    assert_eq!((0, 0, 0, 0), latched_time(&mut rtc));
+}
+
+#[test]
+fn halt_stops_the_clock() {
+    let mut rtc = Rtc::new();
+    rtc.write(DAY_HIGH_REGISTER, DAY_HIGH_HALT);
+
+    rtc.tick(10 * T_CYCLES_PER_SECOND);
+    assert_eq!((0, 0, 0, 0), latched_time(&mut rtc));
+
+    rtc.write(DAY_HIGH_REGISTER, 0x00);
+    rtc.tick(T_CYCLES_PER_SECOND);
+    assert_eq!((0, 0, 0, 1), latched_time(&mut rtc));
+}
+
+#[test]
+fn writing_seconds_restarts_the_second() {
+    let mut rtc = Rtc::new();
+
+    rtc.tick(T_CYCLES_PER_SECOND - 4);
+    rtc.write(SECONDS_REGISTER, 10);
+    rtc.tick(4);
+
+    assert_eq!((0, 0, 0, 10), latched_time(&mut rtc));
+}
+
+#[test]
+fn reads_see_the_latched_registers() {
+    let mut rtc = Rtc::new();
+
+    rtc.tick(5 * T_CYCLES_PER_SECOND);
+    assert_eq!(0, rtc.read(SECONDS_REGISTER), "nothing latched yet");
+
+    latch(&mut rtc);
+    rtc.tick(5 * T_CYCLES_PER_SECOND);
+    assert_eq!(5, rtc.read(SECONDS_REGISTER));
+
+    // only a 0x00 immediately followed by 0x01 latches
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x02);
+    rtc.write_latch(0x01);
+    assert_eq!(5, rtc.read(SECONDS_REGISTER));
+    rtc.write_latch(0x01);
+    assert_eq!(5, rtc.read(SECONDS_REGISTER));
+
+    rtc.write_latch(0x00);
+    rtc.write_latch(0x01);
+    assert_eq!(10, rtc.read(SECONDS_REGISTER));
+}
+
+#[test]
+fn save_round_trip() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0x123, 4, 5, 6);
+    latch(&mut rtc);
+    set_time(&mut rtc, 0x1AB, 7, 8, 9);
+
+    let bytes = rtc.to_save_bytes(1_000_000);
+    assert_eq!(9, u32::from_le_bytes(bytes[0..4].try_into().unwrap()), "live seconds come first");
+    assert_eq!(6, u32::from_le_bytes(bytes[20..24].try_into().unwrap()), "then the latched registers");
+    assert_eq!(1_000_000, u64::from_le_bytes(bytes[40..48].try_into().unwrap()));
+
+    let mut restored = Rtc::from_save_bytes(&bytes, 1_000_000).unwrap();
+    assert_eq!(rtc.live, restored.live);
+    assert_eq!((0x123, 4, 5, 6), (restored.latched.days(), restored.latched.hours, restored.latched.minutes, restored.latched.seconds));
+    assert_eq!((0x1AB, 7, 8, 9), latched_time(&mut restored));
+}
+
+#[test]
+fn catches_up_on_wall_clock_time() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 1, 22, 30, 0);
+    let bytes = rtc.to_save_bytes(50_000);
+
+    // 3 days, 2 hours, 1 minute and 5 seconds later
+    let mut restored = Rtc::from_save_bytes(&bytes, 50_000 + 3 * 86400 + 2 * 3600 + 60 + 5).unwrap();
+
+    assert_eq!((5, 0, 31, 5), latched_time(&mut restored));
+}
+
+#[test]
+fn catching_up_past_day_511_sets_carry() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 500, 0, 0, 0);
+    let bytes = rtc.to_save_bytes(0);
+
+    let mut restored = Rtc::from_save_bytes(&bytes, 20 * 86400).unwrap();
+
+    assert_eq!((8, 0, 0, 0), latched_time(&mut restored));
+    assert_ne!(0, restored.read(DAY_HIGH_REGISTER) & DAY_HIGH_CARRY);
+}
+
+#[test]
+fn halted_clock_doesnt_catch_up() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0, 1, 2, 3);
+    rtc.write(DAY_HIGH_REGISTER, DAY_HIGH_HALT);
+    let bytes = rtc.to_save_bytes(0);
+
+    let mut restored = Rtc::from_save_bytes(&bytes, 86400).unwrap();
+
+    assert_eq!((0, 1, 2, 3), latched_time(&mut restored));
+}
+
+#[test]
+fn loads_legacy_32_bit_timestamps() {
+    let mut rtc = Rtc::new();
+    set_time(&mut rtc, 0, 0, 0, 30);
+    let bytes = rtc.to_save_bytes(100);
+
+    let mut restored = Rtc::from_save_bytes(&bytes[..LEGACY_SAVE_SIZE], 145).unwrap();
+
+    assert_eq!((0, 0, 1, 15), latched_time(&mut restored));
+    assert!(Rtc::from_save_bytes(&bytes[..40], 100).is_none());
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+/// A 64 KiB MBC1+RAM+BATTERY image with 32 KiB of RAM titled "TESTGAME"
+fn test_rom() -> Vec<u8> {
+    let mut rom = vec![0x00; 0x10000];
+    rom[0x0134..0x0134 + 8].copy_from_slice(b"TESTGAME");
+    rom[0x0146] = 0x03;
+    with_valid_header(rom, 0x03, 0x03)
+}
+
+#[test]
+fn parses_header_fields() {
+    let cartridge = Cartridge::from_bytes(&test_rom()).expect("test ROM should load");
+    let header = cartridge.header();
+
+    assert_eq!("TESTGAME", header.title);
+    assert_eq!(CgbSupport::None, header.cgb_support);
+    assert!(header.sgb_support);
+    assert_eq!(0x03, header.cartridge_type.code);
+    assert_eq!(ControllerKind::Mbc1, header.cartridge_type.controller);
+    assert!(header.cartridge_type.has_ram);
+    assert!(header.cartridge_type.has_battery);
+    assert!(!header.cartridge_type.has_timer);
+    assert_eq!(0x10000, header.rom_size);
+    assert_eq!(4, header.rom_banks());
+    assert_eq!(0x8000, header.ram_size);
+    assert_eq!(Destination::Overseas, header.destination);
+
+    cartridge.verify_global_checksum().expect("global checksum should match");
+}
+
+#[test]
+fn cgb_title_excludes_manufacturer_code_and_flag() {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0134..0x0134 + 15].copy_from_slice(b"ABCDEFGHIJKLMNO");
+    rom[0x0143] = 0x80;
+    let rom = with_valid_header(rom, 0x00, 0x00);
+
+    let header = Cartridge::from_bytes(&rom).unwrap().header;
+
+    assert_eq!("ABCDEFGHIJK", header.title);
+    assert_eq!(CgbSupport::Compatible, header.cgb_support);
+}
+
+#[test]
+fn cartridge_type_codes() {
+    let cases = [
+        (0x00, ControllerKind::RomOnly, false, false, false, false),
+        (0x06, ControllerKind::Mbc2, true, true, false, false),
+        (0x0F, ControllerKind::Mbc3, false, true, true, false),
+        (0x13, ControllerKind::Mbc3, true, true, false, false),
+        (0x1E, ControllerKind::Mbc5, true, true, false, true),
+        (0xFF, ControllerKind::Unsupported, false, false, false, false),
+    ];
+
+    for (code, controller, ram, battery, timer, rumble) in cases {
+        let cartridge_type = CartridgeType::from_code(code);
+        assert_eq!(controller, cartridge_type.controller, "type {code:#04X}");
+        assert_eq!(ram, cartridge_type.has_ram, "type {code:#04X}");
+        assert_eq!(battery, cartridge_type.has_battery, "type {code:#04X}");
+        assert_eq!(timer, cartridge_type.has_timer, "type {code:#04X}");
+        assert_eq!(rumble, cartridge_type.has_rumble, "type {code:#04X}");
+    }
+}
+
+#[test]
+fn rejects_bad_header_checksum() {
+    let mut rom = test_rom();
+    rom[0x0134] = b'X';
+
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::HeaderChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn global_checksum_mismatch_is_reported_but_not_enforced() {
+    let mut rom = test_rom();
+    rom[0x4000] = 0xAB;
+
+    let cartridge = Cartridge::from_bytes(&rom).expect("the global checksum isn't checked on load");
+
+    assert!(matches!(
+        cartridge.verify_global_checksum(),
+        Err(CartridgeError::GlobalChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn rejects_invalid_sizes_and_codes() {
+    assert!(matches!(Cartridge::from_bytes(&[0; 0x100]), Err(CartridgeError::TooSmall(0x100))));
+
+    let mut rom = test_rom();
+    rom[0x0148] = 0x09;
+    rom[0x014D] = CartridgeHeader::compute_header_checksum(&rom);
+    assert!(matches!(Cartridge::from_bytes(&rom), Err(CartridgeError::InvalidRomSize(0x09))));
+
+    let mut rom = test_rom();
+    rom[0x0149] = 0x06;
+    rom[0x014D] = CartridgeHeader::compute_header_checksum(&rom);
+    assert!(matches!(Cartridge::from_bytes(&rom), Err(CartridgeError::InvalidRamSize(0x06))));
+}
+
+#[test]
+fn loads_images_smaller_or_bigger_than_the_header_says() {
+    let mut rom = test_rom();
+    rom.truncate(0x8000);
+    let mut cartridge = Cartridge::from_bytes(&rom).expect("a trimmed image should load");
+    cartridge.write_rom(0x2000, 0x02);
+    assert_eq!(0xFF, cartridge.read_rom(0x4000), "the missing banks read as padding");
+
+    // 80 KiB, so it's padded to 128 KiB and bank 4 doesn't wrap around to bank 0
+    let mut rom = test_rom();
+    rom.resize(0x14000, 0x00);
+    rom[0x10000] = 0x42;
+    let mut cartridge = Cartridge::from_bytes(&rom).expect("an overdumped image should load");
+    cartridge.write_rom(0x2000, 0x04);
+    assert_eq!(0x42, cartridge.read_rom(0x4000));
+    cartridge.write_rom(0x2000, 0x05);
+    assert_eq!(0xFF, cartridge.read_rom(0x4000));
+}
+
+#[test]
+fn loads_with_an_unknown_destination_code() {
+    let mut rom = test_rom();
+    rom[0x014A] = 0x02;
+    rom[0x014D] = CartridgeHeader::compute_header_checksum(&rom);
+    let cartridge = Cartridge::from_bytes(&rom).expect("the destination code shouldn't stop it loading");
+    assert_eq!(Destination::Unknown(0x02), cartridge.header().destination);
+}
+
+#[test]
+fn loads_from_path() {
+    let path = std::env::temp_dir().join(format!("gameboy_emulator_cartridge_{}.gb", std::process::id()));
+    std::fs::write(&path, test_rom()).unwrap();
+
+    let cartridge = Cartridge::from_path(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!("TESTGAME", cartridge.expect("ROM file should load").header().title);
+
+    assert!(matches!(
+        Cartridge::from_path(std::env::temp_dir().join("gameboy_emulator_does_not_exist.gb")),
+        Err(CartridgeError::Io(_))
+    ));
+}
@@ -21,34 +21,54 @@
 
 mod register;
 mod instruction;
+mod trace;
+#[cfg(test)]
+mod tests;
 
 // Brings in 
 use instruction::*;
 use register::*;
+use crate::memory_bus::MemoryBus;
+use crate::timer::DIV_ADDRESS;
 
-struct MemoryBus {
-    memory: [u8; 0xFFFF] // 65535 bytes    
-}
-
-impl MemoryBus {
-    #[inline]
-    fn read_byte(&self, address: u16) -> u8 {
-        self.memory[address as usize]
-    }
-    fn write_byte(&mut self, address: u16, value: u8) {
-        self.memory[address as usize] = value;
-    }
-}
+pub use instruction::{ assemble, disassemble, disassemble_at, AssembleError, Disassembly, InstructionBuildError };
+pub use register::{ FlagRegister, Registers };
+pub use trace::{ TraceFilter, Tracer };
 
 /// 2-byte unsigned value representing the PC's value
 type PCAddr = u16;
-struct CPU {
+
+/// A number of T-cycles, i.e. ticks of the 4.194304 MHz system clock
+pub type TCycles = u32;
+/// Every M-cycle (the unit instructions are timed in) is 4 T-cycles
+const T_CYCLES_PER_M_CYCLE: TCycles = 4;
+/// Dispatching an interrupt takes 5 M-cycles (2 idle, 2 to push PC, 1 to set PC)
+const INTERRUPT_DISPATCH_M_CYCLES: TCycles = 5;
+
+pub struct CPU {
     registers: Registers,
     /// Program Counter
     pc: PCAddr,
     /// Stack Pointer
     sp: u16,
-    bus: MemoryBus
+    bus: MemoryBus,
+    /// Interrupt Master Enable -- whether pending interrupts get dispatched at all
+    ime: bool,
+    /// Set by EI, as EI only enables IME after the instruction following it has executed
+    ime_scheduled: bool,
+    /// Set by HALT, the CPU idles until an interrupt is pending
+    halted: bool,
+    /// Set by HALT when IME=0 and an interrupt is already pending; the next opcode fetch
+    /// fails to increment PC so the byte after HALT is read twice
+    halt_bug: bool,
+    /// Set by STOP, the CPU idles until a button is pressed
+    stopped: bool,
+    /// Set by an illegal opcode, after which the CPU never runs anything again
+    locked: bool,
+    /// Running count of every T-cycle the CPU has spent since power on
+    cycles: u64,
+    /// Logs every instruction before it runs, when tracing
+    tracer: Option<Tracer>,
 } 
 
 // DIRECT INSTRUCTION EXECUTION impl-block
@@ -213,6 +233,12 @@ impl CPU {
                                 self.sp = self.registers.get_hl();
                                 self.pc.wrapping_add(1)
                             }
+                            LDInputU16::IISP => {
+                                let address = self.read_immediate_u16();
+                                self.bus.write_byte(address, self.sp as u8);
+                                self.bus.write_byte(address.wrapping_add(1), (self.sp >> 8) as u8);
+                                self.pc.wrapping_add(3)
+                            }
                         }
                     }
                     LoadU16Cmd::PUSH(InputU16(rr)) => {
@@ -255,8 +281,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.add(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.add(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::ADC(input) => {
@@ -273,8 +305,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                            }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.adc(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.adc(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::SUB(input) => {
@@ -291,8 +329,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.sub(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.sub(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::SBC(input) => {
@@ -309,8 +353,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.sbc(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.sbc(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::AND(input) => {
@@ -327,8 +377,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.and(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.and(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::XOR(input) => {
@@ -345,8 +401,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.xor(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.xor(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::OR(input) => {
@@ -363,8 +425,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.registers.a = self.or(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.registers.a = self.or(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::CP(input) => {
@@ -381,8 +449,14 @@ impl CPU {
                                 }
                                 self.pc.wrapping_add(1)
                             }
-                            CompoundInputU8::Immediate => todo!("immediate value // clock cycles // addressing & accessing system"),
-                            CompoundInputU8::Address => todo!("addressing HL")
+                            CompoundInputU8::Immediate => {
+                                self.cp(self.read_immediate_u8());
+                                self.pc.wrapping_add(2)
+                            }
+                            CompoundInputU8::Address => {
+                                self.cp(self.bus.read_byte(self.registers.get_hl()));
+                                self.pc.wrapping_add(1)
+                            }
                         }
                     }
                     AritLogiU8Cmd::INC(input) => {
@@ -558,62 +632,12 @@ impl CPU {
                         self.pc.wrapping_add(1) 
                     }
                     AritLogiU16Cmd::ADDSP => {
-                        // NOTE: this comes in as a u8 so i wont have to bit mask it later since it is already in the range i want (bottom 12 bits) for the following operations
-                        let signed_immediate = self.read_immediate_u8() as i16;
-                        let did_overflow;
-
-                        if signed_immediate.is_negative() {
-                            // NOTE: promoting to i32 because SP starts out as u16 and may not fit inside an i16 as expected (i.e. it may be a different negative number)
-                            // ALSO: This may not even be documented as a required edge case, and also this might be a dumb way of doing it anyways! todo!("CHECK THIS")
-                            self.registers.f.half_carry = ((self.sp as i32 & 0x0FFF) + (signed_immediate as i32)).is_negative();
-
-                            // SAFETY: signed_immediate is read initially as a u8 so the abs() will always fit in an i16 (and u16)
-                            (self.sp, did_overflow) = self.sp.overflowing_sub(signed_immediate.abs() as u16)
-                        } else {
-                            self.registers.f.half_carry = ((self.sp & 0x0FFF) + (signed_immediate as u16)) & 0x1000 == 0x1000;
-                            (self.sp, did_overflow) = self.sp.overflowing_add(signed_immediate as u16)
-                        }
-
-                        // zero flag is reset
-                        self.registers.f.zero = false;
-                        // N/subtract flag is reset
-                        self.registers.f.subtract = false;
-                        // carry flag set/reset as defined
-                        self.registers.f.carry = did_overflow;
-                        // half-carry is set in the above if/else
-
-
+                        self.sp = self.add_sp_signed();
                         self.pc.wrapping_add(2)
                     }
                     AritLogiU16Cmd::LDHLSP => {
-                        // todo!("check if carry & half-carry flags follow the same procedure as ADDSP -- im pretty sure they do")
-                        // NOTE: this comes in as a u8 so i wont have to bit mask it later since it is already in the range i want (bottom 12 bits) for the following operations
-                        let signed_immediate = self.read_immediate_u8() as i16;
-                        let (new_hl, did_overflow);
-
-                        if signed_immediate.is_negative() {
-                            // NOTE: promoting to i32 because SP starts out as u16 and may not fit inside an i16 as expected (i.e. it may be a different negative number)
-                            // ALSO: This may not even be documented as a required edge case, and also this might be a dumb way of doing it anyways! todo!("CHECK THIS")
-                            self.registers.f.half_carry = ((self.sp as i32 & 0x0FFF) + (signed_immediate as i32)).is_negative();
-
-                            // SAFETY: signed_immediate is read initially as a u8 so the abs() will always fit in an i16 (and u16)
-                            (new_hl, did_overflow) = self.sp.overflowing_sub(signed_immediate.abs() as u16)
-                        } else {
-                            self.registers.f.half_carry = ((self.sp & 0x0FFF) + (signed_immediate as u16)) & 0x1000 == 0x1000;
-                            (new_hl, did_overflow) = self.sp.overflowing_add(signed_immediate as u16)
-                        }
-
-                        self.registers.set_hl(new_hl);
-
-                        // zero flag is reset
-                        self.registers.f.zero = false;
-                        // N/subtract flag is reset
-                        self.registers.f.subtract = false;
-                        // carry flag set/reset as defined
-                        self.registers.f.carry = did_overflow;
-                        // half-carry is set in the above if/else
-
-
+                        let sum = self.add_sp_signed();
+                        self.registers.set_hl(sum);
                         self.pc.wrapping_add(2)
                     }
                 }
@@ -890,11 +914,37 @@ impl CPU {
                         self.registers.f.carry = true;
                         self.pc.wrapping_add(1)
                     }
-                    CtrCmd::NOP => todo!("Implement"),
-                    CtrCmd::HALT => todo!("Implement"),
-                    CtrCmd::STOP => todo!("Implement"),
-                    CtrCmd::DI => todo!("Implement"),
-                    CtrCmd::EI => todo!("Implement"),
+                    CtrCmd::NOP => self.pc.wrapping_add(1),
+                    CtrCmd::HALT => {
+                        if !self.ime && self.bus.interrupts.has_pending() {
+                            // HALT exits immediately but the CPU fails to increment PC on the next fetch
+                            self.halt_bug = true;
+                        } else {
+                            self.halted = true;
+                        }
+                        self.pc.wrapping_add(1)
+                    }
+                    CtrCmd::STOP => {
+                        // the DMG stops its clock until a button is pressed (see `step`), and DIV is reset
+                        self.stopped = true;
+                        self.bus.write_byte(DIV_ADDRESS, 0);
+                        self.pc.wrapping_add(2)
+                    }
+                    CtrCmd::DI => {
+                        // also cancels an EI that hasn't taken effect yet
+                        self.ime = false;
+                        self.ime_scheduled = false;
+                        self.pc.wrapping_add(1)
+                    }
+                    CtrCmd::EI => {
+                        // IME is set by [`CPU::step`] after the next instruction, see [`CPU::ime_scheduled`]
+                        self.ime_scheduled = true;
+                        self.pc.wrapping_add(1)
+                    }
+                    CtrCmd::ILLEGAL(_) => {
+                        self.locked = true;
+                        self.pc
+                    }
                 }
             }
             
@@ -921,10 +971,30 @@ impl CPU {
                             }
                         }
                     }
-                    JmpCmd::CALL(input) => todo!("Design & Implement"),
-                    JmpCmd::RET(input) => todo!("Design & Implement"),
-                    JmpCmd::RETI => todo!("Implement"),
-                    JmpCmd::RST() => todo!("Design & Implement"),
+                    JmpCmd::CALL(input) => {
+                        match input {
+                            JmpCmdInput::Direct => self.call(true),
+                            JmpCmdInput::Conditional(cond) => {
+                                let should_call = self.check_cond(cond);
+                                self.call(should_call)
+                            }
+                        }
+                    }
+                    JmpCmd::RET(input) => {
+                        match input {
+                            JmpCmdInput::Direct => self.ret(true),
+                            JmpCmdInput::Conditional(cond) => {
+                                let should_return = self.check_cond(cond);
+                                self.ret(should_return)
+                            }
+                        }
+                    }
+                    JmpCmd::RETI => {
+                        // unlike EI there is no delay here
+                        self.ime = true;
+                        self.ret(true)
+                    }
+                    JmpCmd::RST(vector) => self.restart(vector),
                 }
             }
         }
@@ -1038,6 +1108,18 @@ impl CPU {
     }
 
 /* ArithmeticLogical16Bit Utils */
+    /// SP plus the signed immediate byte, for ADD SP,dd and LD HL,SP+dd. The flags come from
+    /// adding the low byte of SP and the immediate as unsigned bytes, as the ALU is 8-bit.
+    fn add_sp_signed(&mut self) -> u16 {
+        let immediate = self.read_immediate_u8();
+        self.registers.f.zero = false;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = (self.sp & 0x0F) + (immediate as u16 & 0x0F) > 0x0F;
+        self.registers.f.carry = (self.sp & 0xFF) + immediate as u16 > 0xFF;
+
+        self.sp.wrapping_add(immediate as i8 as u16)
+    }
+
     fn addhl(&mut self, value: u16) -> u16 {
         let (sum, did_overflow) = self.registers.get_hl().overflowing_add(value);
         // zero-flag is not affected
@@ -1097,7 +1179,7 @@ impl CPU {
         self.registers.f.zero = res == 0;
         self.registers.f.subtract = false;
         self.registers.f.half_carry = false;
-        self.registers.f.carry = (value & (0b1 << 7)) == 1;
+        self.registers.f.carry = (value & (0b1 << 7)) != 0;
     
         res
     }
@@ -1119,7 +1201,7 @@ impl CPU {
         self.registers.f.zero = res == 0;
         self.registers.f.subtract = false;
         self.registers.f.half_carry = false;
-        self.registers.f.carry = (value & (0b1 << 7)) == 1;
+        self.registers.f.carry = (value & (0b1 << 7)) != 0;
     
         res
     }
@@ -1139,7 +1221,7 @@ impl CPU {
         self.registers.f.zero = res == 0;
         self.registers.f.subtract = false;
         self.registers.f.half_carry = false;
-        self.registers.f.carry = (value & (0b1 << 7)) == 1;
+        self.registers.f.carry = (value & (0b1 << 7)) != 0;
 
         res
     }
@@ -1160,7 +1242,7 @@ impl CPU {
             JmpCmdCondition::NZ => !self.registers.f.zero,
             JmpCmdCondition::Z => self.registers.f.zero,
             JmpCmdCondition::NC => !self.registers.f.carry,
-            JmpCmdCondition::C => !self.registers.f.carry,
+            JmpCmdCondition::C => self.registers.f.carry,
         }
     }
     
@@ -1177,28 +1259,174 @@ impl CPU {
     }
 
     fn jump_relative(&mut self, should_jump: bool) -> PCAddr {
+        // JR dd is 2-bytes wide (OPCODE | RELATIVE_BYTE) and the offset counts from the instruction after it
+        let next_pc = self.pc.wrapping_add(2);
         if should_jump {
             // offset is signed
-            let offset = self.bus.read_byte(self.pc + 1) as i8; 
-            // todo!("verify if i should wrap this or if this will never actually wrap in proper JR calls")
-            if offset >= 0 {
-                self.pc.wrapping_add(offset as u16)
-            } else {
-                self.pc.wrapping_sub((offset as i16).abs() as u16)
-            }
+            let offset = self.read_immediate_u8() as i8;
+            next_pc.wrapping_add(offset as u16)
         } else {
-            // JR dd is 2-bytes wide (OPCODE | RELATIVE_BYTE)
-            self.pc.wrapping_add(2)
+            next_pc
         }
     }
 
-    
+    fn call(&mut self, should_call: bool) -> PCAddr {
+        // CALL nn is 3-bytes wide (OPCODE | ADDR_LEAST_SIG_BYTE | ADDR_MOST_SIG_BYTE)
+        // and the address pushed is the one of the instruction after it
+        let return_address = self.pc.wrapping_add(3);
+        if should_call {
+            self.push(return_address);
+            self.read_immediate_u16()
+        } else {
+            return_address
+        }
+    }
+
+    fn ret(&mut self, should_return: bool) -> PCAddr {
+        if should_return {
+            self.pop()
+        } else {
+            // RET is 1-byte wide
+            self.pc.wrapping_add(1)
+        }
+    }
+
+    // RST is a 1-byte CALL to a fixed address in page zero
+    fn restart(&mut self, vector: RstVector) -> PCAddr {
+        self.push(self.pc.wrapping_add(1));
+        vector.address()
+    }
 }
 
 // MEMORY MANIPULATION / CPU-LOOP / ENCODING INSTRUCTIONS TO BE EXECUTED impl-block
 impl CPU {
+    /// A CPU in the state the DMG boot ROM leaves it in when it hands over to the cartridge at 0x0100
+    pub fn new(mut bus: MemoryBus) -> Self {
+        bus.skip_boot_rom();
+        Self {
+            registers: Registers {
+                a: 0x01, b: 0x00, c: 0x13, d: 0x00, e: 0xD8, h: 0x01, l: 0x4D,
+                f: FlagRegister::from(0xB0),
+            },
+            pc: 0x0100,
+            sp: 0xFFFE,
+            bus,
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            halt_bug: false,
+            stopped: false,
+            locked: false,
+            cycles: 0,
+            tracer: None,
+        }
+    }
+
+    pub fn bus(&self) -> &MemoryBus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut MemoryBus {
+        &mut self.bus
+    }
+
+    /// T-cycles spent since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn set_sp(&mut self, sp: u16) {
+        self.sp = sp;
+    }
+
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether the CPU ran into an illegal opcode, which it never recovers from
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Starts logging instructions to `tracer`, and hands back the one that was going if any
+    pub fn set_tracer(&mut self, tracer: Tracer) -> Option<Tracer> {
+        self.tracer.replace(tracer)
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    /// Runs the CPU for one instruction (or one interrupt dispatch, or one M-cycle of idling in HALT)
+    /// and returns how many T-cycles that took, so the rest of the system can be clocked by it.
     // todo!("not sure if there is any point in propagating errors but its in place somewhat for now here")
-    fn step(&mut self) -> Result<(), instruction::InstructionBuildError> {
+    pub fn step(&mut self) -> Result<TCycles, InstructionBuildError> {
+        let cycles = self.step_inner()?;
+        self.cycles += cycles as u64;
+        // STOP stops the system clock, so nothing on the bus runs until a button wakes the CPU
+        // (the joypad doesn't need the clock to see it). Time still counts for whoever's running
+        // the emulator, so a cycle limit still ends a run stuck in STOP.
+        if !self.stopped {
+            self.bus.tick(cycles);
+        }
+        Ok(cycles)
+    }
+
+    fn step_inner(&mut self) -> Result<TCycles, InstructionBuildError> {
+        if self.locked {
+            // not even an interrupt gets it going again
+            return Ok(T_CYCLES_PER_M_CYCLE);
+        }
+        if self.stopped {
+            // any selected button line going low wakes it up
+            if self.bus.joypad.read_register() & 0x0F == 0x0F {
+                return Ok(T_CYCLES_PER_M_CYCLE);
+            }
+            self.stopped = false;
+        }
+
+        if self.service_interrupt() {
+            return Ok(INTERRUPT_DISPATCH_M_CYCLES * T_CYCLES_PER_M_CYCLE);
+        }
+        if self.halted {
+            return Ok(T_CYCLES_PER_M_CYCLE);
+        }
+
+        if self.tracer.as_ref().is_some_and(|tracer| tracer.wants(self.pc)) {
+            self.trace();
+        }
+
+        // an EI executed last step takes effect once this step's instruction is done
+        let enable_ime = self.ime_scheduled;
+
         // todo!("cover all instruction reading styles")
         let mut instruction_byte = self.bus.read_byte(self.pc); 
         let prefixed = instruction_byte == 0xCB;
@@ -1207,17 +1435,67 @@ impl CPU {
         }
     
         let instruction = Instruction::from_byte(instruction_byte, prefixed)?;
+
+        // conditions only depend on flags that jumps never touch, so checking ahead of execution is safe
+        let branch_taken = instruction.condition().is_some_and(|cond| self.check_cond(cond));
+        let cycles = instruction.machine_cycles(branch_taken) as TCycles * T_CYCLES_PER_M_CYCLE;
+
+        if self.halt_bug {
+            // the opcode was fetched without PC moving past it, so everything after
+            // the opcode (immediates & the next PC) is read one byte early
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
+
         let next_pc = self.execute(instruction);
         
         self.pc = next_pc;
+
+        if enable_ime && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
         
-        Ok(())
+        Ok(cycles)
+    }
+
+    /// Logs the instruction at PC, before it runs
+    fn trace(&mut self) {
+        let pc_memory = [0, 1, 2, 3].map(|offset| self.bus.peek_byte(self.pc.wrapping_add(offset)));
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(&self.registers, self.sp, self.pc, pc_memory);
+        }
+    }
+
+    /// Checks for a pending interrupt, waking the CPU out of HALT if there is one, and dispatches
+    /// it if IME is set: IME is reset, the IF bit is acknowledged, PC is pushed and PC jumps to
+    /// the interrupt's vector.
+    ///
+    /// Returns whether an interrupt was dispatched, as dispatching takes the place of an instruction.
+    fn service_interrupt(&mut self) -> bool {
+        let Some(interrupt) = self.bus.interrupts.highest_pending() else {
+            return false;
+        };
+
+        // a pending interrupt ends HALT even when IME=0 (execution just continues after the HALT)
+        self.halted = false;
+
+        if !self.ime {
+            return false;
+        }
+
+        self.ime = false;
+        self.bus.interrupts.acknowledge(interrupt);
+        self.push(self.pc);
+        self.pc = interrupt.vector();
+
+        true
     }
 
     /// Reads the byte immediately after the opcode in memory.
     #[inline]
     fn read_immediate_u8(&self) -> u8 {
-        self.bus.read_byte(self.pc.wrapping_add(2))
+        self.bus.read_byte(self.pc.wrapping_add(1))
     }
 
     /// Reads the next two bytes immediately after the opcode in memory as a u16.
@@ -1225,7 +1503,7 @@ impl CPU {
     #[inline]
     fn read_immediate_u16(&self) -> u16 {
         (self.read_immediate_u8() as u16) // LS-byte first
-        | ((self.bus.read_byte(self.pc.wrapping_add(3)) as u16) << 8) // MS-byte last
+        | ((self.bus.read_byte(self.pc.wrapping_add(2)) as u16) << 8) // MS-byte last
     }
 
     /// Handles Stack Pointer PUSH operation logic
@@ -1238,12 +1516,15 @@ impl CPU {
     }
 
     /// Handles Stack Pointer POP operation logic
+    /// 
+    /// The mirror image of [`CPU::push`]: SP points at the last byte pushed (the LS-byte),
+    /// so read first and then move SP up.
     fn pop(&mut self) -> u16 {
-        self.sp = self.sp.wrapping_add(1);
         let ls_byte = self.bus.read_byte(self.sp) as u16;
-
         self.sp = self.sp.wrapping_add(1);
+
         let ms_byte = (self.bus.read_byte(self.sp)) as u16;
+        self.sp = self.sp.wrapping_add(1);
 
         (ms_byte << 8) | ls_byte
     }
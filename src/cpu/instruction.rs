@@ -1,6 +1,10 @@
 mod input;
 mod commands;
 mod helper_macros;
+mod timing;
+mod disassembly;
+mod encoding;
+mod assembler;
 #[cfg(test)]
 mod tests;
 
@@ -19,6 +23,8 @@ use crate::cpu::register::RegisterU16::{ BC, DE, HL, SP, AF };
 // and implementing instructions, which will involve commands and inputs, etc...
 pub use input::*;
 pub use commands::*;
+pub use disassembly::{ disassemble, disassemble_at, Disassembly };
+pub use assembler::{ assemble, AssembleError };
 
 #[derive(Debug)]
 pub struct InstructionBuildError(String);
@@ -87,7 +93,6 @@ impl Instruction {
                 cy := carry bit
         */
         match byte {
-/* TODO STATUS: Bit Ops, CPU Control, Jumpcommands, some Addressing inputs */
 /* START || 8-bit Load Commands || START */
             // LD r,r | xx | 4 | ---- | r=r
                 /* A,r | 7x */
@@ -99,6 +104,7 @@ impl Instruction {
             0x7C => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(A, H)),
             0x7D => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(A, L)),
                 /* B, r | 4x */
+            0x47 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, A)),
             0x40 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, B)),
             0x41 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, C)),
             0x42 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, D)),
@@ -106,6 +112,7 @@ impl Instruction {
             0x44 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, H)),
             0x45 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(B, L)),
                 /* C, r | 4x */
+            0x4F => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, A)),
             0x48 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, B)),
             0x49 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, C)),
             0x4A => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, D)),
@@ -113,6 +120,7 @@ impl Instruction {
             0x4C => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, H)),
             0x4D => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(C, L)),
                 /* D, r | 5x */
+            0x57 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, A)),
             0x50 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, B)),
             0x51 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, C)),
             0x52 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, D)),
@@ -120,6 +128,7 @@ impl Instruction {
             0x54 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, H)),
             0x55 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(D, L)),
                 /* E, r | 5x */
+            0x5F => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, A)),
             0x58 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, B)),
             0x59 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, C)),
             0x5A => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, D)),
@@ -127,6 +136,7 @@ impl Instruction {
             0x5C => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, H)),
             0x5D => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(E, L)),
                 /* H, r | 6x */
+            0x67 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, A)),
             0x60 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, B)),
             0x61 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, C)),
             0x62 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, D)),
@@ -134,6 +144,7 @@ impl Instruction {
             0x64 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, H)),
             0x65 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(H, L)),
                 /* L, r | 6x */
+            0x6F => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(L, A)),
             0x68 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(L, B)),
             0x69 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(L, C)),
             0x6A => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RR(L, D)),
@@ -157,6 +168,7 @@ impl Instruction {
             0x66 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RHL(H)),
             0x6E => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::RHL(L)),
             // LD (HL),r | 7x | 8 | ---- | (HL)=r
+            0x77 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::HLR(A)),
             0x70 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::HLR(B)),
             0x71 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::HLR(C)),
             0x72 => load_u8_impl!(LoadU8Cmd::LD, LDInputU8::HLR(D)),
@@ -206,6 +218,8 @@ impl Instruction {
             0x31 => load_u16_impl!(LoadU16Cmd::LD, LDInputU16::RRNN(SP)),
             // LD SP,HL | F9 | 8 | ---- | SP=HL
             0xF9 => load_u16_impl!(LoadU16Cmd::LD, LDInputU16::SPHL),
+            // LD (nn),SP | 08 nn nn | 20 | ---- | (nn)=SP
+            0x08 => load_u16_impl!(LoadU16Cmd::LD, LDInputU16::IISP),
 
             // PUSH rr | x5 | 16 | ---- | SP=SP-2; (SP)=rr
             0xF5 => load_u16_impl!(LoadU16Cmd::PUSH, InputU16(AF)),
@@ -402,7 +416,22 @@ impl Instruction {
 /* END || Single Bit Operation Commands || END */
 
 /* START || CPU Control Commands || START */
-            // todo!()
+            // CCF | 3F | 4 | -00c | cy=cy^1
+            0x3F => control_impl!(CtrCmd::CCF),
+            // SCF | 37 | 4 | -001 | cy=1
+            0x37 => control_impl!(CtrCmd::SCF),
+            // NOP | 00 | 4 | ---- | no operation
+            0x00 => control_impl!(CtrCmd::NOP),
+            // HALT | 76 | N*4 | ---- | halt until interrupt occurs (low power)
+            0x76 => control_impl!(CtrCmd::HALT),
+            // STOP | 10 00 | 4 | ---- | low power standby until a button is pressed
+            0x10 => control_impl!(CtrCmd::STOP),
+            // DI | F3 | 4 | ---- | disable interrupts, IME=0
+            0xF3 => control_impl!(CtrCmd::DI),
+            // EI | FB | 4 | ---- | enable interrupts, IME=1 (after the next instruction)
+            0xFB => control_impl!(CtrCmd::EI),
+            // illegal | xx | 4 | ---- | not an instruction, the CPU locks up until power off
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => control_impl!(CtrCmd::ILLEGAL(byte)),
 /* END || CPU Control Commands || END */
 
 /* START || Jump Commands || START */
@@ -416,12 +445,38 @@ impl Instruction {
             0xD2 => jump_impl!(JmpCmd::JP, JPInput::Conditional(JmpCmdCondition::NC)),
             0xDA => jump_impl!(JmpCmd::JP, JPInput::Conditional(JmpCmdCondition::C)),
 
-            // JR 
-
-        
-        
+            // JR dd | 18 ss | 12 | ---- | PC=PC+2+dd (relative to the next instruction)
+            0x18 => jump_impl!(JmpCmd::JR, JmpCmdInput::Direct),
+            // JR f,dd | xx ss | 12/8 | ---- | conditional relative jump
+            0x20 => jump_impl!(JmpCmd::JR, JmpCmdInput::Conditional(JmpCmdCondition::NZ)),
+            0x28 => jump_impl!(JmpCmd::JR, JmpCmdInput::Conditional(JmpCmdCondition::Z)),
+            0x30 => jump_impl!(JmpCmd::JR, JmpCmdInput::Conditional(JmpCmdCondition::NC)),
+            0x38 => jump_impl!(JmpCmd::JR, JmpCmdInput::Conditional(JmpCmdCondition::C)),
+
+            // CALL nn | CD nn nn | 24 | ---- | SP=SP-2; (SP)=PC; PC=nn
+            0xCD => jump_impl!(JmpCmd::CALL, JmpCmdInput::Direct),
+            // CALL f,nn | xx nn nn | 24/12 | ---- | conditional call
+            0xC4 => jump_impl!(JmpCmd::CALL, JmpCmdInput::Conditional(JmpCmdCondition::NZ)),
+            0xCC => jump_impl!(JmpCmd::CALL, JmpCmdInput::Conditional(JmpCmdCondition::Z)),
+            0xD4 => jump_impl!(JmpCmd::CALL, JmpCmdInput::Conditional(JmpCmdCondition::NC)),
+            0xDC => jump_impl!(JmpCmd::CALL, JmpCmdInput::Conditional(JmpCmdCondition::C)),
+
+            // RET | C9 | 16 | ---- | PC=(SP); SP=SP+2
+            0xC9 => jump_impl!(JmpCmd::RET, JmpCmdInput::Direct),
+            // RET f | xx | 20/8 | ---- | conditional return
+            0xC0 => jump_impl!(JmpCmd::RET, JmpCmdInput::Conditional(JmpCmdCondition::NZ)),
+            0xC8 => jump_impl!(JmpCmd::RET, JmpCmdInput::Conditional(JmpCmdCondition::Z)),
+            0xD0 => jump_impl!(JmpCmd::RET, JmpCmdInput::Conditional(JmpCmdCondition::NC)),
+            0xD8 => jump_impl!(JmpCmd::RET, JmpCmdInput::Conditional(JmpCmdCondition::C)),
+
+            // RETI | D9 | 16 | ---- | return and enable interrupts (IME=1)
+            0xD9 => jump_impl!(JmpCmd::RETI),
+
+            // RST n | xx | 16 | ---- | SP=SP-2; (SP)=PC; PC=n (n is one of 00,08,10,18,20,28,30,38)
+            opcode if opcode & 0b1100_0111 == 0b1100_0111 => jump_impl!(JmpCmd::RST, RstVector::from_opcode(opcode)),
 /* END || Jump Commands || END */
 
+            // 0xCB is only the prefix, CPU::step decodes the byte after it with from_byte_prefixed
             unmatched_opcode => build_err!(unmatched_opcode)
         }
     }
@@ -538,4 +593,61 @@ impl Instruction {
             unmatched_opcode => build_err!(0xCB00 | (unmatched_opcode as u16))
         }
     }
+
+    /// How many bytes this instruction takes up in memory, the opcode (and CB prefix) included
+    pub fn length(&self) -> u8 {
+        match self {
+            Instruction::Load8Bit(command) => {
+                match command {
+                    LoadU8Cmd::LD(LDInputU8::RI(_) | LDInputU8::HLI | LDInputU8::ReadIoN | LDInputU8::WriteIoN) => 2,
+                    LoadU8Cmd::LD(LDInputU8::AII | LDInputU8::IIA) => 3,
+                    _ => 1,
+                }
+            }
+            Instruction::Load16Bit(command) => {
+                match command {
+                    LoadU16Cmd::LD(LDInputU16::RRNN(_) | LDInputU16::IISP) => 3,
+                    _ => 1,
+                }
+            }
+            Instruction::ArithmeticLogical8Bit(command) => {
+                match command {
+                    AritLogiU8Cmd::ADD(CompoundInputU8::Immediate) | AritLogiU8Cmd::ADC(CompoundInputU8::Immediate)
+                    | AritLogiU8Cmd::SUB(CompoundInputU8::Immediate) | AritLogiU8Cmd::SBC(CompoundInputU8::Immediate)
+                    | AritLogiU8Cmd::AND(CompoundInputU8::Immediate) | AritLogiU8Cmd::XOR(CompoundInputU8::Immediate)
+                    | AritLogiU8Cmd::OR(CompoundInputU8::Immediate) | AritLogiU8Cmd::CP(CompoundInputU8::Immediate) => 2,
+                    _ => 1,
+                }
+            }
+            Instruction::ArithmeticLogical16Bit(command) => {
+                match command {
+                    AritLogiU16Cmd::ADDSP | AritLogiU16Cmd::LDHLSP => 2,
+                    _ => 1,
+                }
+            }
+            Instruction::RotateShift(command) => {
+                match command {
+                    RSCmd::RLCA | RSCmd::RLA | RSCmd::RRCA | RSCmd::RRA => 1,
+                    // the rest are prefixed
+                    _ => 2,
+                }
+            }
+            Instruction::SingleBit(_) => 2,
+            Instruction::Control(command) => {
+                match command {
+                    // STOP is followed by a byte the CPU skips over (0x00 in anything assembled properly)
+                    CtrCmd::STOP => 2,
+                    _ => 1,
+                }
+            }
+            Instruction::Jump(command) => {
+                match command {
+                    JmpCmd::JP(JPInput::HL) => 1,
+                    JmpCmd::JP(_) | JmpCmd::CALL(_) => 3,
+                    JmpCmd::JR(_) => 2,
+                    JmpCmd::RET(_) | JmpCmd::RETI | JmpCmd::RST(_) => 1,
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,535 @@
+//! A small assembler for SM83 mnemonics, so tests can be written as assembly rather than bytes.
+//! It takes the syntax the disassembler writes (and the opcode table uses), so anything
+//! disassembled assembles back to the same bytes:
+//! ```text
+//! start:  LD A,$2A        ; immediates are $hex, 0xhex or decimal
+//!         LD (HL+),A      ; (HLI) and LDI (HL),A work too
+//!         LDH ($FF44),A   ; the high page, ($44) works too
+//!         DEC B
+//!         JR NZ,start     ; relative jumps take a label, an address, or $+n from the JR itself
+//!         DB $DD, 1, 2    ; raw bytes
+//! ```
+//! Mnemonics and registers are case-insensitive, labels aren't. `A,` can be left off the ALU
+//! operations that always work on A (`ADD B` is `ADD A,B`), and `JP (HL)` is `JP HL`.
+
+#[cfg(test)]
+mod tests;
+
+use super::*;
+use crate::cpu::register::{ RegisterU8, RegisterU16 };
+
+#[derive(Debug)]
+pub struct AssembleError {
+    /// 1-based, like an editor shows it
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+/// Assembles `source` as if it's loaded at `origin`, which is where labels and `$` count from
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AssembleError> {
+    // the first pass works out every statement and where it goes, which doesn't depend on what
+    // the labels are, then the second fills in the immediates now the labels are known
+    let mut labels: Vec<(String, u16)> = Vec::new();
+    let mut statements = Vec::new();
+    let mut address = origin as u32;
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: String| AssembleError { line: index + 1, message };
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("{label:?} isn't a label name")));
+            }
+            if labels.iter().any(|(existing, _)| existing == label) {
+                return Err(error(format!("{label} is defined twice")));
+            }
+            labels.push((label.to_string(), address as u16));
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line).map_err(error)?;
+        let at = address as u16;
+        address += statement.length() as u32;
+        if address > 0x1_0000 {
+            return Err(error("the program runs past the end of memory".to_string()));
+        }
+        statements.push((index + 1, at, statement));
+    }
+
+    let mut bytes = Vec::new();
+    for (line, at, statement) in statements {
+        let error = |message: String| AssembleError { line, message };
+        match statement {
+            Statement::Bytes(values) => {
+                for value in values {
+                    let value = value.value(at, &labels).map_err(error)?;
+                    bytes.push(operand_u8(value).map_err(error)?);
+                }
+            }
+            Statement::Instruction(instruction, immediate) => {
+                let immediate = immediate.resolve(at, &labels).map_err(error)?;
+                bytes.extend(instruction.encode(immediate));
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+enum Statement {
+    Instruction(Instruction, Immediate),
+    /// `DB`
+    Bytes(Vec<Expression>),
+}
+
+impl Statement {
+    fn length(&self) -> usize {
+        match self {
+            Statement::Instruction(instruction, _) => instruction.length() as usize,
+            Statement::Bytes(values) => values.len(),
+        }
+    }
+}
+
+/// A number or label, to be worked out once every label is known
+enum Expression {
+    Number(i64),
+    Label(String),
+    /// `$`, `$+n` or `$-n`: relative to the start of the instruction it's in
+    Here(i64),
+}
+
+impl Expression {
+    fn value(&self, at: u16, labels: &[(String, u16)]) -> Result<i64, String> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Here(offset) => Ok(at as i64 + offset),
+            Expression::Label(label) => {
+                labels.iter()
+                    .find(|(name, _)| name == label)
+                    .map(|(_, address)| *address as i64)
+                    .ok_or_else(|| format!("There's no label {label}"))
+            }
+        }
+    }
+
+    /// The value if it's a plain number, for operands that can't be labels (bit numbers, RST)
+    fn number(&self) -> Option<i64> {
+        match self {
+            Expression::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// How an instruction's immediate is worked out
+enum Immediate {
+    None,
+    U8(Expression),
+    U16(Expression),
+    /// `ADD SP,e` and `LD HL,SP+e`
+    Signed(Expression),
+    /// A JR target, encoded as the distance from the instruction after the JR
+    Relative(Expression),
+    /// An LDH address, given in full (0xFF00-0xFFFF) or as just the low byte
+    HighPage(Expression),
+}
+
+impl Immediate {
+    fn resolve(&self, at: u16, labels: &[(String, u16)]) -> Result<u16, String> {
+        match self {
+            Immediate::None => Ok(0),
+            Immediate::U8(expression) => Ok(operand_u8(expression.value(at, labels)?)? as u16),
+            Immediate::U16(expression) => {
+                let value = expression.value(at, labels)?;
+                match value {
+                    -0x8000..=0xFFFF => Ok(value as u16),
+                    _ => Err(format!("{value} doesn't fit in 16 bits")),
+                }
+            }
+            Immediate::Signed(expression) => {
+                let value = expression.value(at, labels)?;
+                match value {
+                    -0x80..=0x7F => Ok(value as i8 as u8 as u16),
+                    _ => Err(format!("{value} doesn't fit in a signed byte")),
+                }
+            }
+            Immediate::Relative(expression) => {
+                let distance = expression.value(at, labels)? - (at as i64 + 2);
+                match distance {
+                    -0x80..=0x7F => Ok(distance as i8 as u8 as u16),
+                    _ => Err(format!("The target is {distance} bytes away, too far for JR")),
+                }
+            }
+            Immediate::HighPage(expression) => {
+                let value = expression.value(at, labels)?;
+                match value {
+                    0x00..=0xFF | 0xFF00..=0xFFFF => Ok(value as u16 & 0xFF),
+                    _ => Err(format!("{value:#06X} isn't in the high page (0xFF00-0xFFFF)")),
+                }
+            }
+        }
+    }
+}
+
+fn operand_u8(value: i64) -> Result<u8, String> {
+    match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(format!("{value} doesn't fit in a byte")),
+    }
+}
+
+/// An operand, worked out as far as it can be without knowing the instruction
+enum Operand {
+    R8(RegisterU8),
+    R16(RegisterU16),
+    /// `(HL)`
+    HL,
+    /// `(HL+)`
+    HLIncrement,
+    /// `(HL-)`
+    HLDecrement,
+    /// `(BC)`
+    BC,
+    /// `(DE)`
+    DE,
+    /// `(C)`, the high page at C
+    IoC,
+    /// `SP+e` or `SP-e`
+    SPOffset(Expression),
+    /// `(nn)`
+    Memory(Expression),
+    Immediate(Expression),
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = text.to_ascii_uppercase();
+
+    let operand = match upper.as_str() {
+        "A" => Operand::R8(RegisterU8::A),
+        "B" => Operand::R8(RegisterU8::B),
+        "C" => Operand::R8(RegisterU8::C),
+        "D" => Operand::R8(RegisterU8::D),
+        "E" => Operand::R8(RegisterU8::E),
+        "H" => Operand::R8(RegisterU8::H),
+        "L" => Operand::R8(RegisterU8::L),
+        "BC" => Operand::R16(RegisterU16::BC),
+        "DE" => Operand::R16(RegisterU16::DE),
+        "HL" => Operand::R16(RegisterU16::HL),
+        "SP" => Operand::R16(RegisterU16::SP),
+        "AF" => Operand::R16(RegisterU16::AF),
+        "(HL)" => Operand::HL,
+        "(HL+)" | "(HLI)" => Operand::HLIncrement,
+        "(HL-)" | "(HLD)" => Operand::HLDecrement,
+        "(BC)" => Operand::BC,
+        "(DE)" => Operand::DE,
+        "(C)" | "($FF00+C)" | "(0XFF00+C)" => Operand::IoC,
+        _ => {
+            if upper.starts_with("SP+") || upper.starts_with("SP-") {
+                let offset = parse_number(&text[2..]).ok_or_else(|| format!("{text} isn't an SP offset"))?;
+                return Ok(Operand::SPOffset(Expression::Number(offset)));
+            }
+            match text.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+                Some(inner) => Operand::Memory(parse_expression(inner)?),
+                None => Operand::Immediate(parse_expression(&text)?),
+            }
+        }
+    };
+    Ok(operand)
+}
+
+fn parse_expression(text: &str) -> Result<Expression, String> {
+    let not_a_number = || format!("{text} isn't a number or label");
+    if let Some(offset) = text.strip_prefix('$').filter(|rest| rest.is_empty() || rest.starts_with(['+', '-'])) {
+        return match offset {
+            "" => Ok(Expression::Here(0)),
+            _ => parse_number(offset).map(Expression::Here).ok_or_else(not_a_number),
+        };
+    }
+    if is_label(text) {
+        return Ok(Expression::Label(text.to_string()));
+    }
+    parse_number(text).map(Expression::Number).ok_or_else(not_a_number)
+}
+
+/// `$hex`, `0xhex` or decimal, with an optional sign in front
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_statement(line: &str) -> Result<Statement, String> {
+    let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic.to_ascii_uppercase(), operands.trim()),
+        None => (line.to_ascii_uppercase(), ""),
+    };
+    let operands: Vec<&str> = match operands {
+        "" => Vec::new(),
+        _ => operands.split(',').map(str::trim).collect(),
+    };
+
+    if mnemonic == "DB" {
+        if operands.is_empty() {
+            return Err("DB needs at least one byte".to_string());
+        }
+        return operands.iter().map(|operand| parse_expression(operand)).collect::<Result<_, _>>().map(Statement::Bytes);
+    }
+
+    // conditions look like registers (C especially), so they're picked out before the operands
+    // get parsed
+    let jumps = ["JP", "JR", "CALL", "RET"];
+    let condition = match operands.first() {
+        Some(first) if jumps.contains(&mnemonic.as_str()) && (operands.len() == 2 || mnemonic == "RET") => {
+            Some(parse_condition(first)?)
+        }
+        _ => None,
+    };
+    let operands = match condition {
+        Some(_) => &operands[1..],
+        None => &operands[..],
+    };
+
+    let parsed = operands.iter().map(|operand| parse_operand(operand)).collect::<Result<Vec<_>, _>>()?;
+    let count = parsed.len();
+    let mut parsed = parsed.into_iter();
+    let unknown = || format!("{line} isn't an instruction");
+    let (instruction, immediate) = build_instruction(&mnemonic, condition, parsed.next(), parsed.next(), count).ok_or_else(unknown)?;
+    Ok(Statement::Instruction(instruction, immediate))
+}
+
+fn parse_condition(text: &str) -> Result<JmpCmdCondition, String> {
+    match text.to_ascii_uppercase().as_str() {
+        "NZ" => Ok(JmpCmdCondition::NZ),
+        "Z" => Ok(JmpCmdCondition::Z),
+        "NC" => Ok(JmpCmdCondition::NC),
+        "C" => Ok(JmpCmdCondition::C),
+        _ => Err(format!("{text} isn't a condition")),
+    }
+}
+
+/// The instruction for a mnemonic and its operands (with any condition already taken off the
+/// front), or `None` if there isn't one
+fn build_instruction(
+    mnemonic: &str,
+    condition: Option<JmpCmdCondition>,
+    first: Option<Operand>,
+    second: Option<Operand>,
+    count: usize,
+) -> Option<(Instruction, Immediate)> {
+    if count > 2 {
+        return None;
+    }
+    let none = Immediate::None;
+
+    let built = match (mnemonic, condition, first, second) {
+        ("NOP", None, None, None) => (Instruction::Control(CtrCmd::NOP), none),
+        ("HALT", None, None, None) => (Instruction::Control(CtrCmd::HALT), none),
+        ("STOP", None, None | Some(Operand::Immediate(Expression::Number(0))), None) => (Instruction::Control(CtrCmd::STOP), none),
+        ("DI", None, None, None) => (Instruction::Control(CtrCmd::DI), none),
+        ("EI", None, None, None) => (Instruction::Control(CtrCmd::EI), none),
+        ("CCF", None, None, None) => (Instruction::Control(CtrCmd::CCF), none),
+        ("SCF", None, None, None) => (Instruction::Control(CtrCmd::SCF), none),
+        ("DAA", None, None, None) => (Instruction::ArithmeticLogical8Bit(AritLogiU8Cmd::DAA), none),
+        ("CPL", None, None, None) => (Instruction::ArithmeticLogical8Bit(AritLogiU8Cmd::CPL), none),
+        ("RLCA", None, None, None) => (Instruction::RotateShift(RSCmd::RLCA), none),
+        ("RLA", None, None, None) => (Instruction::RotateShift(RSCmd::RLA), none),
+        ("RRCA", None, None, None) => (Instruction::RotateShift(RSCmd::RRCA), none),
+        ("RRA", None, None, None) => (Instruction::RotateShift(RSCmd::RRA), none),
+
+        // ---- 8-bit loads
+        ("LD", None, Some(Operand::R8(r1)), Some(Operand::R8(r2))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::RR(r1, r2))), none)
+        }
+        ("LD", None, Some(Operand::R8(r)), Some(Operand::Immediate(value))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::RI(r))), Immediate::U8(value))
+        }
+        ("LD", None, Some(Operand::R8(r)), Some(Operand::HL)) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::RHL(r))), none),
+        ("LD", None, Some(Operand::HL), Some(Operand::R8(r))) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::HLR(r))), none),
+        ("LD", None, Some(Operand::HL), Some(Operand::Immediate(value))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::HLI)), Immediate::U8(value))
+        }
+        ("LD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::BC)) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::ABC)), none),
+        ("LD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::DE)) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::ADE)), none),
+        ("LD", None, Some(Operand::BC), Some(Operand::R8(RegisterU8::A))) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::BCA)), none),
+        ("LD", None, Some(Operand::DE), Some(Operand::R8(RegisterU8::A))) => (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::DEA)), none),
+        ("LD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::Memory(address))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::AII)), Immediate::U16(address))
+        }
+        ("LD", None, Some(Operand::Memory(address)), Some(Operand::R8(RegisterU8::A))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::IIA)), Immediate::U16(address))
+        }
+        ("LD" | "LDH", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::IoC)) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::ReadIoC)), none)
+        }
+        ("LD" | "LDH", None, Some(Operand::IoC), Some(Operand::R8(RegisterU8::A))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::WriteIoC)), none)
+        }
+        ("LDH", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::Memory(address))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::ReadIoN)), Immediate::HighPage(address))
+        }
+        ("LDH", None, Some(Operand::Memory(address)), Some(Operand::R8(RegisterU8::A))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::WriteIoN)), Immediate::HighPage(address))
+        }
+        ("LD", None, Some(Operand::HLIncrement), Some(Operand::R8(RegisterU8::A)))
+        | ("LDI", None, Some(Operand::HL), Some(Operand::R8(RegisterU8::A))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LDI(LDIInputU8::HLA)), none)
+        }
+        ("LD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::HLIncrement))
+        | ("LDI", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::HL)) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LDI(LDIInputU8::AHL)), none)
+        }
+        ("LD", None, Some(Operand::HLDecrement), Some(Operand::R8(RegisterU8::A)))
+        | ("LDD", None, Some(Operand::HL), Some(Operand::R8(RegisterU8::A))) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LDD(LDDInputU8::HLA)), none)
+        }
+        ("LD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::HLDecrement))
+        | ("LDD", None, Some(Operand::R8(RegisterU8::A)), Some(Operand::HL)) => {
+            (Instruction::Load8Bit(LoadU8Cmd::LDD(LDDInputU8::AHL)), none)
+        }
+
+        // ---- 16-bit loads
+        ("LD", None, Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::SP))), Some(Operand::Immediate(value))) => {
+            (Instruction::Load16Bit(LoadU16Cmd::LD(LDInputU16::RRNN(rr))), Immediate::U16(value))
+        }
+        ("LD", None, Some(Operand::R16(RegisterU16::SP)), Some(Operand::R16(RegisterU16::HL))) => {
+            (Instruction::Load16Bit(LoadU16Cmd::LD(LDInputU16::SPHL)), none)
+        }
+        ("LD", None, Some(Operand::Memory(address)), Some(Operand::R16(RegisterU16::SP))) => {
+            (Instruction::Load16Bit(LoadU16Cmd::LD(LDInputU16::IISP)), Immediate::U16(address))
+        }
+        ("LD", None, Some(Operand::R16(RegisterU16::HL)), Some(Operand::SPOffset(offset))) => {
+            (Instruction::ArithmeticLogical16Bit(AritLogiU16Cmd::LDHLSP), Immediate::Signed(offset))
+        }
+        ("PUSH", None, Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::AF))), None) => {
+            (Instruction::Load16Bit(LoadU16Cmd::PUSH(InputU16(rr))), none)
+        }
+        ("POP", None, Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::AF))), None) => {
+            (Instruction::Load16Bit(LoadU16Cmd::POP(InputU16(rr))), none)
+        }
+
+        // ---- arithmetic and logic
+        ("ADD", None, Some(Operand::R16(RegisterU16::HL)), Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::SP)))) => {
+            (Instruction::ArithmeticLogical16Bit(AritLogiU16Cmd::ADDHL(InputU16(rr))), none)
+        }
+        ("ADD", None, Some(Operand::R16(RegisterU16::SP)), Some(Operand::Immediate(offset))) => {
+            (Instruction::ArithmeticLogical16Bit(AritLogiU16Cmd::ADDSP), Immediate::Signed(offset))
+        }
+        (operation @ ("ADD" | "ADC" | "SUB" | "SBC" | "AND" | "XOR" | "OR" | "CP"), None, Some(Operand::R8(RegisterU8::A)), Some(input))
+        | (operation @ ("ADD" | "ADC" | "SUB" | "SBC" | "AND" | "XOR" | "OR" | "CP"), None, Some(input), None) => {
+            let (input, immediate) = match input {
+                Operand::R8(r) => (CompoundInputU8::Register(r), none),
+                Operand::HL => (CompoundInputU8::Address, none),
+                Operand::Immediate(value) => (CompoundInputU8::Immediate, Immediate::U8(value)),
+                _ => return None,
+            };
+            let command = match operation {
+                "ADD" => AritLogiU8Cmd::ADD(input),
+                "ADC" => AritLogiU8Cmd::ADC(input),
+                "SUB" => AritLogiU8Cmd::SUB(input),
+                "SBC" => AritLogiU8Cmd::SBC(input),
+                "AND" => AritLogiU8Cmd::AND(input),
+                "XOR" => AritLogiU8Cmd::XOR(input),
+                "OR" => AritLogiU8Cmd::OR(input),
+                _ => AritLogiU8Cmd::CP(input),
+            };
+            (Instruction::ArithmeticLogical8Bit(command), immediate)
+        }
+        ("INC", None, Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::SP))), None) => {
+            (Instruction::ArithmeticLogical16Bit(AritLogiU16Cmd::INC(InputU16(rr))), none)
+        }
+        ("DEC", None, Some(Operand::R16(rr @ (RegisterU16::BC | RegisterU16::DE | RegisterU16::HL | RegisterU16::SP))), None) => {
+            (Instruction::ArithmeticLogical16Bit(AritLogiU16Cmd::DEC(InputU16(rr))), none)
+        }
+        ("INC", None, Some(input), None) => (Instruction::ArithmeticLogical8Bit(AritLogiU8Cmd::INC(double_input(input)?)), none),
+        ("DEC", None, Some(input), None) => (Instruction::ArithmeticLogical8Bit(AritLogiU8Cmd::DEC(double_input(input)?)), none),
+
+        // ---- CB-prefixed
+        (operation @ ("RLC" | "RL" | "RRC" | "RR" | "SLA" | "SWAP" | "SRA" | "SRL"), None, Some(input), None) => {
+            let input = double_input(input)?;
+            let command = match operation {
+                "RLC" => RSCmd::RLC(input),
+                "RL" => RSCmd::RL(input),
+                "RRC" => RSCmd::RRC(input),
+                "RR" => RSCmd::RR(input),
+                "SLA" => RSCmd::SLA(input),
+                "SWAP" => RSCmd::SWAP(input),
+                "SRA" => RSCmd::SRA(input),
+                _ => RSCmd::SRL(input),
+            };
+            (Instruction::RotateShift(command), none)
+        }
+        (operation @ ("BIT" | "RES" | "SET"), None, Some(Operand::Immediate(bit)), Some(input)) => {
+            let bit = bit.number().filter(|bit| (0..8).contains(bit))? as U3;
+            let input = BitInput(bit, double_input(input)?);
+            let command = match operation {
+                "BIT" => BitCmd::BIT(input),
+                "RES" => BitCmd::RES(input),
+                _ => BitCmd::SET(input),
+            };
+            (Instruction::SingleBit(command), none)
+        }
+
+        // ---- jumps
+        ("JP", None, Some(Operand::R16(RegisterU16::HL) | Operand::HL), None) => (Instruction::Jump(JmpCmd::JP(JPInput::HL)), none),
+        ("JP", None, Some(Operand::Immediate(target)), None) => (Instruction::Jump(JmpCmd::JP(JPInput::Direct)), Immediate::U16(target)),
+        ("JP", Some(cond), Some(Operand::Immediate(target)), None) => {
+            (Instruction::Jump(JmpCmd::JP(JPInput::Conditional(cond))), Immediate::U16(target))
+        }
+        ("JR", cond, Some(Operand::Immediate(target)), None) => {
+            (Instruction::Jump(JmpCmd::JR(jump_input(cond))), Immediate::Relative(target))
+        }
+        ("CALL", cond, Some(Operand::Immediate(target)), None) => {
+            (Instruction::Jump(JmpCmd::CALL(jump_input(cond))), Immediate::U16(target))
+        }
+        ("RET", cond, None, None) => (Instruction::Jump(JmpCmd::RET(jump_input(cond))), none),
+        ("RETI", None, None, None) => (Instruction::Jump(JmpCmd::RETI), none),
+        ("RST", None, Some(Operand::Immediate(vector)), None) => {
+            let vector = vector.number().filter(|vector| (0..=0x38).contains(vector) && vector % 8 == 0)?;
+            (Instruction::Jump(JmpCmd::RST(RstVector::from_opcode(vector as u8))), none)
+        }
+
+        _ => return None,
+    };
+    Some(built)
+}
+
+fn double_input(operand: Operand) -> Option<DoubleInputU8> {
+    match operand {
+        Operand::R8(r) => Some(DoubleInputU8::Register(r)),
+        Operand::HL => Some(DoubleInputU8::Address),
+        _ => None,
+    }
+}
+
+fn jump_input(condition: Option<JmpCmdCondition>) -> JmpCmdInput {
+    match condition {
+        Some(cond) => JmpCmdInput::Conditional(cond),
+        None => JmpCmdInput::Direct,
+    }
+}
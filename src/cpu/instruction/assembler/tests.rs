@@ -0,0 +1,85 @@
+use super::*;
+
+#[test]
+fn assembles_what_the_disassembler_writes() {
+    for (prefixed, opcode) in (0..=0xFF).map(|opcode| (false, opcode)).chain((0..=0xFF).map(|opcode| (true, opcode))) {
+        let bytes = match prefixed {
+            true => vec![0xCB, opcode],
+            false => vec![opcode, 0x34, 0x12],
+        };
+        let Disassembly { text, length } = disassemble(&bytes);
+        if text.starts_with("DB") {
+            continue;
+        }
+        // STOP's second byte is always written as 0
+        let bytes = if text == "STOP" { vec![0x10, 0x00] } else { bytes };
+
+        let assembled = assemble(&text, 0x0150).unwrap_or_else(|err| panic!("{text}: {err}"));
+        assert_eq!(bytes[..length as usize], assembled[..], "{text}");
+    }
+}
+
+#[test]
+fn resolves_labels_both_ways() {
+    let source = "
+        start:  LD B,3          ; counts down
+        loop:   DEC B
+                JR NZ,loop
+                CALL done
+                JP start
+        done:   RET
+    ";
+    assert_eq!(
+        vec![0x06, 0x03, 0x05, 0x20, 0xFD, 0xCD, 0x5B, 0x01, 0xC3, 0x50, 0x01, 0xC9],
+        assemble(source, 0x0150).unwrap(),
+    );
+}
+
+#[test]
+fn takes_the_usual_spellings() {
+    let source = "
+        ld a,(hli)
+        LD (HLD),A
+        LDI (HL),A
+        LDH A,($44)
+        LDH ($FF44),A
+        LD A,(C)
+        ADD B
+        SUB A,$10
+        JP (HL)
+        JR $
+        STOP 0
+        RST $38
+        LD HL,SP-2
+        ADD SP,0x7F
+        DB 1, $FF, -1
+    ";
+    assert_eq!(
+        vec![
+            0x2A, 0x32, 0x22, 0xF0, 0x44, 0xE0, 0x44, 0xF2, 0x80, 0xD6, 0x10, 0xE9, 0x18, 0xFE, 0x10, 0x00,
+            0xFF, 0xF8, 0xFE, 0xE8, 0x7F, 0x01, 0xFF, 0xFF,
+        ],
+        assemble(source, 0x0000).unwrap(),
+    );
+}
+
+#[test]
+fn reports_the_line_of_an_error() {
+    for (source, line, message) in [
+        ("NOP\nLD A,(HL", 2, "isn't a number or label"),
+        ("NOP\n\nFOO A", 3, "isn't an instruction"),
+        ("LD B,B\nLD (BC),B", 2, "isn't an instruction"),
+        ("JP nowhere", 1, "There's no label nowhere"),
+        ("LD A,256", 1, "doesn't fit in a byte"),
+        ("LDH A,($C000)", 1, "isn't in the high page"),
+        ("BIT 8,A", 1, "isn't an instruction"),
+        ("RST $07", 1, "isn't an instruction"),
+        ("x: NOP\nx: NOP", 2, "defined twice"),
+    ] {
+        let err = assemble(source, 0).expect_err(source);
+        assert_eq!(line, err.line, "{source:?}: {err}");
+        assert!(err.message.contains(message), "{source:?}: {err}");
+    }
+    let far = format!("JR far\nDB {}\nfar: NOP", vec!["0"; 200].join(","));
+    assert!(assemble(&far, 0).unwrap_err().message.contains("too far for JR"));
+}
@@ -64,31 +64,32 @@ pub enum RSCmd {
 /// Single Bit Operation command set
 #[cfg_attr(test, derive(Debug))]
 pub enum BitCmd {
-    BIT(BitInput), // todo!()
-    RES(BitInput), // todo!()
-    SET(BitInput), // todo!()
+    BIT(BitInput),
+    RES(BitInput),
+    SET(BitInput),
 }
 
 // CPU Control command set
 #[cfg_attr(test, derive(Debug))]
 pub enum CtrCmd {
-    // todo!("Implement")
     CCF,
     SCF,
-    NOP, // todo!()
-    HALT, // todo!()
-    STOP, // todo!()
-    DI, // todo!()
-    EI, // todo!()
+    NOP,
+    HALT,
+    STOP,
+    DI,
+    EI,
+    /// One of the 11 opcodes that aren't instructions (0xD3, 0xDB, ...). Running one locks the
+    /// CPU up for good.
+    ILLEGAL(u8),
 }
 
 #[cfg_attr(test, derive(Debug))]
 pub enum JmpCmd {
-    // todo!("Figure out inputs & implement")
     JP(JPInput),
     JR(JmpCmdInput), 
-    CALL(JmpCmdInput), // todo!()
-    RET(JmpCmdInput), // todo!()
-    RETI, // todo!()
-    RST(), // todo!()
+    CALL(JmpCmdInput),
+    RET(JmpCmdInput),
+    RETI,
+    RST(RstVector),
 }
\ No newline at end of file
@@ -0,0 +1,228 @@
+//! Turning instructions back into text, in the syntax of the opcode table (opcodes.txt) with the
+//! immediates filled in: `LD A,(HL+)`, `JR NZ,$+5`, `LDH ($FF44),A`, `BIT 3,(HL)`.
+//!
+//! Relative jumps are written relative to the address of the JR itself, and the signed offsets
+//! of `ADD SP,dd` and `LD HL,SP+dd` in decimal. Anything that isn't an instruction comes out as
+//! a `DB` of its first byte.
+
+use super::*;
+use crate::cpu::register::{ RegisterU8, RegisterU16 };
+use crate::memory_bus::MemoryBus;
+
+/// The longest an instruction gets in bytes
+const MAX_LENGTH: u16 = 3;
+
+/// An instruction as text, and how many bytes it takes up
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Disassembly {
+    pub text: String,
+    pub length: u8,
+}
+
+/// Disassembles the instruction at the start of `bytes`, which mustn't be empty. An instruction
+/// cut off by the end of `bytes` comes out as a `DB` of its first byte.
+pub fn disassemble(bytes: &[u8]) -> Disassembly {
+    let opcode = bytes[0];
+    let decoded = match (opcode, bytes.get(1)) {
+        (0xCB, Some(&prefixed_opcode)) => Instruction::from_byte(prefixed_opcode, true),
+        (opcode, _) => Instruction::from_byte(opcode, false),
+    };
+    let Ok(instruction) = decoded else {
+        return data_byte(opcode);
+    };
+
+    let length = instruction.length();
+    match bytes.get(1..length as usize) {
+        Some(operands) => Disassembly { text: instruction.text(operands), length },
+        None => data_byte(opcode),
+    }
+}
+
+/// Disassembles the instruction at `address`, reading it through `bus` without side effects
+pub fn disassemble_at(bus: &MemoryBus, address: u16) -> Disassembly {
+    let bytes: Vec<u8> = (0..MAX_LENGTH).map(|offset| bus.peek_byte(address.wrapping_add(offset))).collect();
+    disassemble(&bytes)
+}
+
+fn data_byte(byte: u8) -> Disassembly {
+    Disassembly { text: format!("DB ${byte:02X}"), length: 1 }
+}
+
+impl Instruction {
+    /// The text of this instruction, with `operands` being the bytes after the opcode (none for
+    /// prefixed instructions as they don't have immediates)
+    fn text(&self, operands: &[u8]) -> String {
+        let n8 = || format!("${:02X}", operands[0]);
+        let n16 = || format!("${:04X}", u16::from_le_bytes([operands[0], operands[1]]));
+        let signed = || operands[0] as i8;
+        // JR counts from the instruction after it, so from the JR itself that's 2 more
+        let relative = || format!("${:+}", signed() as i16 + 2);
+
+        match self {
+            Instruction::Load8Bit(command) => {
+                match command {
+                    LoadU8Cmd::LD(input) => {
+                        match input {
+                            LDInputU8::RR(r1, r2) => format!("LD {},{}", register_u8(r1), register_u8(r2)),
+                            LDInputU8::RI(r) => format!("LD {},{}", register_u8(r), n8()),
+                            LDInputU8::RHL(r) => format!("LD {},(HL)", register_u8(r)),
+                            LDInputU8::HLR(r) => format!("LD (HL),{}", register_u8(r)),
+                            LDInputU8::HLI => format!("LD (HL),{}", n8()),
+                            LDInputU8::ABC => "LD A,(BC)".to_string(),
+                            LDInputU8::ADE => "LD A,(DE)".to_string(),
+                            LDInputU8::AII => format!("LD A,({})", n16()),
+                            LDInputU8::BCA => "LD (BC),A".to_string(),
+                            LDInputU8::DEA => "LD (DE),A".to_string(),
+                            LDInputU8::IIA => format!("LD ({}),A", n16()),
+                            LDInputU8::ReadIoN => format!("LDH A,($FF{:02X})", operands[0]),
+                            LDInputU8::WriteIoN => format!("LDH ($FF{:02X}),A", operands[0]),
+                            LDInputU8::ReadIoC => "LD A,($FF00+C)".to_string(),
+                            LDInputU8::WriteIoC => "LD ($FF00+C),A".to_string(),
+                        }
+                    }
+                    LoadU8Cmd::LDI(LDIInputU8::HLA) => "LD (HL+),A".to_string(),
+                    LoadU8Cmd::LDI(LDIInputU8::AHL) => "LD A,(HL+)".to_string(),
+                    LoadU8Cmd::LDD(LDDInputU8::HLA) => "LD (HL-),A".to_string(),
+                    LoadU8Cmd::LDD(LDDInputU8::AHL) => "LD A,(HL-)".to_string(),
+                }
+            }
+
+            Instruction::Load16Bit(command) => {
+                match command {
+                    LoadU16Cmd::LD(LDInputU16::RRNN(rr)) => format!("LD {},{}", register_u16(rr), n16()),
+                    LoadU16Cmd::LD(LDInputU16::SPHL) => "LD SP,HL".to_string(),
+                    LoadU16Cmd::LD(LDInputU16::IISP) => format!("LD ({}),SP", n16()),
+                    LoadU16Cmd::PUSH(InputU16(rr)) => format!("PUSH {}", register_u16(rr)),
+                    LoadU16Cmd::POP(InputU16(rr)) => format!("POP {}", register_u16(rr)),
+                }
+            }
+
+            Instruction::ArithmeticLogical8Bit(command) => {
+                let compound = |input: &CompoundInputU8| {
+                    match input {
+                        CompoundInputU8::Register(r) => register_u8(r).to_string(),
+                        CompoundInputU8::Immediate => n8(),
+                        CompoundInputU8::Address => "(HL)".to_string(),
+                    }
+                };
+                match command {
+                    AritLogiU8Cmd::ADD(input) => format!("ADD A,{}", compound(input)),
+                    AritLogiU8Cmd::ADC(input) => format!("ADC A,{}", compound(input)),
+                    AritLogiU8Cmd::SUB(input) => format!("SUB {}", compound(input)),
+                    AritLogiU8Cmd::SBC(input) => format!("SBC A,{}", compound(input)),
+                    AritLogiU8Cmd::AND(input) => format!("AND {}", compound(input)),
+                    AritLogiU8Cmd::XOR(input) => format!("XOR {}", compound(input)),
+                    AritLogiU8Cmd::OR(input) => format!("OR {}", compound(input)),
+                    AritLogiU8Cmd::CP(input) => format!("CP {}", compound(input)),
+                    AritLogiU8Cmd::INC(input) => format!("INC {}", double_input(input)),
+                    AritLogiU8Cmd::DEC(input) => format!("DEC {}", double_input(input)),
+                    AritLogiU8Cmd::DAA => "DAA".to_string(),
+                    AritLogiU8Cmd::CPL => "CPL".to_string(),
+                }
+            }
+
+            Instruction::ArithmeticLogical16Bit(command) => {
+                match command {
+                    AritLogiU16Cmd::ADDHL(InputU16(rr)) => format!("ADD HL,{}", register_u16(rr)),
+                    AritLogiU16Cmd::INC(InputU16(rr)) => format!("INC {}", register_u16(rr)),
+                    AritLogiU16Cmd::DEC(InputU16(rr)) => format!("DEC {}", register_u16(rr)),
+                    AritLogiU16Cmd::ADDSP => format!("ADD SP,{}", signed()),
+                    AritLogiU16Cmd::LDHLSP => format!("LD HL,SP{:+}", signed()),
+                }
+            }
+
+            Instruction::RotateShift(command) => {
+                match command {
+                    RSCmd::RLCA => "RLCA".to_string(),
+                    RSCmd::RLA => "RLA".to_string(),
+                    RSCmd::RRCA => "RRCA".to_string(),
+                    RSCmd::RRA => "RRA".to_string(),
+                    RSCmd::RLC(input) => format!("RLC {}", double_input(input)),
+                    RSCmd::RL(input) => format!("RL {}", double_input(input)),
+                    RSCmd::RRC(input) => format!("RRC {}", double_input(input)),
+                    RSCmd::RR(input) => format!("RR {}", double_input(input)),
+                    RSCmd::SLA(input) => format!("SLA {}", double_input(input)),
+                    RSCmd::SWAP(input) => format!("SWAP {}", double_input(input)),
+                    RSCmd::SRA(input) => format!("SRA {}", double_input(input)),
+                    RSCmd::SRL(input) => format!("SRL {}", double_input(input)),
+                }
+            }
+
+            Instruction::SingleBit(command) => {
+                match command {
+                    BitCmd::BIT(BitInput(bit, input)) => format!("BIT {bit},{}", double_input(input)),
+                    BitCmd::RES(BitInput(bit, input)) => format!("RES {bit},{}", double_input(input)),
+                    BitCmd::SET(BitInput(bit, input)) => format!("SET {bit},{}", double_input(input)),
+                }
+            }
+
+            Instruction::Control(command) => {
+                match command {
+                    CtrCmd::CCF => "CCF".to_string(),
+                    CtrCmd::SCF => "SCF".to_string(),
+                    CtrCmd::NOP => "NOP".to_string(),
+                    CtrCmd::HALT => "HALT".to_string(),
+                    CtrCmd::STOP => "STOP".to_string(),
+                    CtrCmd::DI => "DI".to_string(),
+                    CtrCmd::EI => "EI".to_string(),
+                    CtrCmd::ILLEGAL(byte) => data_byte(*byte).text,
+                }
+            }
+
+            Instruction::Jump(command) => {
+                match command {
+                    JmpCmd::JP(JPInput::Direct) => format!("JP {}", n16()),
+                    JmpCmd::JP(JPInput::HL) => "JP HL".to_string(),
+                    JmpCmd::JP(JPInput::Conditional(cond)) => format!("JP {},{}", condition(*cond), n16()),
+                    JmpCmd::JR(JmpCmdInput::Direct) => format!("JR {}", relative()),
+                    JmpCmd::JR(JmpCmdInput::Conditional(cond)) => format!("JR {},{}", condition(*cond), relative()),
+                    JmpCmd::CALL(JmpCmdInput::Direct) => format!("CALL {}", n16()),
+                    JmpCmd::CALL(JmpCmdInput::Conditional(cond)) => format!("CALL {},{}", condition(*cond), n16()),
+                    JmpCmd::RET(JmpCmdInput::Direct) => "RET".to_string(),
+                    JmpCmd::RET(JmpCmdInput::Conditional(cond)) => format!("RET {}", condition(*cond)),
+                    JmpCmd::RETI => "RETI".to_string(),
+                    JmpCmd::RST(vector) => format!("RST ${:02X}", vector.address()),
+                }
+            }
+        }
+    }
+}
+
+fn register_u8(register: &RegisterU8) -> &'static str {
+    match register {
+        RegisterU8::A => "A",
+        RegisterU8::B => "B",
+        RegisterU8::C => "C",
+        RegisterU8::D => "D",
+        RegisterU8::E => "E",
+        RegisterU8::H => "H",
+        RegisterU8::L => "L",
+    }
+}
+
+fn register_u16(register: &RegisterU16) -> &'static str {
+    match register {
+        RegisterU16::BC => "BC",
+        RegisterU16::DE => "DE",
+        RegisterU16::HL => "HL",
+        RegisterU16::SP => "SP",
+        RegisterU16::AF => "AF",
+    }
+}
+
+fn double_input(input: &DoubleInputU8) -> &'static str {
+    match input {
+        DoubleInputU8::Register(r) => register_u8(r),
+        DoubleInputU8::Address => "(HL)",
+    }
+}
+
+fn condition(cond: JmpCmdCondition) -> &'static str {
+    match cond {
+        JmpCmdCondition::NZ => "NZ",
+        JmpCmdCondition::Z => "Z",
+        JmpCmdCondition::NC => "NC",
+        JmpCmdCondition::C => "C",
+    }
+}
@@ -0,0 +1,203 @@
+//! The other way around from decoding: turning an [`Instruction`] (and its immediate) back into
+//! the bytes it's made of.
+//!
+//! Opcodes are built from the fields the decoder takes apart: the 8-bit registers are numbered
+//! B, C, D, E, H, L, (HL), A from 0 to 7, the register pairs BC, DE, HL, SP (or AF for PUSH and
+//! POP) from 0 to 3, and the conditions NZ, Z, NC, C from 0 to 3.
+
+use super::*;
+use crate::cpu::register::{ RegisterU8, RegisterU16 };
+
+impl Instruction {
+    /// The bytes of this instruction with `immediate` as its operand, as many of them as
+    /// [`Instruction::length`] says: the low byte of `immediate` for 8-bit operands (a signed
+    /// offset being its two's complement), both bytes little-endian for 16-bit ones, nothing for
+    /// instructions without an operand
+    pub fn encode(&self, immediate: u16) -> Vec<u8> {
+        let mut bytes = match self.opcode() {
+            (true, opcode) => vec![0xCB, opcode],
+            (false, opcode) => vec![opcode],
+        };
+        let operand_bytes = self.length() as usize - bytes.len();
+        bytes.extend_from_slice(&immediate.to_le_bytes()[..operand_bytes]);
+        bytes
+    }
+
+    /// Whether this instruction is CB-prefixed, and its opcode
+    pub fn opcode(&self) -> (bool, u8) {
+        match self {
+            Instruction::Load8Bit(command) => {
+                let opcode = match command {
+                    LoadU8Cmd::LD(input) => {
+                        match input {
+                            LDInputU8::RR(r1, r2) => 0x40 | register_u8(r1) << 3 | register_u8(r2),
+                            LDInputU8::RI(r) => 0x06 | register_u8(r) << 3,
+                            LDInputU8::RHL(r) => 0x46 | register_u8(r) << 3,
+                            LDInputU8::HLR(r) => 0x70 | register_u8(r),
+                            LDInputU8::HLI => 0x36,
+                            LDInputU8::ABC => 0x0A,
+                            LDInputU8::ADE => 0x1A,
+                            LDInputU8::AII => 0xFA,
+                            LDInputU8::BCA => 0x02,
+                            LDInputU8::DEA => 0x12,
+                            LDInputU8::IIA => 0xEA,
+                            LDInputU8::ReadIoN => 0xF0,
+                            LDInputU8::WriteIoN => 0xE0,
+                            LDInputU8::ReadIoC => 0xF2,
+                            LDInputU8::WriteIoC => 0xE2,
+                        }
+                    }
+                    LoadU8Cmd::LDI(LDIInputU8::HLA) => 0x22,
+                    LoadU8Cmd::LDI(LDIInputU8::AHL) => 0x2A,
+                    LoadU8Cmd::LDD(LDDInputU8::HLA) => 0x32,
+                    LoadU8Cmd::LDD(LDDInputU8::AHL) => 0x3A,
+                };
+                (false, opcode)
+            }
+
+            Instruction::Load16Bit(command) => {
+                let opcode = match command {
+                    LoadU16Cmd::LD(LDInputU16::RRNN(rr)) => 0x01 | register_u16(rr) << 4,
+                    LoadU16Cmd::LD(LDInputU16::SPHL) => 0xF9,
+                    LoadU16Cmd::LD(LDInputU16::IISP) => 0x08,
+                    LoadU16Cmd::PUSH(InputU16(rr)) => 0xC5 | register_u16(rr) << 4,
+                    LoadU16Cmd::POP(InputU16(rr)) => 0xC1 | register_u16(rr) << 4,
+                };
+                (false, opcode)
+            }
+
+            Instruction::ArithmeticLogical8Bit(command) => {
+                // the eight ALU operations share a layout: 0x80 + op * 8 + register, with
+                // 0xC6 + op * 8 taking an immediate instead
+                let alu = |operation: u8, input: &CompoundInputU8| {
+                    match input {
+                        CompoundInputU8::Register(r) => 0x80 | operation << 3 | register_u8(r),
+                        CompoundInputU8::Address => 0x86 | operation << 3,
+                        CompoundInputU8::Immediate => 0xC6 | operation << 3,
+                    }
+                };
+                let opcode = match command {
+                    AritLogiU8Cmd::ADD(input) => alu(0, input),
+                    AritLogiU8Cmd::ADC(input) => alu(1, input),
+                    AritLogiU8Cmd::SUB(input) => alu(2, input),
+                    AritLogiU8Cmd::SBC(input) => alu(3, input),
+                    AritLogiU8Cmd::AND(input) => alu(4, input),
+                    AritLogiU8Cmd::XOR(input) => alu(5, input),
+                    AritLogiU8Cmd::OR(input) => alu(6, input),
+                    AritLogiU8Cmd::CP(input) => alu(7, input),
+                    AritLogiU8Cmd::INC(input) => 0x04 | double_input(input) << 3,
+                    AritLogiU8Cmd::DEC(input) => 0x05 | double_input(input) << 3,
+                    AritLogiU8Cmd::DAA => 0x27,
+                    AritLogiU8Cmd::CPL => 0x2F,
+                };
+                (false, opcode)
+            }
+
+            Instruction::ArithmeticLogical16Bit(command) => {
+                let opcode = match command {
+                    AritLogiU16Cmd::ADDHL(InputU16(rr)) => 0x09 | register_u16(rr) << 4,
+                    AritLogiU16Cmd::INC(InputU16(rr)) => 0x03 | register_u16(rr) << 4,
+                    AritLogiU16Cmd::DEC(InputU16(rr)) => 0x0B | register_u16(rr) << 4,
+                    AritLogiU16Cmd::ADDSP => 0xE8,
+                    AritLogiU16Cmd::LDHLSP => 0xF8,
+                };
+                (false, opcode)
+            }
+
+            Instruction::RotateShift(command) => {
+                match command {
+                    RSCmd::RLCA => (false, 0x07),
+                    RSCmd::RLA => (false, 0x17),
+                    RSCmd::RRCA => (false, 0x0F),
+                    RSCmd::RRA => (false, 0x1F),
+                    RSCmd::RLC(input) => (true, double_input(input)),
+                    RSCmd::RRC(input) => (true, 0x08 | double_input(input)),
+                    RSCmd::RL(input) => (true, 0x10 | double_input(input)),
+                    RSCmd::RR(input) => (true, 0x18 | double_input(input)),
+                    RSCmd::SLA(input) => (true, 0x20 | double_input(input)),
+                    RSCmd::SRA(input) => (true, 0x28 | double_input(input)),
+                    RSCmd::SWAP(input) => (true, 0x30 | double_input(input)),
+                    RSCmd::SRL(input) => (true, 0x38 | double_input(input)),
+                }
+            }
+
+            Instruction::SingleBit(command) => {
+                let opcode = match command {
+                    BitCmd::BIT(BitInput(bit, input)) => 0x40 | (bit & 0b111) << 3 | double_input(input),
+                    BitCmd::RES(BitInput(bit, input)) => 0x80 | (bit & 0b111) << 3 | double_input(input),
+                    BitCmd::SET(BitInput(bit, input)) => 0xC0 | (bit & 0b111) << 3 | double_input(input),
+                };
+                (true, opcode)
+            }
+
+            Instruction::Control(command) => {
+                let opcode = match command {
+                    CtrCmd::CCF => 0x3F,
+                    CtrCmd::SCF => 0x37,
+                    CtrCmd::NOP => 0x00,
+                    CtrCmd::HALT => 0x76,
+                    CtrCmd::STOP => 0x10,
+                    CtrCmd::DI => 0xF3,
+                    CtrCmd::EI => 0xFB,
+                    CtrCmd::ILLEGAL(byte) => *byte,
+                };
+                (false, opcode)
+            }
+
+            Instruction::Jump(command) => {
+                let opcode = match command {
+                    JmpCmd::JP(JPInput::Direct) => 0xC3,
+                    JmpCmd::JP(JPInput::HL) => 0xE9,
+                    JmpCmd::JP(JPInput::Conditional(cond)) => 0xC2 | condition(*cond) << 3,
+                    JmpCmd::JR(JmpCmdInput::Direct) => 0x18,
+                    JmpCmd::JR(JmpCmdInput::Conditional(cond)) => 0x20 | condition(*cond) << 3,
+                    JmpCmd::CALL(JmpCmdInput::Direct) => 0xCD,
+                    JmpCmd::CALL(JmpCmdInput::Conditional(cond)) => 0xC4 | condition(*cond) << 3,
+                    JmpCmd::RET(JmpCmdInput::Direct) => 0xC9,
+                    JmpCmd::RET(JmpCmdInput::Conditional(cond)) => 0xC0 | condition(*cond) << 3,
+                    JmpCmd::RETI => 0xD9,
+                    JmpCmd::RST(vector) => 0xC7 | vector.address() as u8,
+                };
+                (false, opcode)
+            }
+        }
+    }
+}
+
+fn register_u8(register: &RegisterU8) -> u8 {
+    match register {
+        RegisterU8::B => 0,
+        RegisterU8::C => 1,
+        RegisterU8::D => 2,
+        RegisterU8::E => 3,
+        RegisterU8::H => 4,
+        RegisterU8::L => 5,
+        RegisterU8::A => 7,
+    }
+}
+
+fn double_input(input: &DoubleInputU8) -> u8 {
+    match input {
+        DoubleInputU8::Register(r) => register_u8(r),
+        DoubleInputU8::Address => 6,
+    }
+}
+
+/// SP and AF share a number, which one it means depends on the opcode
+fn register_u16(register: &RegisterU16) -> u8 {
+    match register {
+        RegisterU16::BC => 0,
+        RegisterU16::DE => 1,
+        RegisterU16::HL => 2,
+        RegisterU16::SP | RegisterU16::AF => 3,
+    }
+}
+
+fn condition(cond: JmpCmdCondition) -> u8 {
+    match cond {
+        JmpCmdCondition::NZ => 0,
+        JmpCmdCondition::Z => 1,
+        JmpCmdCondition::NC => 2,
+        JmpCmdCondition::C => 3,
+    }
+}
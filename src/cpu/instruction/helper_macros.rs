@@ -57,7 +57,8 @@ macro_rules! make_macro_with_no_input {
 // Instantiating the implementation macros' definitions
 make_macro_with_no_input!(arithmetic_u8_impl, Instruction::ArithmeticLogical8Bit);
 make_macro_with_no_input!(arithmetic_u16_impl, Instruction::ArithmeticLogical16Bit);
-make_macro!(jump_impl, Instruction::Jump);
+make_macro_with_no_input!(control_impl, Instruction::Control);
+make_macro_with_no_input!(jump_impl, Instruction::Jump);
 make_macro!(load_u8_impl, Instruction::Load8Bit);
 make_macro!(load_u16_impl, Instruction::Load16Bit);
 make_macro_with_no_input!(rotate_shift_impl, Instruction::RotateShift);
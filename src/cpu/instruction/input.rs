@@ -65,8 +65,8 @@ pub enum JPInput {
 pub enum JmpCmdInput {
     /// Has multiple meanings depending on the command.
     /// - [`JmpCmd::JR`], jump to PC + the value in the next byte of memory (relative jump)
-    /// - [`JmpCmd::CALL`], todo!()
-    /// - [`JmpCmd::RET`], todo!()
+    /// - [`JmpCmd::CALL`], push the address of the next instruction and jump to the address in the next two bytes of memory
+    /// - [`JmpCmd::RET`], pop the return address off of the stack and jump to it
     ///
     /// [`JmpCmd::JR`]: super::JmpCmd::JR
     /// [`JmpCmd::CALL`]: super::JmpCmd::CALL
@@ -78,7 +78,50 @@ pub enum JmpCmdInput {
     Conditional(JmpCmdCondition)
 }
 
+/// For [`JmpCmd::RST`] which calls one of the eight fixed restart vectors in page zero.
+///
+/// The variants are named after the address they call (i.e. `H38` is `RST 38H` which calls 0x0038)
+/// and their numerical values are that address, so `vector as u16` is the address to jump to.
+///
+/// [`JmpCmd::RST`]: super::JmpCmd::RST
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub enum RstVector {
+    H00 = 0x00,
+    H08 = 0x08,
+    H10 = 0x10,
+    H18 = 0x18,
+    H20 = 0x20,
+    H28 = 0x28,
+    H30 = 0x30,
+    H38 = 0x38,
+}
+
+impl RstVector {
+    /// The RST opcodes are 0b11xx_x111 where xxx encodes the vector, so masking the opcode
+    /// gives the vector's address directly
+    pub fn from_opcode(opcode: u8) -> Self {
+        match opcode & 0b0011_1000 {
+            0x00 => RstVector::H00,
+            0x08 => RstVector::H08,
+            0x10 => RstVector::H10,
+            0x18 => RstVector::H18,
+            0x20 => RstVector::H20,
+            0x28 => RstVector::H28,
+            0x30 => RstVector::H30,
+            0x38 => RstVector::H38,
+            _ => unreachable!("Match statement enumerates all possible values of the masked 3-bit field")
+        }
+    }
+
+    /// The address in page zero that this vector calls
+    pub fn address(self) -> u16 {
+        self as u16
+    }
+}
+
 /// For `Conditional` variants of inputs for Jump commands
+#[derive(Copy, Clone)]
 #[cfg_attr(test, derive(Debug))]
 pub enum JmpCmdCondition {
     /// do Direct operation if zero-flag is reset
@@ -174,7 +217,9 @@ pub enum LDInputU16 {
     /// Load into (*rr) from (*nn)
     RRNN(RegisterU16),
     /// Load into direct SP from direct HL; (note the lack of deref/parenthesis)
-    SPHL
+    SPHL,
+    /// Load into (*nn) (deref of 2-byte immediate value) from SP, LS-byte first
+    IISP
 }
 
 /// Type alias for a byte -- signals which bit the BitCmd should operate on
@@ -187,11 +232,8 @@ pub struct BitInput(pub U3, pub DoubleInputU8);
 
 impl BitInput {
     pub fn from_opcode(opcode: u8) -> Self {
-        eprintln!("FROM OPCODE RECEIVING: {opcode:#b}");
         let reg_code = opcode & 0b0000_0111;
         let bit_index = (opcode & 0b0011_1000) >> 3;
-        dbg!(bit_index);
-        eprintln!("{bit_index:#b}");
         // let cmd_code = (opcode & 0b1100_0000) >> 6; /* I'll let macros do this since for better or worse that is how i have done it thus far */
         
         let reg_input = match reg_code {
@@ -132,4 +132,249 @@ fn instruction_from_byte_prefixed_single_bit() {
             "left containing built_instruction with {opcode:#X} failed against right containing correct instruction"
         );
     }
-}
\ No newline at end of file
+}
+
+#[test]
+/// tests the M-cycle costs of the prefixed instructions, which depend on whether they touch (HL)
+fn instruction_machine_cycles_prefixed() {
+    for opcode in 0x00..=0xFF_u8 {
+        let instruction = Instruction::from_byte(opcode, true)
+            .expect("every prefixed opcode should build");
+
+        let touches_hl = opcode & 0b0000_0111 == 6;
+        let is_bit = opcode & 0b1100_0000 == 0b0100_0000;
+        let expected = match (touches_hl, is_bit) {
+            (false, _) => 2,
+            (true, true) => 3,
+            (true, false) => 4,
+        };
+
+        assert_eq!(expected, instruction.machine_cycles(false), "CB {opcode:#04X} has the wrong cycle count");
+    }
+}
+
+/// The table mnemonic of an instruction, without its operands
+fn mnemonic(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Load8Bit(LoadU8Cmd::LD(LDInputU8::ReadIoN | LDInputU8::WriteIoN)) => "LDH",
+        Instruction::Load8Bit(_) => "LD",
+        Instruction::Load16Bit(command) => {
+            match command {
+                LoadU16Cmd::LD(_) => "LD",
+                LoadU16Cmd::PUSH(_) => "PUSH",
+                LoadU16Cmd::POP(_) => "POP",
+            }
+        }
+        Instruction::ArithmeticLogical8Bit(command) => {
+            match command {
+                AritLogiU8Cmd::ADD(_) => "ADD",
+                AritLogiU8Cmd::ADC(_) => "ADC",
+                AritLogiU8Cmd::SUB(_) => "SUB",
+                AritLogiU8Cmd::SBC(_) => "SBC",
+                AritLogiU8Cmd::AND(_) => "AND",
+                AritLogiU8Cmd::XOR(_) => "XOR",
+                AritLogiU8Cmd::OR(_) => "OR",
+                AritLogiU8Cmd::CP(_) => "CP",
+                AritLogiU8Cmd::INC(_) => "INC",
+                AritLogiU8Cmd::DEC(_) => "DEC",
+                AritLogiU8Cmd::DAA => "DAA",
+                AritLogiU8Cmd::CPL => "CPL",
+            }
+        }
+        Instruction::ArithmeticLogical16Bit(command) => {
+            match command {
+                AritLogiU16Cmd::ADDHL(_) | AritLogiU16Cmd::ADDSP => "ADD",
+                AritLogiU16Cmd::INC(_) => "INC",
+                AritLogiU16Cmd::DEC(_) => "DEC",
+                AritLogiU16Cmd::LDHLSP => "LD",
+            }
+        }
+        Instruction::RotateShift(command) => {
+            match command {
+                RSCmd::RLCA => "RLCA",
+                RSCmd::RLA => "RLA",
+                RSCmd::RRCA => "RRCA",
+                RSCmd::RRA => "RRA",
+                RSCmd::RLC(_) => "RLC",
+                RSCmd::RL(_) => "RL",
+                RSCmd::RRC(_) => "RRC",
+                RSCmd::RR(_) => "RR",
+                RSCmd::SLA(_) => "SLA",
+                RSCmd::SWAP(_) => "SWAP",
+                RSCmd::SRA(_) => "SRA",
+                RSCmd::SRL(_) => "SRL",
+            }
+        }
+        Instruction::SingleBit(command) => {
+            match command {
+                BitCmd::BIT(_) => "BIT",
+                BitCmd::RES(_) => "RES",
+                BitCmd::SET(_) => "SET",
+            }
+        }
+        Instruction::Control(command) => {
+            match command {
+                CtrCmd::CCF => "CCF",
+                CtrCmd::SCF => "SCF",
+                CtrCmd::NOP => "NOP",
+                CtrCmd::HALT => "HALT",
+                CtrCmd::STOP => "STOP",
+                CtrCmd::DI => "DI",
+                CtrCmd::EI => "EI",
+                CtrCmd::ILLEGAL(_) => "ILLEGAL",
+            }
+        }
+        Instruction::Jump(command) => {
+            match command {
+                JmpCmd::JP(_) => "JP",
+                JmpCmd::JR(_) => "JR",
+                JmpCmd::CALL(_) => "CALL",
+                JmpCmd::RET(_) => "RET",
+                JmpCmd::RETI => "RETI",
+                JmpCmd::RST(_) => "RST",
+            }
+        }
+    }
+}
+
+#[test]
+/// checks all 256 unprefixed and 256 prefixed opcodes against the table in opcodes.txt: that each
+/// decodes to the right command, with the right length and cycle count
+fn instruction_every_opcode_matches_the_table() {
+    let table = include_str!("opcodes.txt");
+    let mut checked = 0;
+
+    for line in table.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let columns: Vec<&str> = line.split(" | ").collect();
+        let [opcode, table_mnemonic, length, cycles] = columns[..] else {
+            panic!("malformed table line {line:?}");
+        };
+        let (prefixed, opcode) = match opcode.strip_prefix("CB ") {
+            Some(opcode) => (true, opcode),
+            None => (false, opcode),
+        };
+        let opcode = u8::from_str_radix(opcode, 16).unwrap();
+        checked += 1;
+
+        if table_mnemonic == "PREFIX CB" {
+            assert!(Instruction::from_byte(opcode, prefixed).is_err(), "the CB prefix on its own isn't an instruction");
+            continue;
+        }
+
+        let instruction = Instruction::from_byte(opcode, prefixed)
+            .unwrap_or_else(|err| panic!("{line:?} didn't decode: {err}"));
+
+        assert_eq!(table_mnemonic.split(' ').next().unwrap(), mnemonic(&instruction), "{line:?} decoded to {instruction:?}");
+        assert_eq!(length.parse::<u8>().unwrap(), instruction.length(), "{line:?} has the wrong length");
+
+        let (taken, not_taken) = cycles.split_once('/').unwrap_or((cycles, cycles));
+        assert_eq!(taken.parse::<u8>().unwrap(), instruction.machine_cycles(true) * 4, "{line:?} has the wrong cycle count");
+        assert_eq!(not_taken.parse::<u8>().unwrap(), instruction.machine_cycles(false) * 4, "{line:?} has the wrong cycle count when not taken");
+    }
+
+    assert_eq!(512, checked, "the table should cover every opcode of both tables");
+}
+
+#[test]
+fn instruction_illegal_opcodes_keep_their_byte() {
+    for opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD] {
+        let instruction = Instruction::from_byte(opcode, false).unwrap();
+        assert!(matches!(instruction, Instruction::Control(CtrCmd::ILLEGAL(byte)) if byte == opcode), "{opcode:#04X}");
+    }
+}
+
+/// The table's text for an instruction, with its operand placeholders filled in the way the
+/// disassembler writes them for the operand bytes 0x34 0x12
+fn table_text(opcode: u8, table_mnemonic: &str) -> String {
+    if table_mnemonic == "ILLEGAL" {
+        return format!("DB ${opcode:02X}");
+    }
+    if let Some(vector) = table_mnemonic.strip_prefix("RST ") {
+        return format!("RST ${}", vector.trim_end_matches('H'));
+    }
+    table_mnemonic
+        .replace("STOP 0", "STOP")
+        .replace("d16", "$1234")
+        .replace("a16", "$1234")
+        .replace("(a8)", "($FF34)")
+        .replace("d8", "$34")
+        .replace("(C)", "($FF00+C)")
+        // 0x34 is +52, and JR counts from the instruction after it
+        .replace("SP+r8", "SP+52")
+        .replace("SP,r8", "SP,52")
+        .replace("r8", "$+54")
+}
+
+#[test]
+fn instruction_disassembly_matches_the_table() {
+    let table = include_str!("opcodes.txt");
+
+    for line in table.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let columns: Vec<&str> = line.split(" | ").collect();
+        let [opcode, table_mnemonic, length, _] = columns[..] else {
+            panic!("malformed table line {line:?}");
+        };
+        let bytes = match opcode.strip_prefix("CB ") {
+            Some(opcode) => vec![0xCB, u8::from_str_radix(opcode, 16).unwrap()],
+            None => vec![u8::from_str_radix(opcode, 16).unwrap(), 0x34, 0x12],
+        };
+        if table_mnemonic == "PREFIX CB" {
+            continue;
+        }
+
+        let disassembly = disassemble(&bytes);
+        assert_eq!(table_text(bytes[0], table_mnemonic), disassembly.text, "{line:?}");
+        assert_eq!(length.parse::<u8>().unwrap(), disassembly.length, "{line:?}");
+    }
+}
+
+#[test]
+fn disassembly_resolves_immediates() {
+    for (bytes, text, length) in [
+        (&[0x20, 0x03][..], "JR NZ,$+5", 2),
+        (&[0x18, 0xFE], "JR $+0", 2),
+        (&[0x38, 0xF0], "JR C,$-14", 2),
+        (&[0xE8, 0xFE], "ADD SP,-2", 2),
+        (&[0xF8, 0x80], "LD HL,SP-128", 2),
+        (&[0xF0, 0x44], "LDH A,($FF44)", 2),
+        (&[0xC3, 0x50, 0x01], "JP $0150", 3),
+        (&[0x3E, 0x0A, 0x00], "LD A,$0A", 2),
+        (&[0xCB, 0x5E], "BIT 3,(HL)", 2),
+    ] {
+        assert_eq!(Disassembly { text: text.to_string(), length }, disassemble(bytes), "{bytes:02X?}");
+    }
+}
+
+#[test]
+fn disassembly_of_cut_off_instructions_is_a_data_byte() {
+    assert_eq!("DB $C3", disassemble(&[0xC3, 0x50]).text);
+    assert_eq!("DB $CB", disassemble(&[0xCB]).text);
+    assert_eq!(1, disassemble(&[0xFA]).length);
+}
+
+#[test]
+fn instruction_encode_round_trips_every_opcode() {
+    for (prefixed, opcode) in (0..=0xFF).map(|opcode| (false, opcode)).chain((0..=0xFF).map(|opcode| (true, opcode))) {
+        let Ok(instruction) = Instruction::from_byte(opcode, prefixed) else {
+            assert!(!prefixed && opcode == 0xCB, "only the CB prefix shouldn't decode");
+            continue;
+        };
+        assert_eq!((prefixed, opcode), instruction.opcode(), "{instruction:?}");
+
+        // whatever the immediate, the bytes decode back to the same instruction with it
+        for immediate in [0x0000, 0x1234, 0x80FF, 0xFFFF] {
+            let bytes = instruction.encode(immediate);
+            assert_eq!(instruction.length() as usize, bytes.len(), "{instruction:?}");
+
+            let decoded = match bytes[..] {
+                [0xCB, opcode, ..] if prefixed => Instruction::from_byte(opcode, true),
+                [opcode, ..] => Instruction::from_byte(opcode, false),
+                [] => unreachable!("every instruction is at least a byte"),
+            };
+            assert_eq!(bytes, decoded.unwrap().encode(immediate), "{instruction:?}");
+
+            let operands = &bytes[if prefixed { 2 } else { 1 }..];
+            assert_eq!(immediate.to_le_bytes()[..operands.len()], operands[..], "{instruction:?}");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Machine-cycle (M-cycle) costs of every [`Instruction`], i.e. the `C` column of the
+//! comment guide in [`Instruction::from_byte_not_prefixed`] divided by 4 as that column
+//! is in T-cycles (clock ticks) and every M-cycle is 4 T-cycles.
+//!
+//! [`Instruction::from_byte_not_prefixed`]: super::Instruction::from_byte_not_prefixed
+
+use super::*;
+
+impl Instruction {
+    /// The jump condition of this instruction if it is a conditional JP/JR/CALL/RET.
+    ///
+    /// [`CPU::step`] evaluates this before executing to find out which of the two
+    /// costs of a conditional instruction applies.
+    ///
+    /// [`CPU::step`]: crate::cpu::CPU::step
+    pub fn condition(&self) -> Option<JmpCmdCondition> {
+        match self {
+            Instruction::Jump(JmpCmd::JP(JPInput::Conditional(cond)))
+            | Instruction::Jump(JmpCmd::JR(JmpCmdInput::Conditional(cond)))
+            | Instruction::Jump(JmpCmd::CALL(JmpCmdInput::Conditional(cond)))
+            | Instruction::Jump(JmpCmd::RET(JmpCmdInput::Conditional(cond))) => Some(*cond),
+            _ => None
+        }
+    }
+
+    /// How many M-cycles executing this instruction takes.
+    ///
+    /// `branch_taken` only matters for conditional jumps (see [`Instruction::condition`]),
+    /// which take longer when their condition holds. Unconditional instructions ignore it.
+    pub fn machine_cycles(&self, branch_taken: bool) -> u8 {
+        match self {
+            Instruction::Load8Bit(command) => {
+                match command {
+                    LoadU8Cmd::LD(input) => {
+                        match input {
+                            LDInputU8::RR(_, _) => 1,
+                            LDInputU8::RI(_) => 2,
+                            LDInputU8::RHL(_) | LDInputU8::HLR(_) => 2,
+                            LDInputU8::HLI => 3,
+                            LDInputU8::ABC | LDInputU8::ADE | LDInputU8::BCA | LDInputU8::DEA => 2,
+                            LDInputU8::AII | LDInputU8::IIA => 4,
+                            LDInputU8::ReadIoN | LDInputU8::WriteIoN => 3,
+                            LDInputU8::ReadIoC | LDInputU8::WriteIoC => 2,
+                        }
+                    }
+                    LoadU8Cmd::LDI(_) | LoadU8Cmd::LDD(_) => 2,
+                }
+            }
+
+            Instruction::Load16Bit(command) => {
+                match command {
+                    LoadU16Cmd::LD(LDInputU16::RRNN(_)) => 3,
+                    LoadU16Cmd::LD(LDInputU16::SPHL) => 2,
+                    // the 2 bytes of SP are written one M-cycle each
+                    LoadU16Cmd::LD(LDInputU16::IISP) => 5,
+                    LoadU16Cmd::PUSH(_) => 4,
+                    LoadU16Cmd::POP(_) => 3,
+                }
+            }
+
+            Instruction::ArithmeticLogical8Bit(command) => {
+                match command {
+                    AritLogiU8Cmd::ADD(input) | AritLogiU8Cmd::ADC(input)
+                    | AritLogiU8Cmd::SUB(input) | AritLogiU8Cmd::SBC(input)
+                    | AritLogiU8Cmd::AND(input) | AritLogiU8Cmd::XOR(input)
+                    | AritLogiU8Cmd::OR(input) | AritLogiU8Cmd::CP(input) => {
+                        match input {
+                            CompoundInputU8::Register(_) => 1,
+                            CompoundInputU8::Immediate | CompoundInputU8::Address => 2,
+                        }
+                    }
+                    AritLogiU8Cmd::INC(input) | AritLogiU8Cmd::DEC(input) => {
+                        match input {
+                            DoubleInputU8::Register(_) => 1,
+                            // read, modify, write back
+                            DoubleInputU8::Address => 3,
+                        }
+                    }
+                    AritLogiU8Cmd::DAA | AritLogiU8Cmd::CPL => 1,
+                }
+            }
+
+            Instruction::ArithmeticLogical16Bit(command) => {
+                match command {
+                    AritLogiU16Cmd::ADDHL(_) | AritLogiU16Cmd::INC(_) | AritLogiU16Cmd::DEC(_) => 2,
+                    AritLogiU16Cmd::ADDSP => 4,
+                    AritLogiU16Cmd::LDHLSP => 3,
+                }
+            }
+
+            Instruction::RotateShift(command) => {
+                match command {
+                    RSCmd::RLCA | RSCmd::RLA | RSCmd::RRCA | RSCmd::RRA => 1,
+                    // prefixed, so the CB byte costs an extra M-cycle
+                    RSCmd::RLC(input) | RSCmd::RL(input) | RSCmd::RRC(input) | RSCmd::RR(input)
+                    | RSCmd::SLA(input) | RSCmd::SWAP(input) | RSCmd::SRA(input) | RSCmd::SRL(input) => {
+                        match input {
+                            DoubleInputU8::Register(_) => 2,
+                            DoubleInputU8::Address => 4,
+                        }
+                    }
+                }
+            }
+
+            // all prefixed
+            Instruction::SingleBit(command) => {
+                match command {
+                    BitCmd::BIT(BitInput(_, DoubleInputU8::Register(_))) => 2,
+                    // BIT only reads (HL), it doesn't write it back
+                    BitCmd::BIT(BitInput(_, DoubleInputU8::Address)) => 3,
+                    BitCmd::RES(BitInput(_, input)) | BitCmd::SET(BitInput(_, input)) => {
+                        match input {
+                            DoubleInputU8::Register(_) => 2,
+                            DoubleInputU8::Address => 4,
+                        }
+                    }
+                }
+            }
+
+            Instruction::Control(command) => {
+                match command {
+                    CtrCmd::CCF | CtrCmd::SCF | CtrCmd::NOP | CtrCmd::HALT
+                    | CtrCmd::STOP | CtrCmd::DI | CtrCmd::EI | CtrCmd::ILLEGAL(_) => 1,
+                }
+            }
+
+            Instruction::Jump(command) => {
+                match command {
+                    JmpCmd::JP(JPInput::Direct) => 4,
+                    JmpCmd::JP(JPInput::HL) => 1,
+                    JmpCmd::JP(JPInput::Conditional(_)) => if branch_taken { 4 } else { 3 },
+                    JmpCmd::JR(JmpCmdInput::Direct) => 3,
+                    JmpCmd::JR(JmpCmdInput::Conditional(_)) => if branch_taken { 3 } else { 2 },
+                    JmpCmd::CALL(JmpCmdInput::Direct) => 6,
+                    JmpCmd::CALL(JmpCmdInput::Conditional(_)) => if branch_taken { 6 } else { 3 },
+                    JmpCmd::RET(JmpCmdInput::Direct) => 4,
+                    // one M-cycle more than RET when taken as the condition check is its own M-cycle
+                    JmpCmd::RET(JmpCmdInput::Conditional(_)) => if branch_taken { 5 } else { 2 },
+                    JmpCmd::RETI => 4,
+                    JmpCmd::RST(_) => 4,
+                }
+            }
+        }
+    }
+}
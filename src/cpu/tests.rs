@@ -0,0 +1,584 @@
+use super::*;
+use crate::cartridge::{ Cartridge, with_valid_header };
+use crate::interrupt::Interrupt;
+
+/// Builds a zeroed CPU with `program` loaded at `pc` and the stack pointer at the top of WRAM.
+/// Programs at a ROM address are baked into a ROM image as ROM can't be written through the bus.
+fn cpu_with_program(pc: u16, program: &[u8]) -> CPU {
+    let mut rom = vec![0; 0x8000];
+    if (pc as usize) < rom.len() {
+        rom[pc as usize..pc as usize + program.len()].copy_from_slice(program);
+    }
+
+    let mut cpu = CPU {
+        registers: Registers {
+            a: 0, b: 0, c: 0, d: 0, e: 0, h: 0, l: 0,
+            f: FlagRegister { zero: false, subtract: false, half_carry: false, carry: false },
+        },
+        pc,
+        sp: 0xDFFF,
+        bus: MemoryBus::with_cartridge(
+            Cartridge::from_bytes(&with_valid_header(rom, 0x00, 0x00)).expect("test ROM should load")
+        ),
+        ime: false,
+        ime_scheduled: false,
+        halted: false,
+        halt_bug: false,
+        stopped: false,
+        locked: false,
+        cycles: 0,
+        tracer: None,
+    };
+
+    if pc >= 0x8000 {
+        for (offset, byte) in program.iter().enumerate() {
+            cpu.bus.write_byte(pc.wrapping_add(offset as u16), *byte);
+        }
+    }
+
+    cpu
+}
+
+/// [`cpu_with_program`] with the program written in assembly
+fn cpu_with_assembly(pc: u16, source: &str) -> CPU {
+    let program = assemble(source, pc).unwrap_or_else(|err| panic!("the test program should assemble: {err}"));
+    cpu_with_program(pc, &program)
+}
+
+#[test]
+fn push_and_pop_round_trip() {
+    let mut cpu = cpu_with_program(0x0100, &[]);
+
+    cpu.push(0xBEEF);
+    assert_eq!(0xDFFD, cpu.sp);
+    assert_eq!(0xBE, cpu.bus.read_byte(0xDFFE));
+    assert_eq!(0xEF, cpu.bus.read_byte(0xDFFD));
+
+    assert_eq!(0xBEEF, cpu.pop());
+    assert_eq!(0xDFFF, cpu.sp);
+}
+
+#[test]
+fn call_pushes_return_address_and_jumps() {
+    // CALL 0x1234
+    let mut cpu = cpu_with_program(0x0150, &[0xCD, 0x34, 0x12]);
+
+    cpu.step().expect("CALL nn should decode");
+
+    assert_eq!(0x1234, cpu.pc);
+    assert_eq!(0xDFFD, cpu.sp);
+    assert_eq!(0x0153, cpu.pop(), "CALL should push the address of the instruction after it");
+}
+
+#[test]
+fn call_then_ret_returns_to_caller() {
+    // CALL 0xC000 ... 0xC000: RET
+    let mut cpu = cpu_with_program(0x0150, &[0xCD, 0x00, 0xC0]);
+    cpu.bus.write_byte(0xC000, 0xC9);
+
+    cpu.step().expect("CALL nn should decode");
+    cpu.step().expect("RET should decode");
+
+    assert_eq!(0x0153, cpu.pc);
+    assert_eq!(0xDFFF, cpu.sp);
+}
+
+#[test]
+fn conditional_call() {
+    // CALL NZ,0x4000 with Z set -> falls through
+    let mut cpu = cpu_with_program(0x0150, &[0xC4, 0x00, 0x40]);
+    cpu.registers.f.zero = true;
+    cpu.step().expect("CALL NZ,nn should decode");
+    assert_eq!(0x0153, cpu.pc);
+    assert_eq!(0xDFFF, cpu.sp, "an untaken CALL must not touch the stack");
+
+    // CALL Z,0x4000 with Z set -> taken
+    let mut cpu = cpu_with_program(0x0150, &[0xCC, 0x00, 0x40]);
+    cpu.registers.f.zero = true;
+    cpu.step().expect("CALL Z,nn should decode");
+    assert_eq!(0x4000, cpu.pc);
+    assert_eq!(0x0153, cpu.pop());
+
+    // CALL NC,0x4000 with C set -> falls through
+    let mut cpu = cpu_with_program(0x0150, &[0xD4, 0x00, 0x40]);
+    cpu.registers.f.carry = true;
+    cpu.step().expect("CALL NC,nn should decode");
+    assert_eq!(0x0153, cpu.pc);
+
+    // CALL C,0x4000 with C set -> taken
+    let mut cpu = cpu_with_program(0x0150, &[0xDC, 0x00, 0x40]);
+    cpu.registers.f.carry = true;
+    cpu.step().expect("CALL C,nn should decode");
+    assert_eq!(0x4000, cpu.pc);
+}
+
+#[test]
+fn conditional_ret() {
+    // RET Z with Z reset -> falls through
+    let mut cpu = cpu_with_program(0x0200, &[0xC8]);
+    cpu.push(0x0153);
+    cpu.step().expect("RET Z should decode");
+    assert_eq!(0x0201, cpu.pc);
+    assert_eq!(0xDFFD, cpu.sp, "an untaken RET must not touch the stack");
+
+    // RET NZ with Z reset -> taken
+    let mut cpu = cpu_with_program(0x0200, &[0xC0]);
+    cpu.push(0x0153);
+    cpu.step().expect("RET NZ should decode");
+    assert_eq!(0x0153, cpu.pc);
+    assert_eq!(0xDFFF, cpu.sp);
+
+    // RET C with C reset -> falls through
+    let mut cpu = cpu_with_program(0x0200, &[0xD8]);
+    cpu.push(0x0153);
+    cpu.step().expect("RET C should decode");
+    assert_eq!(0x0201, cpu.pc);
+
+    // RET NC with C reset -> taken
+    let mut cpu = cpu_with_program(0x0200, &[0xD0]);
+    cpu.push(0x0153);
+    cpu.step().expect("RET NC should decode");
+    assert_eq!(0x0153, cpu.pc);
+}
+
+#[test]
+fn reti_returns_and_enables_interrupts() {
+    let mut cpu = cpu_with_program(0x0040, &[0xD9]);
+    cpu.push(0x0153);
+
+    cpu.step().expect("RETI should decode");
+
+    assert_eq!(0x0153, cpu.pc);
+    assert_eq!(0xDFFF, cpu.sp);
+    assert!(cpu.ime, "RETI sets IME without EI's delay");
+}
+
+#[test]
+fn rst_calls_every_vector() {
+    let vectors = [
+        (0xC7, 0x00), (0xCF, 0x08), (0xD7, 0x10), (0xDF, 0x18),
+        (0xE7, 0x20), (0xEF, 0x28), (0xF7, 0x30), (0xFF, 0x38),
+    ];
+
+    for (opcode, address) in vectors {
+        let mut cpu = cpu_with_program(0x0150, &[opcode]);
+
+        cpu.step().expect("RST should decode");
+
+        assert_eq!(address, cpu.pc, "RST opcode {opcode:#X} jumped to the wrong vector");
+        assert_eq!(0x0151, cpu.pop(), "RST should push the address of the instruction after it");
+    }
+}
+
+#[test]
+fn interrupt_registers_are_memory_mapped() {
+    let mut cpu = cpu_with_program(0x0150, &[]);
+
+    cpu.bus.write_byte(0xFFFF, 0b0000_0101);
+    cpu.bus.write_byte(0xFF0F, 0b0000_0100);
+
+    assert_eq!(0b0000_0101, cpu.bus.read_byte(0xFFFF));
+    assert_eq!(0b1110_0100, cpu.bus.read_byte(0xFF0F));
+    assert_eq!(Some(Interrupt::Timer), cpu.bus.interrupts.highest_pending());
+}
+
+#[test]
+fn dispatch_pushes_pc_and_jumps_to_vector() {
+    let mut cpu = cpu_with_program(0x0150, &[]);
+    cpu.ime = true;
+    cpu.bus.interrupts.write_enable(0xFF);
+    cpu.bus.interrupts.request(Interrupt::Serial);
+    cpu.bus.interrupts.request(Interrupt::Joypad);
+
+    cpu.step().unwrap();
+
+    assert_eq!(0x58, cpu.pc, "the serial interrupt outranks the joypad one");
+    assert!(!cpu.ime, "dispatch resets IME");
+    assert_eq!(0x0150, cpu.pop());
+    assert_eq!(Some(Interrupt::Joypad), cpu.bus.interrupts.highest_pending(), "only the dispatched IF bit is acknowledged");
+}
+
+#[test]
+fn no_dispatch_without_ime() {
+    // NOP is not decoded yet so use LD B,B as the filler
+    let mut cpu = cpu_with_program(0x0150, &[0x40]);
+    cpu.bus.interrupts.write_enable(0xFF);
+    cpu.bus.interrupts.request(Interrupt::VBlank);
+
+    cpu.step().unwrap();
+
+    assert_eq!(0x0151, cpu.pc);
+    assert!(cpu.bus.interrupts.has_pending());
+}
+
+#[test]
+fn ei_takes_effect_after_the_next_instruction() {
+    // EI; LD B,B; LD B,B
+    let mut cpu = cpu_with_program(0x0150, &[0xFB, 0x40, 0x40]);
+    cpu.bus.interrupts.write_enable(0xFF);
+    cpu.bus.interrupts.request(Interrupt::Timer);
+
+    cpu.step().unwrap();
+    assert!(!cpu.ime);
+    assert_eq!(0x0151, cpu.pc);
+
+    cpu.step().unwrap();
+    assert!(cpu.ime, "IME is set once the instruction after EI is done");
+    assert_eq!(0x0152, cpu.pc, "the instruction after EI runs before any dispatch");
+
+    cpu.step().unwrap();
+    assert_eq!(0x50, cpu.pc);
+    assert_eq!(0x0152, cpu.pop());
+}
+
+#[test]
+fn di_cancels_a_pending_ei() {
+    // EI; DI; LD B,B
+    let mut cpu = cpu_with_program(0x0150, &[0xFB, 0xF3, 0x40]);
+    cpu.bus.interrupts.write_enable(0xFF);
+    cpu.bus.interrupts.request(Interrupt::Timer);
+
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+
+    assert!(!cpu.ime);
+    assert_eq!(0x0153, cpu.pc);
+}
+
+#[test]
+fn halt_waits_for_an_interrupt_and_dispatches_it() {
+    // HALT; LD B,B
+    let mut cpu = cpu_with_program(0x0150, &[0x76, 0x40]);
+    cpu.ime = true;
+    cpu.bus.interrupts.write_enable(Interrupt::VBlank.mask());
+
+    cpu.step().unwrap();
+    assert!(cpu.halted);
+    assert_eq!(0x0151, cpu.pc);
+
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert!(cpu.halted, "HALT idles while nothing is pending");
+    assert_eq!(0x0151, cpu.pc);
+
+    cpu.bus.interrupts.request(Interrupt::VBlank);
+    cpu.step().unwrap();
+    assert!(!cpu.halted);
+    assert_eq!(0x40, cpu.pc);
+    assert_eq!(0x0151, cpu.pop(), "the return address is the instruction after HALT");
+}
+
+#[test]
+fn halt_wakes_without_dispatch_when_ime_is_reset() {
+    // HALT; LD B,B
+    let mut cpu = cpu_with_program(0x0150, &[0x76, 0x40]);
+    cpu.bus.interrupts.write_enable(Interrupt::Timer.mask());
+
+    cpu.step().unwrap();
+    assert!(cpu.halted);
+
+    cpu.bus.interrupts.request(Interrupt::Timer);
+    cpu.step().unwrap();
+
+    assert!(!cpu.halted);
+    assert_eq!(0x0152, cpu.pc, "execution continues after HALT instead of jumping to the vector");
+    assert!(cpu.bus.interrupts.has_pending(), "the IF bit stays set as nothing was dispatched");
+}
+
+#[test]
+fn halt_bug_reads_the_next_byte_twice() {
+    // HALT; INC B; LD C,B
+    let mut cpu = cpu_with_program(0x0150, &[0x76, 0x04, 0x48]);
+    cpu.bus.interrupts.write_enable(Interrupt::Timer.mask());
+    cpu.bus.interrupts.request(Interrupt::Timer);
+
+    cpu.step().unwrap();
+    assert!(!cpu.halted, "HALT with IME=0 and a pending interrupt doesn't halt");
+
+    cpu.step().unwrap();
+    assert_eq!(0x0151, cpu.pc, "PC failed to increment past INC B");
+    cpu.step().unwrap();
+    assert_eq!(0x0152, cpu.pc);
+
+    assert_eq!(2, cpu.registers.b, "INC B ran twice");
+}
+
+#[test]
+fn step_returns_t_cycles_and_counts_them() {
+    // LD B,B; LD B,n; LD (HL),n; PUSH BC; POP BC
+    let mut cpu = cpu_with_program(0x0150, &[0x40, 0x06, 0x12, 0x36, 0x34, 0xC5, 0xC1]);
+    cpu.registers.set_hl(0xC000);
+
+    assert_eq!(4, cpu.step().unwrap());
+    assert_eq!(8, cpu.step().unwrap());
+    assert_eq!(12, cpu.step().unwrap());
+    assert_eq!(16, cpu.step().unwrap());
+    assert_eq!(12, cpu.step().unwrap());
+
+    assert_eq!(52, cpu.cycles);
+}
+
+#[test]
+fn conditional_jumps_cost_more_when_taken() {
+    // (opcode, cycles when Z set, cycles when Z reset)
+    let cases = [
+        (0xCA, 16, 12), // JP Z,nn
+        (0xC2, 12, 16), // JP NZ,nn
+        (0xCC, 24, 12), // CALL Z,nn
+        (0xC4, 12, 24), // CALL NZ,nn
+        (0xC8, 20, 8),  // RET Z
+        (0xC0, 8, 20),  // RET NZ
+    ];
+
+    for (opcode, cycles_zero_set, cycles_zero_reset) in cases {
+        for (zero, expected) in [(true, cycles_zero_set), (false, cycles_zero_reset)] {
+            let mut cpu = cpu_with_program(0x0150, &[opcode, 0x00, 0x40]);
+            cpu.push(0x0200);
+            cpu.registers.f.zero = zero;
+
+            assert_eq!(expected, cpu.step().unwrap(), "opcode {opcode:#X} with Z={zero}");
+        }
+    }
+}
+
+#[test]
+fn unconditional_jump_cycles() {
+    // (program, cycles)
+    let cases: [(&[u8], TCycles); 5] = [
+        (&[0xC3, 0x00, 0x40], 16), // JP nn
+        (&[0xE9], 4),              // JP HL
+        (&[0xCD, 0x00, 0x40], 24), // CALL nn
+        (&[0xC9], 16),             // RET
+        (&[0xFF], 16),             // RST 38H
+    ];
+
+    for (program, expected) in cases {
+        let mut cpu = cpu_with_program(0x0150, program);
+        cpu.push(0x0200);
+
+        assert_eq!(expected, cpu.step().unwrap(), "program {program:X?}");
+    }
+}
+
+#[test]
+fn interrupt_dispatch_and_halt_cycles() {
+    // HALT
+    let mut cpu = cpu_with_program(0x0150, &[0x76]);
+    cpu.ime = true;
+    cpu.bus.interrupts.write_enable(Interrupt::Timer.mask());
+
+    assert_eq!(4, cpu.step().unwrap(), "HALT itself");
+    assert_eq!(4, cpu.step().unwrap(), "idling in HALT takes one M-cycle per step");
+
+    cpu.bus.interrupts.request(Interrupt::Timer);
+    assert_eq!(20, cpu.step().unwrap(), "dispatch takes 5 M-cycles");
+
+    assert_eq!(28, cpu.cycles);
+}
+
+#[test]
+fn jr_jumps_relative_to_the_next_instruction() {
+    // JR +3 ; ... 0x0155: JR -7 (back to 0x0150)
+    let mut cpu = cpu_with_program(0x0150, &[0x18, 0x03, 0x00, 0x00, 0x00, 0x18, 0xF9]);
+
+    cpu.step().expect("JR dd should decode");
+    assert_eq!(0x0155, cpu.pc);
+    cpu.step().expect("JR dd should decode");
+    assert_eq!(0x0150, cpu.pc);
+
+    // JR NZ,+3 with Z set falls through
+    let mut cpu = cpu_with_program(0x0150, &[0x20, 0x03]);
+    cpu.registers.f.zero = true;
+    cpu.step().expect("JR NZ,dd should decode");
+    assert_eq!(0x0152, cpu.pc);
+}
+
+#[test]
+fn ld_nn_sp_stores_sp_little_endian() {
+    // LD (0xC000),SP
+    let mut cpu = cpu_with_program(0x0150, &[0x08, 0x00, 0xC0]);
+    cpu.sp = 0xBEEF;
+
+    assert_eq!(20, cpu.step().expect("LD (nn),SP should decode"));
+    assert_eq!(0xEF, cpu.bus.read_byte(0xC000));
+    assert_eq!(0xBE, cpu.bus.read_byte(0xC001));
+    assert_eq!(0x0153, cpu.pc);
+}
+
+#[test]
+fn add_sp_takes_a_signed_offset() {
+    // ADD SP,-1 ; LD HL,SP+2
+    let mut cpu = cpu_with_program(0x0150, &[0xE8, 0xFF, 0xF8, 0x02]);
+    cpu.sp = 0xD005;
+
+    cpu.step().expect("ADD SP,dd should decode");
+    assert_eq!(0xD004, cpu.sp);
+    // the flags come from the unsigned low byte add 0x05 + 0xFF
+    assert!(cpu.registers.f.half_carry && cpu.registers.f.carry);
+    assert!(!cpu.registers.f.zero && !cpu.registers.f.subtract);
+
+    cpu.step().expect("LD HL,SP+dd should decode");
+    assert_eq!(0xD006, cpu.registers.get_hl());
+    assert_eq!(0xD004, cpu.sp);
+    assert!(!cpu.registers.f.half_carry && !cpu.registers.f.carry);
+}
+
+#[test]
+fn alu_takes_immediates_and_hl() {
+    // ADD A,0x12 ; XOR (HL) ; CP 0x00
+    let mut cpu = cpu_with_program(0x0150, &[0xC6, 0x12, 0xAE, 0xFE, 0x00]);
+    cpu.registers.a = 0x01;
+    cpu.registers.set_hl(0xC000);
+    cpu.bus.write_byte(0xC000, 0x13);
+
+    cpu.step().expect("ADD A,n should decode");
+    assert_eq!(0x13, cpu.registers.a);
+    assert_eq!(0x0152, cpu.pc);
+
+    cpu.step().expect("XOR (HL) should decode");
+    assert_eq!(0x00, cpu.registers.a);
+    assert!(cpu.registers.f.zero);
+    assert_eq!(0x0153, cpu.pc);
+
+    cpu.step().expect("CP n should decode");
+    assert_eq!(0x0155, cpu.pc);
+}
+
+#[test]
+fn ld_r_a_and_ld_hl_a() {
+    // LD B,A ; LD L,A ; LD (HL),A
+    let mut cpu = cpu_with_program(0x0150, &[0x47, 0x6F, 0x77]);
+    cpu.registers.a = 0x42;
+    cpu.registers.h = 0xC0;
+
+    for _ in 0..3 {
+        cpu.step().expect("LD should decode");
+    }
+    assert_eq!(0x42, cpu.registers.b);
+    assert_eq!(0x42, cpu.registers.l);
+    assert_eq!(0x42, cpu.bus.read_byte(0xC042));
+}
+
+#[test]
+fn illegal_opcode_locks_the_cpu() {
+    let mut cpu = cpu_with_program(0x0150, &[0xD3]);
+    cpu.ime = true;
+    cpu.bus.write_byte(0xFFFF, 0x01);
+
+    for _ in 0..10 {
+        assert_eq!(4, cpu.step().expect("illegal opcodes still decode"));
+        cpu.bus.interrupts.request(Interrupt::VBlank);
+    }
+    assert!(cpu.is_locked());
+    assert_eq!(0x0150, cpu.pc, "nothing, not even an interrupt, should move PC");
+}
+
+#[test]
+fn stop_waits_for_a_button() {
+    // STOP 0 ; NOP
+    let mut cpu = cpu_with_program(0x0150, &[0x10, 0x00, 0x00]);
+    // select the d-pad
+    cpu.bus.write_byte(0xFF00, 0x20);
+
+    cpu.step().expect("STOP should decode");
+    assert_eq!(0x0152, cpu.pc);
+    assert_eq!(0, cpu.bus.read_byte(DIV_ADDRESS));
+    let ly = cpu.bus.read_byte(0xFF44);
+    // DIV counts up every 256 T-cycles, so this is plenty to see it move if it ran
+    for _ in 0..100 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(0x0152, cpu.pc);
+    assert_eq!(0, cpu.bus.read_byte(DIV_ADDRESS), "the timer stops with the clock");
+    assert_eq!(ly, cpu.bus.read_byte(0xFF44), "so does the PPU");
+
+    let bus = &mut cpu.bus;
+    bus.joypad.press(crate::joypad::Button::Right, &mut bus.interrupts);
+    cpu.step().unwrap();
+    assert_eq!(0x0153, cpu.pc);
+}
+
+#[test]
+fn runs_an_assembled_loop() {
+    let mut cpu = cpu_with_assembly(0x0150, "
+                LD HL,$C000
+                LD B,4
+        loop:   LD (HL+),A
+                INC A
+                DEC B
+                JR NZ,loop
+        done:   JR done
+    ");
+
+    for _ in 0..100 {
+        if cpu.pc == 0x015A {
+            break;
+        }
+        cpu.step().expect("the loop should decode");
+    }
+    assert_eq!(0x015A, cpu.pc, "the loop should have finished");
+    assert_eq!([0, 1, 2, 3], [0xC000, 0xC001, 0xC002, 0xC003].map(|address| cpu.bus.read_byte(address)));
+    assert_eq!(0xC004, cpu.registers.get_hl());
+    assert_eq!(0, cpu.registers.b);
+}
+
+/// A trace output the test can still read after the CPU took it
+#[derive(Clone, Default)]
+struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn traces_in_the_gameboy_doctor_format() {
+    let mut cpu = cpu_with_assembly(0x0150, "
+                LD B,2
+        loop:   DEC B
+                JR NZ,loop
+                LD A,$42
+                HALT
+    ");
+    let output = SharedBuffer::default();
+    cpu.set_tracer(Tracer::new(output.clone(), TraceFilter::default()));
+    for _ in 0..9 {
+        cpu.step().unwrap();
+    }
+
+    let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!("A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:DFFF PC:0150 PCMEM:06,02,05,20", lines[0]);
+    assert_eq!("A:00 F:00 B:02 C:00 D:00 E:00 H:00 L:00 SP:DFFF PC:0152 PCMEM:05,20,FD,3E", lines[1]);
+    // the HALT gets a line but the steps spent halted after it don't
+    assert_eq!(7, lines.len(), "{trace}");
+    assert_eq!(7, cpu.tracer().unwrap().lines());
+    assert!(lines[6].starts_with("A:42 "), "{trace}");
+    assert!(lines[6].ends_with("SP:DFFF PC:0157 PCMEM:76,00,00,00"), "{trace}");
+}
+
+#[test]
+fn traces_only_what_the_filter_picks() {
+    let mut cpu = cpu_with_assembly(0x0150, "
+        loop:   INC A
+                NOP
+                JR loop
+    ");
+    let output = SharedBuffer::default();
+    cpu.set_tracer(Tracer::new(output.clone(), TraceFilter { pcs: 0x0151..=0x0152, limit: Some(3) }));
+    for _ in 0..30 {
+        cpu.step().unwrap();
+    }
+
+    let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let pcs: Vec<&str> = trace.lines().map(|line| line.split(' ').nth(9).unwrap()).collect();
+    assert_eq!(vec!["PC:0151", "PC:0152", "PC:0151"], pcs);
+    assert!(cpu.take_tracer().unwrap().finish().is_ok());
+}
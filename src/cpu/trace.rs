@@ -0,0 +1,82 @@
+//! Execution traces in the Gameboy Doctor log format, one line per instruction with the state
+//! right before it runs:
+//! ```text
+//! A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//! ```
+//! PCMEM being the four bytes from PC on. Interrupt dispatches and cycles spent idling in HALT
+//! or STOP aren't instructions so they don't get lines, same as in the reference logs.
+//!
+//! Gameboy Doctor's logs (and the BGB ones it's compatible with) come from emulators with LY
+//! stuck at 0x90, so a ROM that polls LY will drift from them in timing rather than in logic.
+
+use std::io::{ self, Write };
+use std::ops::RangeInclusive;
+
+use super::register::Registers;
+
+/// Which instructions make it into a trace
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct TraceFilter {
+    /// Only instructions at these addresses are traced
+    pub pcs: RangeInclusive<u16>,
+    /// The trace stops after this many lines
+    pub limit: Option<u64>,
+}
+
+impl Default for TraceFilter {
+    /// Everything, forever
+    fn default() -> Self {
+        Self { pcs: 0x0000..=0xFFFF, limit: None }
+    }
+}
+
+pub struct Tracer {
+    output: Box<dyn Write>,
+    filter: TraceFilter,
+    lines: u64,
+    /// The first write that failed, after which nothing else is written. `CPU::step` has no
+    /// way to report it so it waits for `finish`.
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// Traces into `output`, which should be buffered as it gets a write per instruction
+    pub fn new(output: impl Write + 'static, filter: TraceFilter) -> Self {
+        Self { output: Box::new(output), filter, lines: 0, error: None }
+    }
+
+    /// How many lines were traced
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    /// Whether the instruction at `pc` goes in the trace
+    pub(super) fn wants(&self, pc: u16) -> bool {
+        self.error.is_none()
+            && self.filter.pcs.contains(&pc)
+            && self.filter.limit.is_none_or(|limit| self.lines < limit)
+    }
+
+    pub(super) fn trace(&mut self, registers: &Registers, sp: u16, pc: u16, pc_memory: [u8; 4]) {
+        let Registers { a, b, c, d, e, h, l, f } = registers;
+        let [m0, m1, m2, m3] = pc_memory;
+        let result = writeln!(
+            self.output,
+            "A:{a:02X} F:{:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} SP:{sp:04X} PC:{pc:04X} PCMEM:{m0:02X},{m1:02X},{m2:02X},{m3:02X}",
+            u8::from(*f),
+        );
+        match result {
+            Ok(()) => self.lines += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    /// Flushes the trace, and reports the first write that failed if any did
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.output.flush()
+    }
+}
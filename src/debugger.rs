@@ -0,0 +1,445 @@
+//! A command-line debugger, what `gameboy_emulator debug` runs. It takes one command per line,
+//! either typed in or from a script, so a session can be replayed in CI:
+//! ```text
+//! break $0150          ; stop when PC gets to 0x0150
+//! watch $C000-$C0FF w  ; stop when the CPU writes 0xC000-0xC0FF
+//! continue             ; run until one of those
+//! step 3
+//! regs
+//! set a $12
+//! x $C000 32
+//! disas
+//! ```
+//! (`help` lists everything.) Numbers are decimal, or hex with `$` or `0x` in front.
+//!
+//! Breakpoints only exist in here and get checked between steps, and the bus only checks
+//! watchpoints while there are some, so an emulator without a debugger doesn't pay for either.
+
+#[cfg(test)]
+mod tests;
+
+use std::io::{ self, BufRead, Write };
+
+use crate::cpu::Registers;
+use crate::emulator::{ Access, Emulator, WatchHit, WatchKind, Watchpoint };
+
+const HELP: &str = "\
+step [N]                     run N instructions (1 by default)
+continue [CYCLES]            run until a breakpoint or watchpoint, or for at most CYCLES T-cycles
+                             (in scripts, 10 seconds of emulated time without CYCLES)
+break <ADDRESS>              stop when PC gets to ADDRESS
+watch <START>[-<END>] [r|w|rw]
+                             stop when the CPU reads and/or writes (the default) the range
+delete [ID]                  remove a breakpoint or watchpoint, or all of them
+list                         show the breakpoints and watchpoints
+regs                         show the registers
+set <REGISTER> <VALUE>       set a, f, b, c, d, e, h, l, af, bc, de, hl, sp or pc
+x <ADDRESS> [LENGTH]         hexdump LENGTH bytes (64 by default)
+poke <ADDRESS> <BYTE>...     write bytes starting at ADDRESS
+disas [ADDRESS] [COUNT]      disassemble COUNT instructions (10 by default), around PC without ADDRESS
+help                         show this
+quit                         stop debugging
+";
+
+/// How many instructions `disas` shows before PC
+const DISASSEMBLY_CONTEXT: usize = 3;
+const DEFAULT_DISASSEMBLY_COUNT: usize = 10;
+const DEFAULT_DUMP_LENGTH: u32 = 64;
+/// The most a `continue` without CYCLES runs for in a script, 10 seconds of emulated time, so
+/// a breakpoint that's never hit fails a CI replay instead of hanging it
+const SCRIPT_CYCLE_LIMIT: u64 = 10 * 4_194_304;
+const BYTES_PER_DUMP_LINE: u32 = 16;
+
+/// What a command did
+#[derive(PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Reply {
+    /// Carry on, after showing this
+    Output(String),
+    Quit,
+}
+
+pub struct Debugger {
+    emulator: Emulator,
+    /// By ID, which breakpoints and watchpoints share
+    breakpoints: Vec<(usize, u16)>,
+    /// By ID, in the order the bus has them
+    watchpoints: Vec<(usize, Watchpoint)>,
+    next_id: usize,
+    /// Whether commands come from a script, where `continue` without CYCLES is capped
+    scripted: bool,
+    /// What that cap is, `SCRIPT_CYCLE_LIMIT` outside tests
+    script_cycle_limit: u64,
+}
+
+impl Debugger {
+    pub fn new(emulator: Emulator) -> Self {
+        Self { emulator, breakpoints: Vec::new(), watchpoints: Vec::new(), next_id: 1, scripted: false, script_cycle_limit: SCRIPT_CYCLE_LIMIT }
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    /// Runs commands from `script`, writing each one and what it did to `output`, until the end
+    /// or a `quit`. Stops at the first command that fails, with the line it's on.
+    pub fn run_script(&mut self, script: impl BufRead, output: &mut impl Write) -> Result<(), String> {
+        self.scripted = true;
+        let result = self.run_script_lines(script, output);
+        self.scripted = false;
+        result
+    }
+
+    fn run_script_lines(&mut self, script: impl BufRead, output: &mut impl Write) -> Result<(), String> {
+        for (index, line) in script.lines().enumerate() {
+            let line = line.map_err(|err| format!("Couldn't read the script: {err}"))?;
+            let command = strip_comment(&line);
+            if command.is_empty() {
+                continue;
+            }
+            write_output(output, &format!("> {command}\n"))?;
+            match self.execute(command) {
+                Ok(Reply::Output(text)) => write_output(output, &text)?,
+                Ok(Reply::Quit) => break,
+                Err(err) => return Err(format!("line {}: {err}", index + 1)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads commands from `input` with a prompt until it runs out or gets a `quit`. Commands
+    /// that fail just say why.
+    pub fn run_interactive(&mut self, mut input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        write!(output, "{}", self.location())?;
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+            let command = strip_comment(&line);
+            if command.is_empty() {
+                continue;
+            }
+            match self.execute(command) {
+                Ok(Reply::Output(text)) => write!(output, "{text}")?,
+                Ok(Reply::Quit) => return Ok(()),
+                Err(err) => writeln!(output, "{err}")?,
+            }
+        }
+    }
+
+    /// Runs one command
+    pub fn execute(&mut self, command: &str) -> Result<Reply, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| args.get(index).copied();
+
+        let output = match name.to_ascii_lowercase().as_str() {
+            "step" | "s" => {
+                let steps = arg(0).map(parse_number).transpose()?.unwrap_or(1);
+                self.run(Some(steps), None)?
+            }
+            "continue" | "c" => {
+                let cycles = arg(0).map(parse_number).transpose()?;
+                if cycles.is_none() && self.breakpoints.is_empty() && self.watchpoints.is_empty() {
+                    return Err("Nothing would stop that, set a breakpoint or watchpoint or give it CYCLES".into());
+                }
+                let cap = self.scripted.then_some(self.script_cycle_limit);
+                self.run(None, cycles.or(cap))?
+            }
+            "break" | "b" => {
+                let address = parse_address(arg(0).ok_or("break needs an address")?)?;
+                let id = self.take_id();
+                self.breakpoints.push((id, address));
+                format!("Breakpoint {id} at ${address:04X}\n")
+            }
+            "watch" | "w" => {
+                let watchpoint = parse_watchpoint(arg(0).ok_or("watch needs an address")?, arg(1))?;
+                let id = self.take_id();
+                self.watchpoints.push((id, watchpoint));
+                self.sync_watchpoints();
+                format!("Watchpoint {id} on {}\n", describe_watchpoint(&watchpoint))
+            }
+            "delete" => {
+                match arg(0) {
+                    Some(id) => {
+                        let id = parse_number(id)? as usize;
+                        let count = self.breakpoints.len() + self.watchpoints.len();
+                        self.breakpoints.retain(|(existing, _)| *existing != id);
+                        self.watchpoints.retain(|(existing, _)| *existing != id);
+                        if self.breakpoints.len() + self.watchpoints.len() == count {
+                            return Err(format!("There's no breakpoint or watchpoint {id}"));
+                        }
+                    }
+                    None => {
+                        self.breakpoints.clear();
+                        self.watchpoints.clear();
+                    }
+                }
+                self.sync_watchpoints();
+                String::new()
+            }
+            "list" | "l" => self.list(),
+            "regs" | "r" => format!("{}\n", self.registers()),
+            "set" => {
+                let register = arg(0).ok_or("set needs a register")?;
+                let value = parse_number(arg(1).ok_or("set needs a value")?)?;
+                self.set_register(register, value)?;
+                format!("{}\n", self.registers())
+            }
+            "x" => {
+                let address = parse_address(arg(0).ok_or("x needs an address")?)?;
+                let length = arg(1).map(parse_number).transpose()?.unwrap_or(DEFAULT_DUMP_LENGTH as u64);
+                self.hexdump(address, length.min(0x1_0000) as u32)
+            }
+            "poke" => {
+                let address = parse_address(arg(0).ok_or("poke needs an address")?)?;
+                if args.len() < 2 {
+                    return Err("poke needs at least one byte".into());
+                }
+                for (offset, byte) in args[1..].iter().enumerate() {
+                    let byte = parse_byte(byte)?;
+                    self.emulator.write_byte(address.wrapping_add(offset as u16), byte);
+                }
+                String::new()
+            }
+            "disas" | "u" => {
+                let start = arg(0).map(parse_address).transpose()?;
+                let count = arg(1).map(parse_number).transpose()?.map_or(DEFAULT_DISASSEMBLY_COUNT, |count| count as usize);
+                self.disassembly(start, count)
+            }
+            "help" | "h" | "?" => HELP.to_string(),
+            "quit" | "q" => return Ok(Reply::Quit),
+            _ => return Err(format!("Unknown command {name}, try help")),
+        };
+        Ok(Reply::Output(output))
+    }
+
+    fn take_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn sync_watchpoints(&mut self) {
+        let watchpoints = self.watchpoints.iter().map(|(_, watchpoint)| *watchpoint).collect();
+        self.emulator.set_watchpoints(watchpoints);
+    }
+
+    /// Steps until `steps` are done, `cycles` have passed, or something stops it, then says why
+    /// it stopped and where it is
+    fn run(&mut self, steps: Option<u64>, cycles: Option<u64>) -> Result<String, String> {
+        let start_cycles = self.emulator.cpu().cycles();
+        let mut stepped = 0;
+
+        let reason = loop {
+            if steps.is_some_and(|steps| stepped >= steps) {
+                break String::new();
+            }
+            if cycles.is_some_and(|cycles| self.emulator.cpu().cycles() - start_cycles >= cycles) {
+                break format!("Stopped after {} T-cycles\n", self.emulator.cpu().cycles() - start_cycles);
+            }
+
+            let pc = self.emulator.cpu().pc();
+            self.emulator.step().map_err(|err| format!("The CPU hit a bad instruction: {err}"))?;
+            stepped += 1;
+
+            if let Some(hit) = self.emulator.take_watch_hit() {
+                break self.describe_hit(hit, pc);
+            }
+            let pc = self.emulator.cpu().pc();
+            if let Some((id, _)) = self.breakpoints.iter().find(|(_, address)| *address == pc) {
+                break format!("Breakpoint {id} at ${pc:04X}\n");
+            }
+            if self.emulator.cpu().is_locked() {
+                break "The CPU is locked up after an illegal opcode\n".to_string();
+            }
+        };
+        Ok(reason + &self.location())
+    }
+
+    fn describe_hit(&self, hit: WatchHit, pc: u16) -> String {
+        let (id, _) = self.watchpoints[hit.index];
+        let access = match hit.access {
+            Access::Read => "read",
+            Access::Write => "write",
+        };
+        format!("Watchpoint {id}: {access} ${:02X} at ${:04X}, by the instruction at ${pc:04X}\n", hit.value, hit.address)
+    }
+
+    /// The instruction PC is at
+    fn location(&self) -> String {
+        let pc = self.emulator.cpu().pc();
+        format!("${pc:04X}: {}\n", self.emulator.disassemble_at(pc).text)
+    }
+
+    fn list(&self) -> String {
+        let mut lines: Vec<(usize, String)> = self.breakpoints.iter()
+            .map(|(id, address)| (*id, format!("{id}: break at ${address:04X}\n")))
+            .chain(self.watchpoints.iter().map(|(id, watchpoint)| (*id, format!("{id}: watch {}\n", describe_watchpoint(watchpoint)))))
+            .collect();
+        if lines.is_empty() {
+            return "No breakpoints or watchpoints\n".to_string();
+        }
+        lines.sort();
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    fn registers(&self) -> String {
+        let cpu = self.emulator.cpu();
+        let Registers { a, b, c, d, e, h, l, f } = cpu.registers();
+        let flags: String = [(f.zero, 'Z'), (f.subtract, 'N'), (f.half_carry, 'H'), (f.carry, 'C')]
+            .iter()
+            .map(|&(set, flag)| if set { flag } else { '-' })
+            .collect();
+        format!(
+            "A=${a:02X} F=${:02X} B=${b:02X} C=${c:02X} D=${d:02X} E=${e:02X} H=${h:02X} L=${l:02X} SP=${:04X} PC=${:04X} flags={flags} ime={} halted={} cycles={}",
+            u8::from(*f), cpu.sp(), cpu.pc(), cpu.ime() as u8, cpu.is_halted() as u8, cpu.cycles(),
+        )
+    }
+
+    fn set_register(&mut self, register: &str, value: u64) -> Result<(), String> {
+        let register = register.to_ascii_lowercase();
+        let wide = matches!(register.as_str(), "af" | "bc" | "de" | "hl" | "sp" | "pc");
+        let limit = if wide { 0xFFFF } else { 0xFF };
+        if value > limit {
+            return Err(format!("{value:#X} doesn't fit in {register}"));
+        }
+
+        let cpu = self.emulator.cpu_mut();
+        let registers = cpu.registers_mut();
+        match register.as_str() {
+            "a" => registers.a = value as u8,
+            "f" => registers.f = (value as u8).into(),
+            "b" => registers.b = value as u8,
+            "c" => registers.c = value as u8,
+            "d" => registers.d = value as u8,
+            "e" => registers.e = value as u8,
+            "h" => registers.h = value as u8,
+            "l" => registers.l = value as u8,
+            "af" => registers.set_af(value as u16),
+            "bc" => registers.set_bc(value as u16),
+            "de" => registers.set_de(value as u16),
+            "hl" => registers.set_hl(value as u16),
+            "sp" => cpu.set_sp(value as u16),
+            "pc" => cpu.set_pc(value as u16),
+            _ => return Err(format!("There's no register {register}")),
+        }
+        Ok(())
+    }
+
+    fn hexdump(&self, start: u16, length: u32) -> String {
+        let mut dump = String::new();
+        for line_start in (0..length).step_by(BYTES_PER_DUMP_LINE as usize) {
+            let bytes: Vec<u8> = (line_start..length.min(line_start + BYTES_PER_DUMP_LINE))
+                .map(|offset| self.emulator.read_byte(start.wrapping_add(offset as u16)))
+                .collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let text: String = bytes.iter().map(|&byte| if byte.is_ascii_graphic() { byte as char } else { '.' }).collect();
+            dump.push_str(&format!("${:04X}: {:<47}  {text}\n", start.wrapping_add(line_start as u16), hex.join(" ")));
+        }
+        dump
+    }
+
+    /// `count` instructions from `start`, or without one from a few instructions before PC
+    fn disassembly(&self, start: Option<u16>, count: usize) -> String {
+        let pc = self.emulator.cpu().pc();
+        let mut address = start.unwrap_or_else(|| self.start_before(pc, DISASSEMBLY_CONTEXT));
+
+        let mut listing = String::new();
+        for _ in 0..count {
+            let disassembly = self.emulator.disassemble_at(address);
+            let bytes: Vec<String> = (0..disassembly.length as u16)
+                .map(|offset| format!("{:02X}", self.emulator.read_byte(address.wrapping_add(offset))))
+                .collect();
+            let marker = if address == pc { '>' } else { ' ' };
+            let breakpoint = if self.breakpoints.iter().any(|(_, breakpoint)| *breakpoint == address) { '*' } else { ' ' };
+            listing.push_str(&format!("{marker}{breakpoint} ${address:04X}  {:<8}  {}\n", bytes.join(" "), disassembly.text));
+            address = address.wrapping_add(disassembly.length as u16);
+        }
+        listing
+    }
+
+    /// Where to start disassembling to show `before` instructions ahead of `pc`. Instructions
+    /// don't say where they start, so this looks for the furthest back start whose instructions
+    /// land right on `pc`, falling back to `pc` itself.
+    fn start_before(&self, pc: u16, before: usize) -> u16 {
+        for distance in (1..=before as u16 * 3).rev() {
+            let Some(start) = pc.checked_sub(distance) else {
+                continue;
+            };
+            let mut starts = Vec::new();
+            let mut address = start as u32;
+            while address < pc as u32 {
+                starts.push(address as u16);
+                address += self.emulator.disassemble_at(address as u16).length as u32;
+            }
+            if address == pc as u32 && starts.len() >= before {
+                return starts[starts.len() - before];
+            }
+        }
+        pc
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split([';', '#']).next().unwrap_or_default().trim()
+}
+
+fn write_output(output: &mut impl Write, text: &str) -> Result<(), String> {
+    output.write_all(text.as_bytes()).map_err(|err| format!("Couldn't write the output: {err}"))
+}
+
+fn describe_watchpoint(watchpoint: &Watchpoint) -> String {
+    let kind = match watchpoint.kind {
+        WatchKind::Read => "reads",
+        WatchKind::Write => "writes",
+        WatchKind::ReadWrite => "reads and writes",
+    };
+    match watchpoint.start == watchpoint.end {
+        true => format!("{kind} of ${:04X}", watchpoint.start),
+        false => format!("{kind} of ${:04X}-${:04X}", watchpoint.start, watchpoint.end),
+    }
+}
+
+fn parse_watchpoint(range: &str, kind: Option<&str>) -> Result<Watchpoint, String> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+        None => (parse_address(range)?, parse_address(range)?),
+    };
+    if end < start {
+        return Err(format!("{range} ends before it starts"));
+    }
+    let kind = match kind.map(|kind| kind.to_ascii_lowercase()).as_deref() {
+        Some("r") => WatchKind::Read,
+        Some("w") | None => WatchKind::Write,
+        Some("rw") => WatchKind::ReadWrite,
+        Some(other) => return Err(format!("{other} isn't r, w or rw")),
+    };
+    Ok(Watchpoint { start, end, kind })
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("{text} isn't a number"))
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    let address = parse_number(text)?;
+    u16::try_from(address).map_err(|_| format!("{text} isn't an address"))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let byte = parse_number(text)?;
+    u8::try_from(byte).map_err(|_| format!("{text} isn't a byte"))
+}
@@ -0,0 +1,189 @@
+use super::*;
+use crate::cartridge::{ with_valid_header, Cartridge };
+use crate::cpu::assemble;
+
+/// A debugger on a ROM running `source` from 0x0100
+fn debugger(source: &str) -> Debugger {
+    let program = assemble(source, 0x0100).expect("the test program should assemble");
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+    let rom = with_valid_header(rom, 0x00, 0x00);
+    Debugger::new(Emulator::new(Cartridge::from_bytes(&rom).unwrap()))
+}
+
+/// Fills 0xC000-0xC003 with 1, 2, 3, 4 (A starts at 1 after the boot ROM) then spins
+const FILL: &str = "
+            LD HL,$C000
+            LD B,4
+    loop:   LD (HL+),A
+            INC A
+            DEC B
+            JR NZ,loop
+    done:   JR done
+";
+
+fn output(reply: Result<Reply, String>) -> String {
+    match reply {
+        Ok(Reply::Output(text)) => text,
+        other => panic!("expected output, got {other:?}"),
+    }
+}
+
+#[test]
+fn steps_and_shows_where_it_is() {
+    let mut debugger = debugger(FILL);
+
+    assert_eq!("$0103: LD B,$04\n", output(debugger.execute("step")));
+    assert_eq!("$0106: INC A\n", output(debugger.execute("s 2")));
+    assert_eq!(0x0106, debugger.emulator().cpu().pc());
+}
+
+#[test]
+fn continues_to_a_breakpoint() {
+    let mut debugger = debugger(FILL);
+
+    assert_eq!("Breakpoint 1 at $010A\n", output(debugger.execute("break $010A")));
+    assert_eq!("Breakpoint 1 at $010A\n$010A: JR $+0\n", output(debugger.execute("continue")));
+    assert_eq!([1, 2, 3, 4], [0xC000, 0xC001, 0xC002, 0xC003].map(|address| debugger.emulator().read_byte(address)));
+
+    // it's sitting on the breakpoint, the spin loop stays there
+    output(debugger.execute("delete 1"));
+    assert!(output(debugger.execute("continue 1000")).starts_with("Stopped after 100"));
+}
+
+#[test]
+fn watchpoints_catch_reads_and_writes() {
+    let mut debugger = debugger(FILL);
+
+    assert_eq!("Watchpoint 1 on writes of $C002\n", output(debugger.execute("watch $C002")));
+    assert_eq!(
+        "Watchpoint 1: write $03 at $C002, by the instruction at $0105\n$0106: INC A\n",
+        output(debugger.execute("continue")),
+    );
+
+    output(debugger.execute("delete"));
+    output(debugger.execute("watch $0103-$0104 r"));
+    output(debugger.execute("set pc $0103"));
+    let reply = output(debugger.execute("step 5"));
+    assert!(reply.starts_with("Watchpoint 2: read $06 at $0103, by the instruction at $0103\n"), "{reply}");
+}
+
+#[test]
+fn looking_at_memory_isnt_caught_by_watchpoints() {
+    let mut debugger = debugger(FILL);
+
+    output(debugger.execute("watch $C000-$C0FF rw"));
+    output(debugger.execute("poke $C000 $41 $42"));
+    output(debugger.execute("x $C000 2"));
+    assert_eq!(None, debugger.emulator_mut().take_watch_hit());
+    assert_eq!(0x42, debugger.emulator().read_byte(0xC001));
+}
+
+#[test]
+fn dumps_and_sets_registers() {
+    let mut debugger = debugger(FILL);
+
+    let regs = output(debugger.execute("regs"));
+    assert!(regs.starts_with("A=$01 F=$B0 B=$00 C=$13"), "{regs}");
+    assert!(regs.contains("SP=$FFFE PC=$0100 flags=Z-HC"), "{regs}");
+
+    output(debugger.execute("set hl $C123"));
+    output(debugger.execute("set F 0"));
+    let regs = output(debugger.execute("set a 7"));
+    assert!(regs.contains("A=$07 F=$00") && regs.contains("H=$C1 L=$23") && regs.contains("flags=----"), "{regs}");
+
+    assert!(debugger.execute("set a $100").is_err());
+    assert!(debugger.execute("set ix 1").is_err());
+}
+
+#[test]
+fn hexdumps_memory() {
+    let mut debugger = debugger(FILL);
+    output(debugger.execute("poke $C000 $48 $69 0"));
+
+    let dump = output(debugger.execute("x $C000 20"));
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(2, lines.len());
+    assert_eq!("$C000: 48 69 00 00 00 00 00 00 00 00 00 00 00 00 00 00  Hi..............", lines[0]);
+    assert!(lines[1].starts_with("$C010: 00 00 00 00  "), "{dump}");
+}
+
+#[test]
+fn disassembles_around_pc() {
+    let mut debugger = debugger(FILL);
+    output(debugger.execute("break $0107"));
+    output(debugger.execute("step 3"));
+
+    let listing = output(debugger.execute("disas"));
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(DEFAULT_DISASSEMBLY_COUNT, lines.len());
+    assert_eq!("   $0100  21 00 C0  LD HL,$C000", lines[0]);
+    assert_eq!(">  $0106  3C        INC A", lines[3]);
+    assert_eq!(" * $0107  05        DEC B", lines[4]);
+
+    let listing = output(debugger.execute("disas $0108 1"));
+    assert_eq!("   $0108  20 FB     JR NZ,$-3\n", listing);
+}
+
+#[test]
+fn runs_a_script() {
+    let mut debugger = debugger(FILL);
+    let script = "
+        # fill the first two bytes
+        break $0108 ; the JR
+        continue
+        continue
+        regs
+        quit
+        step
+    ";
+
+    let mut output = Vec::new();
+    debugger.run_script(script.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("> break $0108\nBreakpoint 1 at $0108\n> continue\nBreakpoint 1 at $0108\n"), "{output}");
+    assert!(output.contains("> regs\nA=$03"), "{output}");
+    assert!(!output.contains("> step"), "nothing runs after quit");
+}
+
+#[test]
+fn continue_needs_something_to_stop_it() {
+    let mut debugger = debugger(FILL);
+
+    let err = debugger.execute("continue").unwrap_err();
+    assert!(err.starts_with("Nothing would stop that"), "{err}");
+    assert_eq!(0x0100, debugger.emulator().cpu().pc(), "it shouldn't have run at all");
+    assert!(output(debugger.execute("continue 100")).starts_with("Stopped after 10"));
+}
+
+#[test]
+fn script_continues_give_up_eventually() {
+    let mut debugger = debugger(FILL);
+    debugger.script_cycle_limit = 10_000;
+    let mut output = Vec::new();
+
+    // never hit
+    debugger.run_script("break $0200\ncontinue\n".as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("> continue\nStopped after 100"), "{output}");
+}
+
+#[test]
+fn scripts_stop_at_the_first_error() {
+    let mut debugger = debugger(FILL);
+    let mut output = Vec::new();
+
+    let err = debugger.run_script("step\n\nfrobnicate\nstep".as_bytes(), &mut output).unwrap_err();
+    assert_eq!("line 3: Unknown command frobnicate, try help", err);
+    assert_eq!(0x0103, debugger.emulator().cpu().pc());
+}
+
+#[test]
+fn interactive_sessions_carry_on_after_errors() {
+    let mut debugger = debugger(FILL);
+    let mut output = Vec::new();
+
+    debugger.run_interactive("bogus\nstep\n".as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!("$0100: LD HL,$C000\n> Unknown command bogus, try help\n> $0103: LD B,$04\n> \n", output);
+}
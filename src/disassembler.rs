@@ -0,0 +1,82 @@
+//! Disassembling ROMs into listings, what the `disassemble` subcommand of the `gameboy_emulator`
+//! binary prints. For single instructions see [`disassemble`] (or
+//! [`Emulator::disassemble_at`](crate::emulator::Emulator::disassemble_at) for what a running
+//! emulator sees), and [`assemble`] to go the other way.
+//!
+//! The listing shows ROM banks the way the CPU sees them: bank 0 at 0x0000-0x3FFF, and whichever
+//! bank is asked for in the switchable area at 0x4000-0x7FFF.
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::cpu::{ assemble, disassemble, AssembleError, Disassembly };
+
+/// The size of a ROM bank, and so of each half of the ROM area
+pub const BANK_SIZE: usize = 0x4000;
+/// Where the switchable ROM bank is mapped
+const SWITCHABLE_START: u16 = 0x4000;
+/// Where the ROM area ends
+const ROM_END: u32 = 0x8000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ListingError {
+    /// The ROM doesn't have this bank
+    NoSuchBank { bank: usize, banks: usize },
+    /// The range is empty or goes past the ROM area
+    BadRange { start: u16, end: u32 },
+}
+
+impl std::fmt::Display for ListingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListingError::NoSuchBank { bank, banks } => write!(f, "There's no bank {bank}, the ROM has {banks}"),
+            ListingError::BadRange { start, end } => {
+                write!(f, "{start:#06X}-{end:#06X} isn't a range in the ROM area (0x0000-0x8000)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ListingError {}
+
+/// Disassembles a whole bank: bank 0 at 0x0000, any other at 0x4000
+pub fn disassemble_bank(rom: &[u8], bank: usize) -> Result<String, ListingError> {
+    let start = if bank == 0 { 0 } else { SWITCHABLE_START };
+    disassemble_range(rom, bank, start, start as u32 + BANK_SIZE as u32)
+}
+
+/// Disassembles the addresses from `start` up to (but not including) `end`, with `bank` in the
+/// switchable area. The last instruction is listed whole even if it runs past `end`.
+pub fn disassemble_range(rom: &[u8], bank: usize, start: u16, end: u32) -> Result<String, ListingError> {
+    if start as u32 >= end || end > ROM_END {
+        return Err(ListingError::BadRange { start, end });
+    }
+    let banks = rom.len().div_ceil(BANK_SIZE);
+    if bank >= banks || (bank == 0 && end > SWITCHABLE_START as u32) {
+        return Err(ListingError::NoSuchBank { bank, banks });
+    }
+
+    let mut listing = String::new();
+    let mut address = start as u32;
+    while address < end {
+        // the bytes from here to the end of the ROM area, so instructions at the end of the range
+        // still get their operands
+        let bytes: Vec<u8> = (address..ROM_END).take(3).map(|address| rom_byte(rom, bank, address as u16)).collect();
+        let Disassembly { text, length } = disassemble(&bytes);
+
+        let hex: Vec<String> = bytes[..length as usize].iter().map(|byte| format!("{byte:02X}")).collect();
+        let shown_bank = if address < SWITCHABLE_START as u32 { 0 } else { bank };
+        listing.push_str(&format!("{shown_bank:02X}:{address:04X}  {:<8}  {text}\n", hex.join(" ")));
+        address += length as u32;
+    }
+    Ok(listing)
+}
+
+/// The byte at `address` in the ROM area with `bank` switched in, 0xFF past the end of the file
+fn rom_byte(rom: &[u8], bank: usize, address: u16) -> u8 {
+    let offset = match address.checked_sub(SWITCHABLE_START) {
+        Some(offset) => bank * BANK_SIZE + offset as usize,
+        None => address as usize,
+    };
+    rom.get(offset).copied().unwrap_or(0xFF)
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+/// Two banks: bank 0 starting with `NOP ; JP $0150`, bank 1 with `LD A,$2A ; RET`
+fn rom() -> Vec<u8> {
+    let mut rom = vec![0; 2 * BANK_SIZE];
+    rom[..4].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[BANK_SIZE..BANK_SIZE + 3].copy_from_slice(&[0x3E, 0x2A, 0xC9]);
+    rom
+}
+
+#[test]
+fn lists_a_range_with_addresses_and_bytes() {
+    let listing = disassemble_range(&rom(), 1, 0x0000, 0x0005).unwrap();
+    assert_eq!("00:0000  00        NOP\n00:0001  C3 50 01  JP $0150\n00:0004  00        NOP\n", listing);
+}
+
+#[test]
+fn lists_the_switchable_bank_at_0x4000() {
+    let listing = disassemble_bank(&rom(), 1).unwrap();
+    let mut lines = listing.lines();
+    assert_eq!(Some("01:4000  3E 2A     LD A,$2A"), lines.next());
+    assert_eq!(Some("01:4002  C9        RET"), lines.next());
+    assert_eq!(Some("01:4003  00        NOP"), lines.next());
+    assert!(listing.ends_with("01:7FFF  00        NOP\n"));
+
+    assert_eq!(BANK_SIZE, disassemble_bank(&rom(), 0).unwrap().lines().count() + 2);
+}
+
+#[test]
+fn lists_the_last_instruction_whole() {
+    let listing = disassemble_range(&rom(), 1, 0x0001, 0x0002).unwrap();
+    assert_eq!("00:0001  C3 50 01  JP $0150\n", listing);
+}
+
+#[test]
+fn rejects_missing_banks_and_bad_ranges() {
+    assert_eq!(Err(ListingError::NoSuchBank { bank: 2, banks: 2 }), disassemble_bank(&rom(), 2));
+    assert_eq!(Err(ListingError::NoSuchBank { bank: 0, banks: 2 }), disassemble_range(&rom(), 0, 0x3FF0, 0x4010));
+    assert_eq!(Err(ListingError::BadRange { start: 0x10, end: 0x10 }), disassemble_range(&rom(), 1, 0x10, 0x10));
+    assert_eq!(Err(ListingError::BadRange { start: 0x7000, end: 0x8001 }), disassemble_range(&rom(), 1, 0x7000, 0x8001));
+}
@@ -0,0 +1,372 @@
+//! The emulator as a whole, i.e. the handle a frontend drives: it owns the CPU (which owns the
+//! bus and everything on it) and the things that live outside the emulated hardware, like the
+//! save file.
+
+mod input;
+mod save;
+mod wav;
+#[cfg(test)]
+mod tests;
+
+pub use crate::cartridge::{ Cartridge, CartridgeError };
+pub use crate::cpu::{ Disassembly, TraceFilter, Tracer };
+pub use crate::joypad::Button;
+pub use crate::memory_bus::{ Access, WatchHit, WatchKind, Watchpoint };
+pub use crate::ppu::RenderMode;
+pub use crate::serial::{ CaptureEndpoint, LinkEndpoint, NullEndpoint, SerialEndpoint, SocketEndpoint };
+pub use input::InputScript;
+pub use save::SaveFile;
+pub use wav::WavWriter;
+
+use std::fs::File;
+use std::io::{ self, BufWriter };
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cpu::{ disassemble_at, CPU, InstructionBuildError, TCycles };
+use crate::memory_bus::MemoryBus;
+use crate::ppu::{ self, SCREEN_WIDTH, SCREEN_HEIGHT };
+
+/// How much emulated time passes between save file flushes unless configured otherwise
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// What recordings use when the host didn't pick a sample rate
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+/// Seconds of audio kept for a host that stopped taking samples, the oldest go past that
+const MAX_BUFFERED_AUDIO_SECONDS: usize = 1;
+
+pub struct Emulator {
+    cpu: CPU,
+    /// Where battery backed RAM goes, `None` for cartridges without a battery (or when saving is off)
+    save_file: Option<SaveFile>,
+    /// What the last autosave failed with, until a save works again
+    save_error: Option<io::Error>,
+    /// The `.wav` file audio is being recorded to, if any
+    recording: Option<WavWriter<BufWriter<File>>>,
+    /// What cut the recording short, kept for `stop_recording` to hand to the host
+    recording_error: Option<io::Error>,
+    /// How many of the samples still in the APU's buffer are already recorded
+    recorded_samples: usize,
+    /// Whether the host pulls samples with `take_samples`. If not, samples are only kept until
+    /// they're recorded.
+    samples_taken: bool,
+}
+
+impl Emulator {
+    /// Runs `cartridge` without a save file
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self::with_cpu(CPU::new(MemoryBus::with_cartridge(cartridge)), None)
+    }
+
+    /// Loads the ROM at `rom_path`, and for battery backed cartridges also the `.sav` next to it
+    pub fn open(rom_path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        let rom_path = rom_path.as_ref();
+        let cartridge = Cartridge::from_path(rom_path)?;
+        if cartridge.has_battery() {
+            Self::with_save_file(cartridge, SaveFile::next_to(rom_path))
+        } else {
+            Ok(Self::new(cartridge))
+        }
+    }
+
+    /// Runs `cartridge` with its battery backed state loaded from, and saved to, `save_file`
+    pub fn with_save_file(mut cartridge: Cartridge, save_file: SaveFile) -> Result<Self, CartridgeError> {
+        save_file.load(&mut cartridge)?;
+        Ok(Self::with_cpu(CPU::new(MemoryBus::with_cartridge(cartridge)), Some(save_file)))
+    }
+
+    fn with_cpu(cpu: CPU, save_file: Option<SaveFile>) -> Self {
+        Self { cpu, save_file, save_error: None, recording: None, recording_error: None, recorded_samples: 0, samples_taken: false }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    /// For poking at registers from a debugger
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        self.cpu.bus().cartridge().expect("the emulator always has a cartridge in the slot")
+    }
+
+    /// Reads `address` the way the CPU would see it right now, without side effects
+    pub fn read_byte(&self, address: u16) -> u8 {
+        self.cpu.bus().peek_byte(address)
+    }
+
+    /// Writes `address` the way the CPU would, without watchpoints seeing it
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.cpu.bus_mut().poke_byte(address, value);
+    }
+
+    /// Replaces the watchpoints on the CPU's memory accesses, an empty list turning them off
+    pub fn set_watchpoints(&mut self, watchpoints: Vec<Watchpoint>) {
+        self.cpu.bus_mut().set_watchpoints(watchpoints);
+    }
+
+    /// The first access a watchpoint caught since this was last called
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.cpu.bus().take_watch_hit()
+    }
+
+    /// Disassembles the instruction at `address`, as the CPU would see it right now
+    pub fn disassemble_at(&self, address: u16) -> Disassembly {
+        disassemble_at(self.cpu.bus(), address)
+    }
+
+    pub fn save_file(&self) -> Option<&SaveFile> {
+        self.save_file.as_ref()
+    }
+
+    /// See [`SaveFile::set_autosave_interval`]; does nothing without a save file
+    pub fn set_autosave_interval(&mut self, interval: Option<Duration>) {
+        if let Some(save_file) = &mut self.save_file {
+            save_file.set_autosave_interval(interval);
+        }
+    }
+
+    /// Runs one CPU step (see [`CPU::step`]) and returns how many T-cycles it took
+    pub fn step(&mut self) -> Result<TCycles, InstructionBuildError> {
+        let frame = self.frame_count();
+        let cycles = self.cpu.step()?;
+
+        if let (Some(save_file), Some(cartridge)) = (&mut self.save_file, self.cpu.bus_mut().cartridge_mut()) {
+            // a failed autosave shouldn't stop the game, the next one (or the one on shutdown) may work
+            match save_file.tick(cartridge, cycles) {
+                Ok(true) => self.save_error = None,
+                Ok(false) => {}
+                Err(err) => self.save_error = Some(err),
+            }
+        }
+        if self.recording.is_some() {
+            self.record_new_samples();
+            if self.frame_count() != frame {
+                self.flush_recording();
+            }
+        }
+        self.trim_samples();
+        Ok(cycles)
+    }
+
+    /// Runs until the PPU finishes a frame. With the LCD off nothing finishes, so this gives up
+    /// after a frame's worth of cycles.
+    pub fn run_frame(&mut self) -> Result<(), InstructionBuildError> {
+        let frame = self.cpu.bus().ppu.frame_count();
+        let mut cycles = 0;
+        while self.cpu.bus().ppu.frame_count() == frame && cycles < ppu::DOTS_PER_FRAME {
+            cycles += self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Frames the PPU has finished since power on
+    pub fn frame_count(&self) -> u64 {
+        self.cpu.bus().ppu.frame_count()
+    }
+
+    /// Runs `frames` frames, applying the events `script` has for each frame (by
+    /// [`Emulator::frame_count`]) before running it
+    pub fn run_frames_with_input(&mut self, frames: u64, script: &InputScript) -> Result<(), InstructionBuildError> {
+        for _ in 0..frames {
+            for event in script.events_at(self.frame_count()) {
+                if event.pressed {
+                    self.press(event.button);
+                } else {
+                    self.release(event.button);
+                }
+            }
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+
+    pub fn press(&mut self, button: Button) {
+        let bus = self.cpu.bus_mut();
+        bus.joypad.press(button, &mut bus.interrupts);
+    }
+
+    pub fn release(&mut self, button: Button) {
+        let bus = self.cpu.bus_mut();
+        bus.joypad.release(button, &mut bus.interrupts);
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.cpu.bus().joypad.is_pressed(button)
+    }
+
+    /// The screen, one shade 0 (white) to 3 (black) per pixel, row by row
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        self.cpu.bus().ppu.framebuffer()
+    }
+
+    /// Starts (or with `None` stops) making audio samples at `sample_rate` per second, see
+    /// [`Emulator::take_samples`]. A WAV file has just the one sample rate, so this ends any
+    /// recording. If finishing it fails, the next `stop_recording` says so.
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        if let Err(err) = self.stop_recording() {
+            self.recording_error = Some(err);
+        }
+        self.cpu.bus_mut().apu.set_sample_rate(sample_rate);
+    }
+
+    /// The audio made since the last call, as interleaved left/right 16-bit PCM samples
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        self.samples_taken = true;
+        if self.recording.is_some() {
+            self.record_new_samples();
+        }
+        self.recorded_samples = 0;
+        self.cpu.bus_mut().apu.take_samples()
+    }
+
+    /// Starts recording audio to a `.wav` file at `path`, at the sample rate already picked or
+    /// `DEFAULT_SAMPLE_RATE`. Any recording already going is finished first.
+    pub fn start_recording(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.stop_recording()?;
+        let sample_rate = match self.cpu.bus().apu.sample_rate() {
+            Some(sample_rate) => sample_rate,
+            None => {
+                self.cpu.bus_mut().apu.set_sample_rate(Some(DEFAULT_SAMPLE_RATE));
+                DEFAULT_SAMPLE_RATE
+            }
+        };
+        self.recording = Some(WavWriter::create(path, sample_rate)?);
+        // only what's made from now on goes in
+        self.recorded_samples = self.cpu.bus().apu.samples().len();
+        Ok(())
+    }
+
+    /// Finishes the recording, if there's one going. A recording that a write error already cut
+    /// short stops with that error here.
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        if let Some(err) = self.recording_error.take() {
+            return Err(err);
+        }
+        if self.recording.is_none() {
+            return Ok(());
+        }
+        self.record_new_samples();
+        match self.recording.take() {
+            Some(recording) => recording.finish().map(drop),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Makes the recording a complete file up to now, see [`WavWriter::flush`]
+    fn flush_recording(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            if let Err(err) = recording.flush() {
+                self.recording = None;
+                self.recording_error = Some(err);
+            }
+        }
+    }
+
+    /// Writes the samples the APU made since the last call to the recording. The host may still
+    /// want them, so they stay in the APU's buffer (see `trim_samples` for when they go).
+    fn record_new_samples(&mut self) {
+        let samples = &self.cpu.bus().apu.samples()[self.recorded_samples..];
+        if samples.is_empty() {
+            return;
+        }
+        self.recorded_samples += samples.len();
+        if let Some(recording) = &mut self.recording {
+            if let Err(err) = recording.write_samples(samples) {
+                self.recording = None;
+                self.recording_error = Some(err);
+            }
+        }
+    }
+
+    /// Keeps the APU's buffer from growing forever. With nobody taking samples they're only
+    /// there for the recording, so they go once they're in it. Otherwise the oldest go once the
+    /// host is more than `MAX_BUFFERED_AUDIO_SECONDS` behind.
+    fn trim_samples(&mut self) {
+        let apu = &mut self.cpu.bus_mut().apu;
+        let Some(sample_rate) = apu.sample_rate() else {
+            return;
+        };
+        let dropped = if !self.samples_taken && self.recording.is_some() {
+            self.recorded_samples
+        } else {
+            let limit = sample_rate as usize * 2 * MAX_BUFFERED_AUDIO_SECONDS;
+            let buffered = apu.samples().len();
+            // only trimming at twice the limit saves shifting the whole buffer every step
+            if buffered <= 2 * limit {
+                return;
+            }
+            buffered - limit
+        };
+        apu.drop_samples(dropped);
+        self.recorded_samples = self.recorded_samples.saturating_sub(dropped);
+    }
+
+    /// Starts tracing the instructions `filter` picks to the file at `path`, in the Gameboy
+    /// Doctor format (see [`Tracer`]). Any trace already going is finished first.
+    pub fn start_trace(&mut self, path: impl AsRef<Path>, filter: TraceFilter) -> io::Result<()> {
+        self.stop_trace()?;
+        let file = File::create(path)?;
+        self.cpu.set_tracer(Tracer::new(BufWriter::new(file), filter));
+        Ok(())
+    }
+
+    /// Finishes the trace, if there's one going
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.cpu.take_tracer() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Plugs `endpoint` into the other end of the link cable
+    pub fn set_serial_endpoint(&mut self, endpoint: impl SerialEndpoint + 'static) {
+        self.cpu.bus_mut().serial.set_endpoint(endpoint);
+    }
+
+    /// What unplugged the link cable, if the other side went away. The game just sees nobody
+    /// on the other end from then on, so it's up to the host to check.
+    pub fn link_error(&self) -> Option<&io::Error> {
+        self.cpu.bus().serial.link_error()
+    }
+
+    /// Picks how the PPU draws, see [`RenderMode`]. Takes effect from the next line on.
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.cpu.bus_mut().ppu.set_render_mode(render_mode);
+    }
+
+    /// Writes unsaved changes to the save file now
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        if let (Some(save_file), Some(cartridge)) = (&mut self.save_file, self.cpu.bus_mut().cartridge_mut()) {
+            save_file.flush(cartridge)?;
+        }
+        self.save_error = None;
+        Ok(())
+    }
+
+    /// What the last autosave failed with, `None` again once a save works. A failed autosave
+    /// doesn't stop the emulator, so it's up to the host to check.
+    pub fn last_save_error(&self) -> Option<&io::Error> {
+        self.save_error.as_ref()
+    }
+}
+
+impl Drop for Emulator {
+    /// Flushes the save and finishes the audio recording and trace on shutdown
+    fn drop(&mut self) {
+        if let Err(err) = self.flush_save() {
+            eprintln!("Couldn't save on shutdown: {err}");
+        }
+        if let Err(err) = self.stop_recording() {
+            eprintln!("Couldn't finish the audio recording: {err}");
+        }
+        if let Err(err) = self.stop_trace() {
+            eprintln!("Couldn't finish the trace: {err}");
+        }
+    }
+}
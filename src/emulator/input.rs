@@ -0,0 +1,51 @@
+//! Button presses scripted against frame numbers, for driving a game without a frontend (tests,
+//! recorded demos, ...).
+
+use crate::joypad::Button;
+
+/// A press or release of `button` at the start of `frame`
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct InputEvent {
+    pub frame: u64,
+    pub button: Button,
+    pub pressed: bool,
+}
+
+/// A list of input events, kept in frame order. Events on the same frame happen in the order
+/// they were added.
+#[derive(Clone, Default)]
+pub struct InputScript {
+    events: Vec<InputEvent>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, frame: u64, button: Button) -> &mut Self {
+        self.push(InputEvent { frame, button, pressed: true })
+    }
+
+    pub fn release(&mut self, frame: u64, button: Button) -> &mut Self {
+        self.push(InputEvent { frame, button, pressed: false })
+    }
+
+    /// Presses `button` on `frame` and releases it `frames` later
+    pub fn tap(&mut self, frame: u64, button: Button, frames: u64) -> &mut Self {
+        self.press(frame, button).release(frame + frames, button)
+    }
+
+    fn push(&mut self, event: InputEvent) -> &mut Self {
+        let index = self.events.partition_point(|other| other.frame <= event.frame);
+        self.events.insert(index, event);
+        self
+    }
+
+    /// The events that happen at the start of `frame`
+    pub fn events_at(&self, frame: u64) -> impl Iterator<Item = &InputEvent> {
+        let start = self.events.partition_point(|event| event.frame < frame);
+        self.events[start..].iter().take_while(move |event| event.frame == frame)
+    }
+}
@@ -0,0 +1,87 @@
+//! Battery backed saves in `.sav` files.
+//!
+//! The file holds the cartridge's external RAM byte for byte, followed by the 48 byte clock
+//! block on MBC3+TIMER cartridges. That's the layout BGB, mGBA, SameBoy and VBA-M use, so
+//! saves move freely between them and this emulator.
+
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use crate::cartridge::{ Cartridge, CartridgeError };
+
+/// T-cycles per second of emulated time
+const T_CYCLES_PER_SECOND: u64 = 4_194_304;
+
+pub struct SaveFile {
+    path: PathBuf,
+    /// Emulated T-cycles between flushes, `None` to only flush on shutdown
+    autosave_interval: Option<u64>,
+    /// Emulated T-cycles since the last flush
+    cycles_since_flush: u64,
+}
+
+impl SaveFile {
+    /// A save file at `path`, autosaving every `DEFAULT_AUTOSAVE_INTERVAL` of emulated time
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut save_file = Self { path: path.into(), autosave_interval: None, cycles_since_flush: 0 };
+        save_file.set_autosave_interval(Some(super::DEFAULT_AUTOSAVE_INTERVAL));
+        save_file
+    }
+
+    /// The save file for the ROM at `rom_path`, i.e. the same path with a `.sav` extension
+    pub fn next_to(rom_path: impl AsRef<Path>) -> Self {
+        Self::new(rom_path.as_ref().with_extension("sav"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sets how often unsaved changes get flushed, in emulated time (so fast-forwarding saves
+    /// more often in real time). `None` only flushes on shutdown.
+    pub fn set_autosave_interval(&mut self, interval: Option<Duration>) {
+        self.autosave_interval = interval.map(|interval| {
+            (interval.as_secs_f64() * T_CYCLES_PER_SECOND as f64) as u64
+        });
+    }
+
+    /// Loads the save into `cartridge`. A missing file isn't an error, it's just a game that
+    /// hasn't saved yet, but a file whose size doesn't fit the cartridge is.
+    pub fn load(&self, cartridge: &mut Cartridge) -> Result<(), CartridgeError> {
+        match std::fs::read(&self.path) {
+            Ok(data) => cartridge.load_save_data(&data),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the cartridge's save data if it changed since the last flush
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        self.cycles_since_flush = 0;
+        if !cartridge.has_unsaved_changes() {
+            return Ok(());
+        }
+
+        // write next to the real file and rename over it, so a crash mid-write can't eat the save
+        let temp_path = self.path.with_extension("sav.tmp");
+        std::fs::write(&temp_path, cartridge.save_data())?;
+        std::fs::rename(&temp_path, &self.path)?;
+        cartridge.mark_saved();
+        Ok(())
+    }
+
+    /// Counts `t_cycles` of emulated time towards the autosave interval, flushing when it's up.
+    /// Returns whether it flushed.
+    pub fn tick(&mut self, cartridge: &mut Cartridge, t_cycles: u32) -> io::Result<bool> {
+        let Some(interval) = self.autosave_interval else {
+            return Ok(false);
+        };
+        self.cycles_since_flush += t_cycles as u64;
+        if self.cycles_since_flush < interval {
+            return Ok(false);
+        }
+        self.flush(cartridge)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,383 @@
+use std::path::PathBuf;
+
+use crate::cartridge::with_valid_header;
+use input::InputEvent;
+use super::*;
+
+/// 32 KiB of RAM
+const RAM_SIZE_CODE: u8 = 0x03;
+const RAM_SIZE: usize = 0x8000;
+
+/// A temp directory per test, so tests running in parallel don't trip over each other's saves
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gameboy_emulator_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A ROM that spins on `JP 0x0100` forever, as an MBC1 cartridge of `cartridge_type`
+fn spinning_rom(cartridge_type: u8) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0103].copy_from_slice(&[0xC3, 0x00, 0x01]);
+    with_valid_header(rom, cartridge_type, RAM_SIZE_CODE)
+}
+
+/// Writes the ROM into `dir` and returns its path
+fn write_rom(dir: &std::path::Path, cartridge_type: u8) -> PathBuf {
+    let path = dir.join("game.gb");
+    std::fs::write(&path, spinning_rom(cartridge_type)).unwrap();
+    path
+}
+
+/// What a game enabling RAM and writing `value` to 0xA000 does
+fn write_save_byte(emulator: &mut Emulator, value: u8) {
+    emulator.cpu.bus_mut().write_byte(0x0000, 0x0A);
+    emulator.cpu.bus_mut().write_byte(0xA000, value);
+}
+
+#[test]
+fn loads_the_sav_next_to_the_rom() {
+    let dir = test_dir("loads_sav");
+    // MBC1+RAM+BATTERY
+    let rom_path = write_rom(&dir, 0x03);
+    let mut save = vec![0; RAM_SIZE];
+    save[0] = 0x42;
+    std::fs::write(dir.join("game.sav"), &save).unwrap();
+
+    let mut emulator = Emulator::open(&rom_path).unwrap();
+    emulator.cpu.bus_mut().write_byte(0x0000, 0x0A);
+
+    assert_eq!(0x42, emulator.cpu.bus().read_byte(0xA000));
+    assert_eq!(dir.join("game.sav"), emulator.save_file().unwrap().path());
+    drop(emulator);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_sav_starts_a_fresh_save() {
+    let dir = test_dir("missing_sav");
+    let rom_path = write_rom(&dir, 0x03);
+
+    let emulator = Emulator::open(&rom_path).unwrap();
+    drop(emulator);
+
+    assert!(!dir.join("game.sav").exists(), "nothing was written so nothing is saved");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn wrong_sized_sav_is_rejected() {
+    let dir = test_dir("wrong_sized_sav");
+    let rom_path = write_rom(&dir, 0x03);
+    std::fs::write(dir.join("game.sav"), vec![0; 0x2000]).unwrap();
+
+    let result = Emulator::open(&rom_path);
+
+    assert!(matches!(result, Err(CartridgeError::SaveSizeMismatch { expected: RAM_SIZE, actual: 0x2000 })));
+    // and the save that didn't fit is left alone
+    assert_eq!(0x2000, std::fs::read(dir.join("game.sav")).unwrap().len());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn flushes_on_shutdown() {
+    let dir = test_dir("flush_on_shutdown");
+    let rom_path = write_rom(&dir, 0x03);
+
+    let mut emulator = Emulator::open(&rom_path).unwrap();
+    write_save_byte(&mut emulator, 0x99);
+    drop(emulator);
+
+    let save = std::fs::read(dir.join("game.sav")).unwrap();
+    assert_eq!(RAM_SIZE, save.len());
+    assert_eq!(0x99, save[0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn autosaves_on_the_configured_interval() {
+    let dir = test_dir("autosave");
+    let rom_path = write_rom(&dir, 0x03);
+    let save_path = dir.join("game.sav");
+
+    let mut emulator = Emulator::open(&rom_path).unwrap();
+    // 1 ms is 4194 T-cycles, and each JP takes 16
+    emulator.set_autosave_interval(Some(Duration::from_millis(1)));
+    write_save_byte(&mut emulator, 0x12);
+
+    for _ in 0..200 {
+        emulator.step().unwrap();
+    }
+    assert!(!save_path.exists(), "the interval isn't up yet");
+
+    for _ in 0..100 {
+        emulator.step().unwrap();
+    }
+    assert_eq!(0x12, std::fs::read(&save_path).unwrap()[0]);
+    assert!(!emulator.cartridge().has_unsaved_changes());
+
+    // no changes, no writes
+    std::fs::remove_file(&save_path).unwrap();
+    for _ in 0..300 {
+        emulator.step().unwrap();
+    }
+    assert!(!save_path.exists());
+
+    emulator.set_autosave_interval(None);
+    write_save_byte(&mut emulator, 0x34);
+    for _ in 0..1000 {
+        emulator.step().unwrap();
+    }
+    assert!(!save_path.exists(), "autosave is off");
+
+    emulator.flush_save().unwrap();
+    assert_eq!(0x34, std::fs::read(&save_path).unwrap()[0]);
+    drop(emulator);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_autosaves_are_kept_for_the_host() {
+    let dir = test_dir("autosave_error");
+    let rom_path = write_rom(&dir, 0x03);
+    let mut emulator = Emulator::open(&rom_path).unwrap();
+    emulator.set_autosave_interval(Some(Duration::from_millis(1)));
+    write_save_byte(&mut emulator, 0x12);
+
+    // nowhere to write it
+    std::fs::remove_dir_all(&dir).unwrap();
+    for _ in 0..300 {
+        emulator.step().unwrap();
+    }
+    assert!(emulator.last_save_error().is_some());
+    assert!(emulator.cartridge().has_unsaved_changes());
+
+    std::fs::create_dir_all(&dir).unwrap();
+    for _ in 0..300 {
+        emulator.step().unwrap();
+    }
+    assert!(emulator.last_save_error().is_none());
+    assert_eq!(0x12, std::fs::read(dir.join("game.sav")).unwrap()[0]);
+
+    drop(emulator);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cartridges_without_a_battery_dont_save() {
+    let dir = test_dir("no_battery");
+    // MBC1+RAM
+    let rom_path = write_rom(&dir, 0x02);
+
+    let mut emulator = Emulator::open(&rom_path).unwrap();
+    write_save_byte(&mut emulator, 0x56);
+    drop(emulator);
+
+    assert!(!dir.join("game.sav").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn input_script_keeps_events_in_frame_order() {
+    let mut script = InputScript::new();
+    script.tap(3, Button::A, 2).press(1, Button::Start).release(3, Button::Start);
+
+    let at = |frame| script.events_at(frame).copied().collect::<Vec<_>>();
+    assert_eq!(vec![InputEvent { frame: 1, button: Button::Start, pressed: true }], at(1));
+    assert_eq!(
+        vec![
+            InputEvent { frame: 3, button: Button::A, pressed: true },
+            InputEvent { frame: 3, button: Button::Start, pressed: false },
+        ],
+        at(3),
+    );
+    assert_eq!(vec![InputEvent { frame: 5, button: Button::A, pressed: false }], at(5));
+    assert!(at(2).is_empty());
+}
+
+#[test]
+fn scripted_buttons_reach_p1_on_their_frames() {
+    let rom = spinning_rom(0x01);
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&rom).unwrap());
+    // select the action row, like a game polling A/B/Select/Start
+    emulator.cpu.bus_mut().write_byte(0xFF00, 0x10);
+    let mut script = InputScript::new();
+    script.tap(2, Button::A, 3);
+
+    emulator.run_frames_with_input(2, &script).unwrap();
+    assert_eq!(2, emulator.frame_count());
+    assert_eq!(0xDF, emulator.cpu.bus().read_byte(0xFF00));
+
+    emulator.run_frames_with_input(1, &script).unwrap();
+    assert!(emulator.is_pressed(Button::A));
+    assert_eq!(0xDE, emulator.cpu.bus().read_byte(0xFF00));
+    assert_ne!(0, emulator.cpu.bus().read_byte(0xFF0F) & 0x10, "the press requests the joypad interrupt");
+
+    emulator.run_frames_with_input(3, &script).unwrap();
+    assert!(!emulator.is_pressed(Button::A));
+    assert_eq!(0xDF, emulator.cpu.bus().read_byte(0xFF00));
+}
+
+#[test]
+fn audio_can_be_pulled_and_hashed_headless() {
+    use std::hash::{ DefaultHasher, Hash, Hasher };
+
+    let audio_hash = || {
+        let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+        emulator.set_sample_rate(Some(44_100));
+        // a beep on channel 2, like a game would start one
+        for (address, value) in [(0xFF16, 0x80), (0xFF17, 0xF3), (0xFF18, 0x00), (0xFF19, 0x87)] {
+            emulator.cpu.bus_mut().write_byte(address, value);
+        }
+        emulator.run_frames_with_input(5, &InputScript::new()).unwrap();
+
+        let samples = emulator.take_samples();
+        assert!(samples.iter().any(|&sample| sample != 0), "the beep should be audible");
+        let mut hasher = DefaultHasher::new();
+        samples.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    assert_eq!(audio_hash(), audio_hash());
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn wav_header_describes_16_bit_stereo_pcm() {
+    let mut wav = WavWriter::new(std::io::Cursor::new(Vec::new()), 32_000).unwrap();
+    // the odd sample out is half a frame and gets dropped
+    wav.write_samples(&[1, -1, 2, -2, 3]).unwrap();
+    let bytes = wav.finish().unwrap().into_inner();
+
+    assert_eq!(44 + 8, bytes.len());
+    assert_eq!(b"RIFF", &bytes[0..4]);
+    assert_eq!(36 + 8, le_u32(&bytes, 4));
+    assert_eq!(b"WAVEfmt ", &bytes[8..16]);
+    assert_eq!(16, le_u32(&bytes, 16));
+    // PCM, 2 channels
+    assert_eq!([1, 0, 2, 0], bytes[20..24]);
+    assert_eq!(32_000, le_u32(&bytes, 24));
+    assert_eq!(32_000 * 4, le_u32(&bytes, 28));
+    // 4 bytes per frame, 16 bits per sample
+    assert_eq!([4, 0, 16, 0], bytes[32..36]);
+    assert_eq!(b"data", &bytes[36..40]);
+    assert_eq!(8, le_u32(&bytes, 40));
+    assert_eq!([1, 0, 0xFF, 0xFF, 2, 0, 0xFE, 0xFF], bytes[44..]);
+}
+
+#[test]
+fn recording_is_a_valid_wav_after_every_frame() {
+    let dir = test_dir("recording");
+    let wav_path = dir.join("session.wav");
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    // a host that plays the audio back as well
+    assert!(emulator.take_samples().is_empty());
+    emulator.start_recording(&wav_path).unwrap();
+    assert!(emulator.is_recording());
+
+    emulator.run_frame().unwrap();
+    emulator.run_frame().unwrap();
+    // the emulator is still going, like when it crashes mid-session
+    let bytes = std::fs::read(&wav_path).unwrap();
+    assert_eq!(bytes.len() as u32 - 8, le_u32(&bytes, 4));
+    assert_eq!(bytes.len() as u32 - 44, le_u32(&bytes, 40));
+    assert_eq!(DEFAULT_SAMPLE_RATE, le_u32(&bytes, 24));
+
+    // the host taking samples for playback doesn't take them from the recording
+    let taken = emulator.take_samples();
+    emulator.stop_recording().unwrap();
+    assert!(!emulator.is_recording());
+    emulator.run_frame().unwrap();
+
+    let bytes = std::fs::read(&wav_path).unwrap();
+    let recorded: Vec<i16> = bytes[44..].chunks(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+    assert_eq!(taken, recorded, "nothing gets recorded after stopping");
+    assert_eq!(recorded.len() as u32 * 2, le_u32(&bytes, 40));
+    drop(emulator);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn recordings_cut_short_say_why_when_stopped() {
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    // every write to /dev/full fails with the disk being full
+    emulator.start_recording("/dev/full").unwrap();
+    emulator.run_frame().unwrap();
+    assert!(!emulator.is_recording());
+
+    assert!(emulator.stop_recording().is_err());
+    // and only the once
+    emulator.stop_recording().unwrap();
+}
+
+#[test]
+fn recorded_samples_dont_pile_up_when_nobody_takes_them() {
+    let dir = test_dir("recording_only");
+    let wav_path = dir.join("session.wav");
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    emulator.start_recording(&wav_path).unwrap();
+
+    emulator.run_frame().unwrap();
+    emulator.run_frame().unwrap();
+    assert!(emulator.cpu.bus().apu.samples().is_empty());
+    emulator.stop_recording().unwrap();
+    // everything still made it in: about two frames (1/30 of a second) at 44.1 kHz, 4 bytes a
+    // stereo sample
+    let data_size = le_u32(&std::fs::read(&wav_path).unwrap(), 40);
+    assert!((1400..1500).contains(&(data_size / 4)), "{data_size}");
+    drop(emulator);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn samples_the_host_fell_behind_on_are_dropped() {
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    // a low rate so a second's worth doesn't take long to fill up
+    emulator.set_sample_rate(Some(1000));
+    assert!(emulator.take_samples().is_empty());
+
+    // a bit over 2 seconds
+    for _ in 0..125 {
+        emulator.run_frame().unwrap();
+    }
+    let buffered = emulator.take_samples().len();
+    assert!((2000..=4000).contains(&buffered), "{buffered}");
+}
+
+#[test]
+fn serial_output_reaches_the_endpoint() {
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    let capture = CaptureEndpoint::new();
+    emulator.set_serial_endpoint(capture.clone());
+
+    // what Blargg's test ROMs do for every character they print
+    emulator.cpu.bus_mut().write_byte(0xFF01, b'O');
+    emulator.cpu.bus_mut().write_byte(0xFF02, 0x81);
+    emulator.run_frame().unwrap();
+
+    assert_eq!("O", capture.text());
+    assert_ne!(0, emulator.cpu.bus().read_byte(0xFF0F) & 0x08, "the transfer finished with an interrupt");
+}
+
+#[test]
+fn traces_to_a_file() {
+    let dir = test_dir("trace");
+    let trace_path = dir.join("trace.log");
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&spinning_rom(0x01)).unwrap());
+    emulator.start_trace(&trace_path, TraceFilter { limit: Some(2), ..TraceFilter::default() }).unwrap();
+    // unlike a recording, the trace doesn't care about the sample rate
+    emulator.set_sample_rate(Some(22_050));
+    assert!(emulator.cpu().tracer().is_some());
+    emulator.run_frame().unwrap();
+    emulator.stop_trace().unwrap();
+
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    let line = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:C3,00,01,00\n";
+    assert_eq!(line.repeat(2), trace);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
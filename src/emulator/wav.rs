@@ -0,0 +1,100 @@
+//! Recording audio to 16-bit stereo PCM `.wav` files.
+//!
+//! The header has the sizes of the data in it, which aren't known until the recording ends. So
+//! they get patched in every time the recording is flushed (once a frame), and a recording cut
+//! short by a crash is still a valid file up to the last flush.
+
+use std::fs::File;
+use std::io::{ self, BufWriter, Seek, SeekFrom, Write };
+use std::path::Path;
+
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+const BYTES_PER_FRAME: u32 = CHANNELS as u32 * BITS_PER_SAMPLE as u32 / 8;
+/// The PCM format tag in the fmt chunk
+const FORMAT_PCM: u16 = 1;
+/// "RIFF" size "WAVE", the 16 byte fmt chunk with its 8 byte chunk header, then the data chunk's
+const HEADER_SIZE: u32 = 12 + 8 + 16 + 8;
+/// Where the RIFF size and data size go
+const RIFF_SIZE_OFFSET: u64 = 4;
+const DATA_SIZE_OFFSET: u64 = HEADER_SIZE as u64 - 4;
+
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    /// Bytes of samples written so far
+    data_size: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path` and starts recording into it
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(writer: W, sample_rate: u32) -> io::Result<Self> {
+        let mut wav = Self { writer, sample_rate, data_size: 0 };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        header.extend_from_slice(&CHANNELS.to_le_bytes());
+        header.extend_from_slice(&self.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(self.sample_rate * BYTES_PER_FRAME).to_le_bytes());
+        header.extend_from_slice(&(BYTES_PER_FRAME as u16).to_le_bytes());
+        header.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.data_size.to_le_bytes());
+        self.writer.write_all(&header)
+    }
+
+    /// Appends interleaved left/right samples. An odd sample out (half a frame) is dropped, and
+    /// so is anything past the 4 GiB a WAV file can hold.
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let frames = samples.len() / CHANNELS as usize;
+        let room = (u32::MAX - HEADER_SIZE - self.data_size) / BYTES_PER_FRAME;
+        let frames = frames.min(room as usize);
+
+        let mut bytes = Vec::with_capacity(frames * BYTES_PER_FRAME as usize);
+        for sample in &samples[..frames * CHANNELS as usize] {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.writer.write_all(&bytes)?;
+        self.data_size += bytes.len() as u32;
+        Ok(())
+    }
+
+    /// Makes everything written so far a complete file: patches the sizes into the header and
+    /// flushes the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        let riff_size = HEADER_SIZE - 8 + self.data_size;
+        self.writer.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.writer.write_all(&riff_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    /// Flushes one last time and hands the writer back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
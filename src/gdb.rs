@@ -0,0 +1,534 @@
+//! A stub for GDB's remote serial protocol, so gdb (or anything else speaking RSP) can debug a
+//! ROM over a local TCP port: `gameboy_emulator gdb <ROM>`, then `target remote :2345` in gdb.
+//!
+//! Packets are `$<data>#<checksum>`, the checksum being the sum of the data bytes mod 256 in
+//! hex, and each one gets a `+` back (or `-` to ask for it again) until gdb turns that off with
+//! `QStartNoAckMode`. A lone 0x03 byte interrupts a `c`. What's supported:
+//! ```text
+//! ?                         why it stopped (always S05 at the start)
+//! g / G<REGS>               read / write all the registers
+//! p<N> / P<N>=<VALUE>       read / write register N
+//! m<ADDR>,<LEN>             read memory, through the bus without side effects
+//! M<ADDR>,<LEN>:<HEX>       write memory, the same way
+//! X<ADDR>,<LEN>:<BINARY>    write memory, with the data escaped instead of in hex
+//! Z0,<ADDR>,<KIND> / z0,... set / remove a software breakpoint
+//! s / c [ADDR]              single-step / continue (from ADDR if given)
+//! D / k                     detach / kill, which both end the session
+//! ```
+//! plus the `q` queries gdb needs to get going, and an empty reply (meaning "not supported") to
+//! anything else. Stop replies are `S05` (SIGTRAP) after a step or at a breakpoint, `S02`
+//! (SIGINT) after an interrupt, and `S04` (SIGILL) when the CPU hit an illegal opcode.
+//!
+//! There's no SM83 in gdb, so the register layout comes from `target.xml` (see
+//! [`TARGET_XML`]): A, F, B, C, D, E, H and L as bytes then SP and PC as little endian words,
+//! numbered 0 to 9 in that order. Like the debugger's, breakpoints are checked between steps
+//! rather than patched into memory, since most of it is ROM anyway.
+
+#[cfg(test)]
+mod tests;
+
+use std::io::{ self, ErrorKind, Read, Write };
+use std::net::{ TcpListener, TcpStream, ToSocketAddrs };
+
+use crate::emulator::Emulator;
+
+/// Where `gameboy_emulator gdb` listens unless told otherwise, the port gdbserver defaults to
+pub const DEFAULT_PORT: u16 = 2345;
+
+/// The register set gdb is told about in `qXfer:features:read`
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gameboy.sm83.core">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="f" bitsize="8" type="uint8"/>
+    <reg name="b" bitsize="8" type="uint8"/>
+    <reg name="c" bitsize="8" type="uint8"/>
+    <reg name="d" bitsize="8" type="uint8"/>
+    <reg name="e" bitsize="8" type="uint8"/>
+    <reg name="h" bitsize="8" type="uint8"/>
+    <reg name="l" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// How many bytes each register takes in `g`, by register number
+const REGISTER_SIZES: [usize; 10] = [1, 1, 1, 1, 1, 1, 1, 1, 2, 2];
+/// The byte gdb sends to interrupt a continue
+const INTERRUPT: u8 = 0x03;
+/// Steps between looking for an interrupt while continuing, a syscall each time is slow
+const INTERRUPT_POLL_STEPS: u32 = 1024;
+/// Advertised in `qSupported`, in hex as the protocol wants it
+const PACKET_SIZE: usize = 0x4000;
+
+/// The errno in error replies for anything malformed
+const EINVAL: u8 = 22;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// What handling a packet means for the session
+enum Flow {
+    Reply(String),
+    /// Reply with this, then the session is over
+    Detach(String),
+    /// Over without a reply
+    Kill,
+}
+
+pub struct GdbStub {
+    emulator: Emulator,
+    breakpoints: Vec<u16>,
+}
+
+impl GdbStub {
+    pub fn new(emulator: Emulator) -> Self {
+        Self { emulator, breakpoints: Vec::new() }
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    /// Waits for gdb to connect at `address`, then serves it until it detaches
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    /// Serves one gdb session over `stream`, until gdb detaches, kills the target or hangs up.
+    /// Breakpoints stay set between sessions.
+    pub fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut connection = Connection::new(stream)?;
+        while let Some(packet) = connection.read_packet()? {
+            match self.handle(&packet, &mut connection)? {
+                Flow::Reply(reply) => connection.send(&reply)?,
+                Flow::Detach(reply) => return connection.send(&reply),
+                Flow::Kill => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, packet: &[u8], connection: &mut Connection) -> io::Result<Flow> {
+        let Some((&command, arguments)) = packet.split_first() else {
+            return Ok(Flow::Reply(String::new()));
+        };
+        // the packets with binary data are the only ones that aren't text
+        if command == b'X' {
+            return Ok(Flow::Reply(self.write_binary(arguments).unwrap_or_else(error)));
+        }
+        let arguments = String::from_utf8_lossy(arguments);
+
+        let reply = match command {
+            b'?' => stop_reply(SIGTRAP),
+            b'g' => self.read_registers(),
+            b'G' => self.write_registers(&arguments).unwrap_or_else(error),
+            b'p' => self.read_register(&arguments).unwrap_or_else(error),
+            b'P' => self.write_register(&arguments).unwrap_or_else(error),
+            b'm' => self.read_memory(&arguments).unwrap_or_else(error),
+            b'M' => self.write_memory(&arguments).unwrap_or_else(error),
+            b'Z' | b'z' => self.breakpoint(command == b'Z', &arguments).unwrap_or_else(error),
+            b's' | b'c' => {
+                if let Err(reply) = self.resume_at(&arguments) {
+                    return Ok(Flow::Reply(reply));
+                }
+                let signal = if command == b's' { self.step() } else { self.cont(connection)? };
+                stop_reply(signal)
+            }
+            // there's only one thread, whichever gdb picks is fine
+            b'H' => "OK".to_string(),
+            b'D' => return Ok(Flow::Detach("OK".to_string())),
+            b'k' => return Ok(Flow::Kill),
+            b'q' | b'Q' => self.query(command, &arguments, connection),
+            _ => String::new(),
+        };
+        Ok(Flow::Reply(reply))
+    }
+
+    fn query(&mut self, command: u8, query: &str, connection: &mut Connection) -> String {
+        if command == b'Q' {
+            if query == "StartNoAckMode" {
+                connection.acknowledge = false;
+                return "OK".to_string();
+            }
+            return String::new();
+        }
+
+        if query.starts_with("Supported") {
+            return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+");
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            return read_chunk(TARGET_XML, range).unwrap_or_else(error);
+        }
+        match query {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// The registers in `g` order
+    fn registers(&self) -> [u16; 10] {
+        let cpu = self.emulator.cpu();
+        let registers = cpu.registers();
+        [
+            registers.a as u16, u8::from(registers.f) as u16, registers.b as u16, registers.c as u16,
+            registers.d as u16, registers.e as u16, registers.h as u16, registers.l as u16,
+            cpu.sp(), cpu.pc(),
+        ]
+    }
+
+    fn set_register(&mut self, number: usize, value: u16) {
+        let cpu = self.emulator.cpu_mut();
+        let registers = cpu.registers_mut();
+        match number {
+            0 => registers.a = value as u8,
+            1 => registers.f = (value as u8).into(),
+            2 => registers.b = value as u8,
+            3 => registers.c = value as u8,
+            4 => registers.d = value as u8,
+            5 => registers.e = value as u8,
+            6 => registers.h = value as u8,
+            7 => registers.l = value as u8,
+            8 => cpu.set_sp(value),
+            9 => cpu.set_pc(value),
+            _ => unreachable!("register numbers are checked against REGISTER_SIZES"),
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        self.registers().iter().zip(REGISTER_SIZES).map(|(&value, size)| encode_register(value, size)).collect()
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Result<String, u8> {
+        let bytes = decode_hex(hex)?;
+        if bytes.len() != REGISTER_SIZES.iter().sum::<usize>() {
+            return Err(EINVAL);
+        }
+        let mut rest = bytes.as_slice();
+        for (number, size) in REGISTER_SIZES.into_iter().enumerate() {
+            let (value, after) = rest.split_at(size);
+            self.set_register(number, decode_register(value));
+            rest = after;
+        }
+        Ok("OK".to_string())
+    }
+
+    fn read_register(&self, number: &str) -> Result<String, u8> {
+        let number = parse_register_number(number)?;
+        Ok(encode_register(self.registers()[number], REGISTER_SIZES[number]))
+    }
+
+    fn write_register(&mut self, arguments: &str) -> Result<String, u8> {
+        let (number, value) = arguments.split_once('=').ok_or(EINVAL)?;
+        let number = parse_register_number(number)?;
+        let value = decode_hex(value)?;
+        if value.len() != REGISTER_SIZES[number] {
+            return Err(EINVAL);
+        }
+        self.set_register(number, decode_register(&value));
+        Ok("OK".to_string())
+    }
+
+    fn read_memory(&self, arguments: &str) -> Result<String, u8> {
+        let (address, length) = parse_address_length(arguments)?;
+        Ok((0..length).map(|offset| format!("{:02x}", self.emulator.read_byte(address.wrapping_add(offset)))).collect())
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> Result<String, u8> {
+        let (range, hex) = arguments.split_once(':').ok_or(EINVAL)?;
+        let (address, length) = parse_address_length(range)?;
+        self.poke(address, length, &decode_hex(hex)?)
+    }
+
+    fn write_binary(&mut self, arguments: &[u8]) -> Result<String, u8> {
+        let colon = arguments.iter().position(|&byte| byte == b':').ok_or(EINVAL)?;
+        let range = std::str::from_utf8(&arguments[..colon]).map_err(|_| EINVAL)?;
+        let (address, length) = parse_address_length(range)?;
+        self.poke(address, length, &unescape(&arguments[colon + 1..]))
+    }
+
+    fn poke(&mut self, address: u16, length: u16, data: &[u8]) -> Result<String, u8> {
+        if data.len() != length as usize {
+            return Err(EINVAL);
+        }
+        for (offset, &byte) in data.iter().enumerate() {
+            self.emulator.write_byte(address.wrapping_add(offset as u16), byte);
+        }
+        Ok("OK".to_string())
+    }
+
+    fn breakpoint(&mut self, insert: bool, arguments: &str) -> Result<String, u8> {
+        let mut fields = arguments.split(',');
+        // only software breakpoints, an empty reply tells gdb the other kinds aren't there
+        if fields.next() != Some("0") {
+            return Ok(String::new());
+        }
+        let address = fields.next().ok_or(EINVAL).and_then(parse_address)?;
+
+        if insert {
+            if !self.breakpoints.contains(&address) {
+                self.breakpoints.push(address);
+            }
+        } else {
+            self.breakpoints.retain(|&breakpoint| breakpoint != address);
+        }
+        Ok("OK".to_string())
+    }
+
+    /// Moves PC to the address a `s` or `c` came with, if any
+    fn resume_at(&mut self, address: &str) -> Result<(), String> {
+        if !address.is_empty() {
+            let address = parse_address(address).map_err(error)?;
+            self.emulator.cpu_mut().set_pc(address);
+        }
+        Ok(())
+    }
+
+    /// Runs one instruction, and returns the signal to stop with
+    fn step(&mut self) -> u8 {
+        match self.emulator.step() {
+            Ok(_) if self.emulator.cpu().is_locked() => SIGILL,
+            Ok(_) => SIGTRAP,
+            Err(_) => SIGILL,
+        }
+    }
+
+    /// Runs until a breakpoint or an interrupt from gdb, and returns the signal to stop with. It
+    /// always runs at least one instruction, so continuing from a breakpoint gets past it.
+    fn cont(&mut self, connection: &mut Connection) -> io::Result<u8> {
+        let mut steps = 0;
+        loop {
+            let signal = self.step();
+            if signal != SIGTRAP {
+                return Ok(signal);
+            }
+            if self.breakpoints.contains(&self.emulator.cpu().pc()) {
+                return Ok(SIGTRAP);
+            }
+
+            steps += 1;
+            if steps % INTERRUPT_POLL_STEPS == 0 && connection.interrupted()? {
+                return Ok(SIGINT);
+            }
+        }
+    }
+}
+
+/// One gdb connection, and the bytes read from it but not handled yet
+struct Connection {
+    stream: TcpStream,
+    pending: Vec<u8>,
+    /// Whether packets still get a `+`, until `QStartNoAckMode`
+    acknowledge: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        // packets are small and each one waits on the other side, Nagle would hold every one back
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, pending: Vec::new(), acknowledge: true })
+    }
+
+    /// The next byte, `None` once gdb hung up
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.pending.is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+        Ok(Some(self.pending.remove(0)))
+    }
+
+    /// Reads whatever's there (waiting for something in blocking mode), false when the
+    /// connection was closed
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Ok(false),
+                Ok(read) => {
+                    self.pending.extend_from_slice(&buffer[..read]);
+                    return Ok(true);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// The data of the next packet with a good checksum, `None` once gdb hung up. Acks from gdb
+    /// and interrupts while nothing's running are skipped.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // anything before the start of a packet is an ack, or an interrupt that came late
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+
+            let expected = std::str::from_utf8(&[high, low]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            let good = expected == Some(checksum(&data));
+            if self.acknowledge {
+                self.stream.write_all(if good { b"+" } else { b"-" })?;
+            }
+            if good || !self.acknowledge {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let data = escape(data.as_bytes());
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(&data);
+        packet.extend_from_slice(format!("#{:02x}", checksum(&data)).as_bytes());
+        self.stream.write_all(&packet)?;
+
+        // gdb asks for it again with a -, it's fine to give up on anything else
+        while self.acknowledge {
+            match self.read_byte()? {
+                Some(b'-') => self.stream.write_all(&packet)?,
+                Some(b'+') | None => break,
+                Some(byte) => {
+                    self.pending.insert(0, byte);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether gdb sent an interrupt, without waiting for one. A closed connection counts too,
+    /// so a continue doesn't run forever after gdb's gone.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if !self.pending.contains(&INTERRUPT) {
+            self.stream.set_nonblocking(true)?;
+            let filled = self.fill();
+            self.stream.set_nonblocking(false)?;
+            match filled {
+                Ok(false) => return Ok(true),
+                Ok(true) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+        match self.pending.iter().position(|&byte| byte == INTERRUPT) {
+            Some(index) => {
+                self.pending.remove(index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+fn error(errno: u8) -> String {
+    format!("E{errno:02x}")
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{signal:02x}")
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Escapes the bytes that mean something in a packet: `}` then the byte XOR 0x20
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            escaped.extend_from_slice(&[b'}', byte ^ 0x20]);
+        } else {
+            escaped.push(byte);
+        }
+    }
+    escaped
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        match (byte, bytes.as_slice().first()) {
+            (b'}', Some(&next)) => {
+                unescaped.push(next ^ 0x20);
+                bytes.next();
+            }
+            _ => unescaped.push(byte),
+        }
+    }
+    unescaped
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, u8> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(EINVAL);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| hex.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()).ok_or(EINVAL))
+        .collect()
+}
+
+/// A register's value as `size` little endian bytes in hex
+fn encode_register(value: u16, size: usize) -> String {
+    value.to_le_bytes()[..size].iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_register(bytes: &[u8]) -> u16 {
+    bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u16)
+}
+
+fn parse_register_number(number: &str) -> Result<usize, u8> {
+    usize::from_str_radix(number, 16).ok().filter(|&number| number < REGISTER_SIZES.len()).ok_or(EINVAL)
+}
+
+fn parse_address(address: &str) -> Result<u16, u8> {
+    u16::from_str_radix(address, 16).map_err(|_| EINVAL)
+}
+
+/// `ADDR,LEN` in hex, as `m` and friends take it
+fn parse_address_length(arguments: &str) -> Result<(u16, u16), u8> {
+    let (address, length) = arguments.split_once(',').ok_or(EINVAL)?;
+    let length = u16::from_str_radix(length, 16).map_err(|_| EINVAL)?;
+    Ok((parse_address(address)?, length))
+}
+
+/// The `OFFSET,LENGTH` chunk of `document` a `qXfer` read asks for: `m` and the chunk while
+/// there's more after it, `l` and the chunk for the last one
+fn read_chunk(document: &str, range: &str) -> Result<String, u8> {
+    let (offset, length) = range.split_once(',').ok_or(EINVAL)?;
+    let offset = usize::from_str_radix(offset, 16).map_err(|_| EINVAL)?.min(document.len());
+    let length = usize::from_str_radix(length, 16).map_err(|_| EINVAL)?;
+    let end = offset.saturating_add(length).min(document.len());
+    let marker = if end < document.len() { 'm' } else { 'l' };
+    Ok(format!("{marker}{}", &document[offset..end]))
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+#[test]
+fn checksums_are_the_sum_mod_256() {
+    assert_eq!(0x00, checksum(b""));
+    assert_eq!(0x3F, checksum(b"?"));
+    // 'g' + 'g' + 'g' overflows
+    assert_eq!((3 * b'g' as u32 % 256) as u8, checksum(b"ggg"));
+}
+
+#[test]
+fn escaping_round_trips() {
+    let data = b"a$b#c}d*e";
+    let escaped = escape(data);
+    assert_eq!(b"a}\x04b}\x03c}]d}\x0ae".to_vec(), escaped);
+    assert_eq!(data.to_vec(), unescape(&escaped));
+}
+
+#[test]
+fn registers_are_little_endian() {
+    assert_eq!("34", encode_register(0x1234, 1));
+    assert_eq!("3412", encode_register(0x1234, 2));
+    assert_eq!(0x1234, decode_register(&[0x34, 0x12]));
+    assert_eq!(Ok(vec![0x34, 0x12]), decode_hex("3412"));
+    assert_eq!(Err(EINVAL), decode_hex("341"));
+    assert_eq!(Err(EINVAL), decode_hex("zz"));
+}
+
+#[test]
+fn register_numbers_stop_at_pc() {
+    assert_eq!(Ok(9), parse_register_number("9"));
+    assert_eq!(Err(EINVAL), parse_register_number("a"));
+}
+
+#[test]
+fn reads_documents_in_chunks() {
+    assert_eq!(Ok("mabc".to_string()), read_chunk("abcdef", "0,3"));
+    assert_eq!(Ok("ldef".to_string()), read_chunk("abcdef", "3,10"));
+    assert_eq!(Ok("l".to_string()), read_chunk("abcdef", "10,10"));
+    assert_eq!(Err(EINVAL), read_chunk("abcdef", "0"));
+}
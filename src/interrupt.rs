@@ -0,0 +1,106 @@
+//! The interrupt controller, i.e. the IE (0xFFFF) and IF (0xFF0F) registers.
+//!
+//! This is the one shared API every peripheral uses to raise an interrupt: whoever owns
+//! the [`InterruptController`] (the memory bus) hands a `&mut` of it to a peripheral when
+//! clocking it and the peripheral calls [`InterruptController::request`].
+//!
+//! The interrupt master enable (IME) flag is NOT here, it is internal CPU state and
+//! not memory mapped, so it lives on the CPU which is also the one that dispatches.
+
+#[cfg(test)]
+mod tests;
+
+/// Address of the interrupt enable (IE) register
+pub const IE_ADDRESS: u16 = 0xFFFF;
+/// Address of the interrupt flag (IF) register
+pub const IF_ADDRESS: u16 = 0xFF0F;
+
+/// The five interrupt sources, where the numerical value of each variant is its bit
+/// position in IE/IF. Lower bits have higher priority.
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum Interrupt {
+    VBlank = 0,
+    LcdStat = 1,
+    Timer = 2,
+    Serial = 3,
+    Joypad = 4,
+}
+
+impl Interrupt {
+    /// All interrupts ordered from highest to lowest priority
+    pub const BY_PRIORITY: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// The bit of this interrupt inside of IE/IF
+    pub fn mask(self) -> u8 {
+        0b1 << self as u8
+    }
+
+    /// The address the CPU calls when dispatching this interrupt (0x40, 0x48, 0x50, 0x58, 0x60)
+    pub fn vector(self) -> u16 {
+        0x40 + 0x08 * self as u16
+    }
+}
+
+/// Only the lower 5 bits of IE/IF are wired to an interrupt source
+const INTERRUPT_BITS: u8 = 0b0001_1111;
+
+pub struct InterruptController {
+    /// IE -- which interrupts are allowed to be dispatched
+    enable: u8,
+    /// IF -- which interrupts have been requested
+    flag: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self { enable: 0x00, flag: 0x00 }
+    }
+
+    /// Raises the IF bit of the given interrupt
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flag |= interrupt.mask();
+    }
+
+    /// Clears the IF bit of the given interrupt (done by the CPU when it dispatches it)
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flag &= !interrupt.mask();
+    }
+
+    /// The highest priority interrupt that is both requested and enabled, if any
+    pub fn highest_pending(&self) -> Option<Interrupt> {
+        let pending = self.enable & self.flag & INTERRUPT_BITS;
+        Interrupt::BY_PRIORITY
+            .into_iter()
+            .find(|interrupt| pending & interrupt.mask() != 0)
+    }
+
+    /// Whether any interrupt is both requested and enabled, regardless of IME
+    pub fn has_pending(&self) -> bool {
+        self.enable & self.flag & INTERRUPT_BITS != 0
+    }
+
+    pub fn read_enable(&self) -> u8 {
+        self.enable
+    }
+
+    /// IE is a full 8-bit register even though only the lower 5 bits do anything
+    pub fn write_enable(&mut self, value: u8) {
+        self.enable = value;
+    }
+
+    /// The upper 3 bits of IF are unused and always read back as 1
+    pub fn read_flag(&self) -> u8 {
+        self.flag | !INTERRUPT_BITS
+    }
+
+    pub fn write_flag(&mut self, value: u8) {
+        self.flag = value & INTERRUPT_BITS;
+    }
+}
@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn vectors_and_masks() {
+    let expected = [(0x40, 0b0_0001), (0x48, 0b0_0010), (0x50, 0b0_0100), (0x58, 0b0_1000), (0x60, 0b1_0000)];
+
+    for (interrupt, (vector, mask)) in std::iter::zip(Interrupt::BY_PRIORITY, expected) {
+        assert_eq!(vector, interrupt.vector(), "{interrupt:?} has the wrong vector");
+        assert_eq!(mask, interrupt.mask(), "{interrupt:?} has the wrong mask");
+    }
+}
+
+#[test]
+fn pending_requires_enable_and_flag() {
+    let mut controller = InterruptController::new();
+
+    controller.request(Interrupt::Timer);
+    assert!(!controller.has_pending(), "a requested but disabled interrupt is not pending");
+    assert_eq!(None, controller.highest_pending());
+
+    controller.write_enable(Interrupt::Timer.mask());
+    assert!(controller.has_pending());
+    assert_eq!(Some(Interrupt::Timer), controller.highest_pending());
+
+    controller.acknowledge(Interrupt::Timer);
+    assert!(!controller.has_pending());
+}
+
+#[test]
+fn highest_pending_follows_priority() {
+    let mut controller = InterruptController::new();
+    controller.write_enable(0xFF);
+
+    controller.request(Interrupt::Joypad);
+    controller.request(Interrupt::Serial);
+    controller.request(Interrupt::LcdStat);
+    assert_eq!(Some(Interrupt::LcdStat), controller.highest_pending());
+
+    controller.request(Interrupt::VBlank);
+    assert_eq!(Some(Interrupt::VBlank), controller.highest_pending());
+
+    controller.acknowledge(Interrupt::VBlank);
+    controller.acknowledge(Interrupt::LcdStat);
+    assert_eq!(Some(Interrupt::Serial), controller.highest_pending());
+}
+
+#[test]
+fn flag_register_unused_bits_read_high() {
+    let mut controller = InterruptController::new();
+    assert_eq!(0xE0, controller.read_flag());
+
+    controller.write_flag(0xFF);
+    assert_eq!(0xFF, controller.read_flag());
+    controller.write_flag(0x04);
+    assert_eq!(0xE4, controller.read_flag());
+
+    controller.write_enable(0xFF);
+    assert_eq!(0xFF, controller.read_enable());
+}
@@ -0,0 +1,114 @@
+//! The joypad, i.e. the P1 register (0xFF00) and the buttons a frontend presses.
+//!
+//! The 8 buttons are wired as a 2x4 matrix, and the game picks which row P1 reads:
+//! ```text
+//! bit 5 | 0 selects the action buttons:    Start, Select, B, A  (bits 3-0)
+//! bit 4 | 0 selects the direction buttons: Down, Up, Left, Right (bits 3-0)
+//! ```
+//! Everything is active low: a pressed button reads 0, and so does a select bit picking its row.
+//! With both rows selected a bit is 0 if either button on it is pressed.
+//!
+//! The joypad interrupt fires whenever one of bits 3-0 goes from 1 to 0, be it from a press or
+//! from the game selecting a row with a button already held.
+
+#[cfg(test)]
+mod tests;
+
+use crate::interrupt::{ Interrupt, InterruptController };
+
+pub const P1_ADDRESS: u16 = 0xFF00;
+
+const SELECT_ACTION: u8 = 1 << 5;
+const SELECT_DIRECTION: u8 = 1 << 4;
+const SELECT_BITS: u8 = SELECT_ACTION | SELECT_DIRECTION;
+const BUTTON_BITS: u8 = 0b0000_1111;
+/// Bits 7-6 aren't wired to anything and read 1
+const UNUSED_BITS: u8 = 0b1100_0000;
+
+/// A button on the Game Boy
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+
+    /// The bit of this button in `Joypad::pressed`: directions in the lower nibble, actions
+    /// in the upper one, each in the order P1 has them
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+pub struct Joypad {
+    /// The select bits (4-5) of the last P1 write
+    select: u8,
+    /// One bit per held button, see `Button::mask`
+    pressed: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self { select: SELECT_BITS, pressed: 0 }
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.mask() != 0
+    }
+
+    pub fn press(&mut self, button: Button, interrupts: &mut InterruptController) {
+        self.update(interrupts, |joypad| joypad.pressed |= button.mask());
+    }
+
+    pub fn release(&mut self, button: Button, interrupts: &mut InterruptController) {
+        self.update(interrupts, |joypad| joypad.pressed &= !button.mask());
+    }
+
+    pub fn read_register(&self) -> u8 {
+        UNUSED_BITS | self.select | self.button_lines()
+    }
+
+    /// Only the select bits are writable
+    pub fn write_register(&mut self, value: u8, interrupts: &mut InterruptController) {
+        self.update(interrupts, |joypad| joypad.select = value & SELECT_BITS);
+    }
+
+    /// Bits 3-0 of P1 for the current selection and buttons
+    fn button_lines(&self) -> u8 {
+        let mut pressed = 0;
+        if self.select & SELECT_DIRECTION == 0 {
+            pressed |= self.pressed & BUTTON_BITS;
+        }
+        if self.select & SELECT_ACTION == 0 {
+            pressed |= self.pressed >> 4;
+        }
+        !pressed & BUTTON_BITS
+    }
+
+    /// Applies `change` and requests the joypad interrupt if any button line went low
+    fn update(&mut self, interrupts: &mut InterruptController, change: impl FnOnce(&mut Self)) {
+        let before = self.button_lines();
+        change(self);
+        if before & !self.button_lines() != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use super::*;
+
+#[test]
+fn nothing_selected_reads_all_released() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = InterruptController::new();
+    joypad.press(Button::A, &mut interrupts);
+    joypad.press(Button::Down, &mut interrupts);
+
+    assert_eq!(0xFF, joypad.read_register());
+}
+
+#[test]
+fn select_bits_pick_the_row() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = InterruptController::new();
+    joypad.press(Button::Start, &mut interrupts);
+    joypad.press(Button::B, &mut interrupts);
+    joypad.press(Button::Left, &mut interrupts);
+
+    joypad.write_register(0x10, &mut interrupts);
+    assert_eq!(0xD0 | 0b0101, joypad.read_register(), "action row: Start and B are 0");
+
+    joypad.write_register(0x20, &mut interrupts);
+    assert_eq!(0xE0 | 0b1101, joypad.read_register(), "direction row: Left is 0");
+
+    joypad.write_register(0x00, &mut interrupts);
+    assert_eq!(0xC0 | 0b0101, joypad.read_register(), "both rows are ANDed together");
+
+    joypad.release(Button::Start, &mut interrupts);
+    joypad.write_register(0x10, &mut interrupts);
+    assert_eq!(0xD0 | 0b1101, joypad.read_register());
+}
+
+#[test]
+fn interrupt_on_a_press_in_the_selected_row() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    joypad.write_register(0x20, &mut interrupts);
+
+    joypad.press(Button::A, &mut interrupts);
+    assert!(!interrupts.has_pending(), "the action row isn't selected");
+
+    joypad.press(Button::Up, &mut interrupts);
+    assert_eq!(Some(Interrupt::Joypad), interrupts.highest_pending());
+    interrupts.acknowledge(Interrupt::Joypad);
+
+    joypad.release(Button::Up, &mut interrupts);
+    assert!(!interrupts.has_pending(), "releases are a low to high transition");
+}
+
+#[test]
+fn interrupt_on_selecting_a_row_with_a_button_held() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    joypad.press(Button::Select, &mut interrupts);
+    assert!(!interrupts.has_pending());
+
+    joypad.write_register(0x10, &mut interrupts);
+    assert_eq!(Some(Interrupt::Joypad), interrupts.highest_pending());
+}
@@ -1,3 +1,32 @@
 #[allow(dead_code)] // to be removed later
 #[allow(unused_variables)] // to be removed later
-pub(crate) mod cpu;
\ No newline at end of file
+#[allow(clippy::upper_case_acronyms)] // names mirror the CPU's mnemonics (CPU, LD, RETI, ...) on purpose
+pub(crate) mod cpu;
+
+pub(crate) mod interrupt;
+
+pub(crate) mod memory_bus;
+
+pub(crate) mod cartridge;
+
+pub(crate) mod ppu;
+
+pub(crate) mod timer;
+
+pub(crate) mod joypad;
+
+pub(crate) mod apu;
+
+pub(crate) mod serial;
+
+pub mod emulator;
+
+pub mod runner;
+
+pub mod disassembler;
+
+pub mod debugger;
+
+pub mod gdb;
+
+pub mod trace_diff;
@@ -1,3 +1,442 @@
-fn main() {
-    todo!("Implememnt runtime")
+//! Runs a ROM headless until a stop condition is hit, then dumps whatever was asked for. With
+//! `disassemble` it lists the instructions in part of a ROM instead, with `debug` it runs the ROM
+//! in the debugger, with `gdb` it waits for gdb to connect and debug it, and with `trace-diff` it
+//! compares an execution trace (from `--trace`) against a reference emulator's.
+//!
+//! Exits with 0 when the run stopped as asked, 2 when a breakpoint or serial pattern was given
+//! but a frame or cycle limit ran out first (so test ROM runs can fail CI on a timeout), and 1
+//! on any error.
+
+use std::fs::{ self, File };
+use std::io::{ self, BufReader, Write };
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::process::ExitCode;
+
+use gameboy_emulator::debugger::Debugger;
+use gameboy_emulator::disassembler;
+use gameboy_emulator::emulator::{ Cartridge, Emulator, RenderMode, SocketEndpoint, TraceFilter };
+use gameboy_emulator::gdb::{ GdbStub, DEFAULT_PORT };
+use gameboy_emulator::runner::{ Runner, StopConditions };
+use gameboy_emulator::trace_diff;
+
+const USAGE: &str = "\
+usage: gameboy_emulator <ROM> [OPTIONS]
+       gameboy_emulator disassemble <ROM> [--bank <N>] [--range <START>:<END>]
+       gameboy_emulator debug <ROM> [--script <PATH>] [--no-save]
+       gameboy_emulator gdb <ROM> [--port <N>] [--no-save]
+       gameboy_emulator trace-diff <TRACE> <REFERENCE>
+
+Runs ROM without a window until one of the stop conditions is hit (at least one is needed).
+
+Stop conditions:
+  --frames <N>            after N frames
+  --cycles <N>            after N T-cycles
+  --until-pc <ADDRESS>    when PC reaches ADDRESS, before the instruction there runs
+  --until-serial <TEXT>   once the serial output contains TEXT
+  --until-ld-b-b          at an LD B,B, the breakpoint test ROMs use
+
+Output (a PATH of - is stdout):
+  --png <PATH>            the final screen as a PNG
+  --registers <PATH>      the final CPU registers as JSON
+  --serial <PATH>         everything sent over serial
+  --wav <PATH>            the audio as a 16-bit stereo WAV
+  --trace <PATH>          a line per instruction in the Gameboy Doctor log format
+  --trace-pc <START>:<END>
+                          only trace the instructions from START up to END
+  --trace-count <N>       stop tracing after N lines
+
+Other:
+  --render-mode <MODE>    scanline (the default) or fifo
+  --link-listen <ADDR>    wait for another gameboy_emulator to plug a link cable in at ADDR
+  --link-connect <ADDR>   plug a link cable into the gameboy_emulator listening at ADDR
+  --no-save               don't load or write the cartridge's .sav file
+  -h, --help              show this
+
+Disassembling (lists bank 0, or bank N at 0x4000 with --bank):
+  --bank <N>              the bank in the switchable area (1 by default with --range)
+  --range <START>:<END>   only the addresses from START up to END, in 0x0000-0x8000
+
+Debugging (type help in the debugger for its commands):
+  --script <PATH>         run the debugger commands in PATH instead of reading them from stdin,
+                          stopping at the first one that fails (a continue without CYCLES
+                          gives up after 10 seconds of emulated time)
+
+gdb (then `target remote :<N>` in gdb):
+  --port <N>              the local TCP port to wait on, 2345 by default
+
+trace-diff shows the first line where TRACE and REFERENCE differ, and exits with 1 if there is one.
+
+Numbers can be decimal or hex with 0x in front. A link cable ADDR is HOST:PORT for TCP, or the
+path of a Unix domain socket (anything with a / in it).
+";
+
+/// Runs a subcommand with the arguments after its name. Returns false when help was asked for.
+type Subcommand = fn(Vec<String>) -> Result<bool, String>;
+
+const EXIT_ERROR: u8 = 1;
+const EXIT_TARGET_MISSED: u8 = 2;
+
+#[derive(Default)]
+struct Options {
+    rom: String,
+    conditions: StopConditions,
+    png: Option<String>,
+    registers: Option<String>,
+    serial: Option<String>,
+    wav: Option<String>,
+    trace: Option<String>,
+    trace_filter: TraceFilter,
+    render_mode: RenderMode,
+    no_save: bool,
+    link: Option<Link>,
+}
+
+/// Which end of a link cable over a socket this side is, and at what address
+enum Link {
+    Listen(String),
+    Connect(String),
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    let subcommand: Option<Subcommand> = match args.peek().map(String::as_str) {
+        Some("disassemble") => Some(disassemble),
+        Some("debug") => Some(debug),
+        Some("gdb") => Some(gdb),
+        Some("trace-diff") => Some(diff_traces),
+        _ => None,
+    };
+    if let Some(subcommand) = subcommand {
+        return match subcommand(args.skip(1).collect()) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => {
+                print!("{USAGE}");
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::from(EXIT_ERROR)
+            }
+        };
+    }
+
+    let options = match parse_options(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_TARGET_MISSED),
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Runs the ROM and writes the outputs. Returns whether it stopped as asked.
+fn run(options: &Options) -> Result<bool, String> {
+    let emulator = if options.no_save {
+        Cartridge::from_path(&options.rom).map(Emulator::new)
+    } else {
+        Emulator::open(&options.rom)
+    };
+    let mut emulator = emulator.map_err(|err| format!("Couldn't load {}: {err}", options.rom))?;
+    emulator.set_render_mode(options.render_mode);
+    if let Some(path) = &options.wav {
+        emulator.start_recording(path).map_err(|err| format!("Couldn't record to {path}: {err}"))?;
+    }
+    if let Some(path) = &options.trace {
+        emulator.start_trace(path, options.trace_filter.clone()).map_err(|err| format!("Couldn't trace to {path}: {err}"))?;
+    }
+
+    let mut runner = Runner::new(emulator);
+    if let Some(link) = &options.link {
+        runner.set_link(open_link(link)?);
+    }
+    let reason = runner.run(&options.conditions).map_err(|err| format!("The CPU hit a bad instruction: {err}"))?;
+
+    if let Some(path) = &options.png {
+        write_output(path, &runner.screenshot_png())?;
+    }
+    if let Some(path) = &options.registers {
+        write_output(path, runner.registers_json().as_bytes())?;
+    }
+    if let Some(path) = &options.serial {
+        write_output(path, runner.serial_output().as_bytes())?;
+    }
+    if let Some(err) = runner.emulator().link_error() {
+        eprintln!("The link cable got disconnected: {err}");
+    }
+    let emulator = runner.emulator_mut();
+    report_save_error(emulator);
+    emulator.stop_recording().map_err(|err| format!("Couldn't finish the recording: {err}"))?;
+    emulator.stop_trace().map_err(|err| format!("Couldn't finish the trace: {err}"))?;
+    emulator.flush_save().map_err(|err| format!("Couldn't write the save file: {err}"))?;
+
+    Ok(reason.hit_target() || !options.conditions.has_targets())
+}
+
+/// Plugs in the link cable, waiting for the other side first when listening
+fn open_link(link: &Link) -> Result<SocketEndpoint, String> {
+    let (Link::Listen(address) | Link::Connect(address)) = link;
+    let unix = address.contains('/');
+    let endpoint = match link {
+        Link::Listen(_) => {
+            eprintln!("Waiting for the other side of the link cable on {address}");
+            if unix {
+                accept_unix(address)
+            } else {
+                TcpListener::bind(address).and_then(|listener| SocketEndpoint::accept_tcp(&listener))
+            }
+        }
+        Link::Connect(_) if unix => connect_unix(address),
+        Link::Connect(_) => SocketEndpoint::connect_tcp(address),
+    };
+    endpoint.map_err(|err| format!("Couldn't plug the link cable in at {address}: {err}"))
+}
+
+#[cfg(unix)]
+fn accept_unix(path: &str) -> io::Result<SocketEndpoint> {
+    SocketEndpoint::accept_unix(&UnixListener::bind(path)?)
+}
+
+#[cfg(unix)]
+fn connect_unix(path: &str) -> io::Result<SocketEndpoint> {
+    SocketEndpoint::connect_unix(path)
+}
+
+#[cfg(not(unix))]
+fn accept_unix(_path: &str) -> io::Result<SocketEndpoint> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "there are no Unix domain sockets here"))
+}
+
+#[cfg(not(unix))]
+fn connect_unix(_path: &str) -> io::Result<SocketEndpoint> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "there are no Unix domain sockets here"))
+}
+
+/// Says so if an autosave failed, as that doesn't stop the emulator
+fn report_save_error(emulator: &Emulator) {
+    if let (Some(err), Some(save_file)) = (emulator.last_save_error(), emulator.save_file()) {
+        eprintln!("Couldn't autosave to {}: {err}", save_file.path().display());
+    }
+}
+
+fn write_output(path: &str, bytes: &[u8]) -> Result<(), String> {
+    let result = if path == "-" {
+        io::stdout().lock().write_all(bytes)
+    } else {
+        fs::write(path, bytes)
+    };
+    result.map_err(|err| format!("Couldn't write {path}: {err}"))
+}
+
+/// `None` when help was asked for
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut rom = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--frames" => options.conditions.frames = Some(parse_number(&value()?)?),
+            "--cycles" => options.conditions.cycles = Some(parse_number(&value()?)?),
+            "--until-pc" => {
+                let address = parse_number(&value()?)?;
+                let address = u16::try_from(address).map_err(|_| format!("{address:#X} isn't an address"))?;
+                options.conditions.breakpoint = Some(address);
+            }
+            "--until-serial" => options.conditions.serial_pattern = Some(value()?),
+            "--until-ld-b-b" => options.conditions.software_breakpoint = true,
+            "--png" => options.png = Some(value()?),
+            "--registers" => options.registers = Some(value()?),
+            "--serial" => options.serial = Some(value()?),
+            "--wav" => options.wav = Some(value()?),
+            "--trace" => options.trace = Some(value()?),
+            "--trace-pc" => {
+                let text = value()?;
+                let (start, end) = text.split_once(':').ok_or_else(|| format!("{text} isn't a START:END range"))?;
+                let (start, end) = (parse_number(start)?, parse_number(end)?);
+                if start >= end || end > 0x10000 {
+                    return Err(format!("{text} isn't a range of addresses"));
+                }
+                options.trace_filter.pcs = start as u16..=(end - 1) as u16;
+            }
+            "--trace-count" => options.trace_filter.limit = Some(parse_number(&value()?)?),
+            "--render-mode" => {
+                options.render_mode = match value()?.as_str() {
+                    "scanline" => RenderMode::Scanline,
+                    "fifo" => RenderMode::Fifo,
+                    other => return Err(format!("Unknown render mode {other}")),
+                };
+            }
+            "--no-save" => options.no_save = true,
+            "--link-listen" => options.link = Some(Link::Listen(value()?)),
+            "--link-connect" => options.link = Some(Link::Connect(value()?)),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Only one ROM can be run, got {arg} too")),
+        }
+    }
+
+    options.rom = rom.ok_or("No ROM given")?;
+    let conditions = &options.conditions;
+    if conditions.frames.is_none() && conditions.cycles.is_none() && !conditions.has_targets() {
+        return Err("No stop condition given, it would run forever".into());
+    }
+    Ok(Some(options))
+}
+
+/// The `disassemble` subcommand. Returns false when help was asked for.
+fn disassemble(args: Vec<String>) -> Result<bool, String> {
+    let mut args = args.into_iter();
+    let mut rom = None;
+    let mut bank = None;
+    let mut range = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(false),
+            "--bank" => bank = Some(parse_number(&value()?)? as usize),
+            "--range" => {
+                let text = value()?;
+                let (start, end) = text.split_once(':').ok_or_else(|| format!("{text} isn't a START:END range"))?;
+                let start = parse_number(start)?;
+                let start = u16::try_from(start).map_err(|_| format!("{start:#X} isn't an address"))?;
+                range = Some((start, parse_number(end)?.min(u32::MAX as u64) as u32));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Only one ROM can be disassembled, got {arg} too")),
+        }
+    }
+
+    let rom_path = rom.ok_or("No ROM given")?;
+    let rom = fs::read(&rom_path).map_err(|err| format!("Couldn't read {rom_path}: {err}"))?;
+    let listing = match range {
+        Some((start, end)) => disassembler::disassemble_range(&rom, bank.unwrap_or(1), start, end),
+        None => disassembler::disassemble_bank(&rom, bank.unwrap_or(0)),
+    };
+    let listing = listing.map_err(|err| format!("Couldn't disassemble {rom_path}: {err}"))?;
+    write_output("-", listing.as_bytes())?;
+    Ok(true)
+}
+
+/// The `debug` subcommand. Returns false when help was asked for.
+fn debug(args: Vec<String>) -> Result<bool, String> {
+    let mut args = args.into_iter();
+    let mut rom = None;
+    let mut script = None;
+    let mut no_save = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(false),
+            "--script" => script = Some(args.next().ok_or("--script needs a value")?),
+            "--no-save" => no_save = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Only one ROM can be debugged, got {arg} too")),
+        }
+    }
+
+    let rom = rom.ok_or("No ROM given")?;
+    let emulator = if no_save {
+        Cartridge::from_path(&rom).map(Emulator::new)
+    } else {
+        Emulator::open(&rom)
+    };
+    let emulator = emulator.map_err(|err| format!("Couldn't load {rom}: {err}"))?;
+
+    let mut debugger = Debugger::new(emulator);
+    let mut stdout = io::stdout().lock();
+    match script {
+        Some(path) => {
+            let file = File::open(&path).map_err(|err| format!("Couldn't open {path}: {err}"))?;
+            debugger.run_script(BufReader::new(file), &mut stdout).map_err(|err| format!("{path}: {err}"))?;
+        }
+        None => debugger.run_interactive(io::stdin().lock(), &mut stdout).map_err(|err| format!("Couldn't talk to the terminal: {err}"))?,
+    }
+    report_save_error(debugger.emulator());
+    debugger.emulator_mut().flush_save().map_err(|err| format!("Couldn't write the save file: {err}"))?;
+    Ok(true)
+}
+
+/// The `gdb` subcommand. Returns false when help was asked for.
+fn gdb(args: Vec<String>) -> Result<bool, String> {
+    let mut args = args.into_iter();
+    let mut rom = None;
+    let mut port = DEFAULT_PORT;
+    let mut no_save = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(false),
+            "--port" => {
+                let value = args.next().ok_or("--port needs a value")?;
+                port = parse_number(&value)?.try_into().map_err(|_| format!("{value} isn't a port"))?;
+            }
+            "--no-save" => no_save = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Only one ROM can be debugged, got {arg} too")),
+        }
+    }
+
+    let rom = rom.ok_or("No ROM given")?;
+    let emulator = if no_save {
+        Cartridge::from_path(&rom).map(Emulator::new)
+    } else {
+        Emulator::open(&rom)
+    };
+    let emulator = emulator.map_err(|err| format!("Couldn't load {rom}: {err}"))?;
+
+    let mut stub = GdbStub::new(emulator);
+    eprintln!("Waiting for gdb on 127.0.0.1:{port}");
+    stub.listen(("127.0.0.1", port)).map_err(|err| format!("The gdb connection failed: {err}"))?;
+    report_save_error(stub.emulator());
+    stub.emulator_mut().flush_save().map_err(|err| format!("Couldn't write the save file: {err}"))?;
+    Ok(true)
+}
+
+/// The `trace-diff` subcommand. Returns false when help was asked for.
+fn diff_traces(args: Vec<String>) -> Result<bool, String> {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(false);
+    }
+    let [ours, reference] = args.as_slice() else {
+        return Err("trace-diff needs our trace and the reference's".into());
+    };
+    let open = |path: &String| File::open(path).map(BufReader::new).map_err(|err| format!("Couldn't open {path}: {err}"));
+
+    let divergence = trace_diff::first_divergence(open(ours)?, open(reference)?)
+        .map_err(|err| format!("Couldn't read the traces: {err}"))?;
+    match divergence {
+        Some(divergence) => Err(divergence.to_string()),
+        None => {
+            println!("The traces match");
+            Ok(true)
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("{text} isn't a number"))
 }
@@ -0,0 +1,295 @@
+//! The memory bus, i.e. the Game Boy's 16-bit address space and who answers for each part of it.
+//!
+//! Memory map:
+//! ```text
+//! 0x0000-0x7FFF | ROM            | cartridge (writes go to the cartridge's controller)
+//! 0x8000-0x9FFF | VRAM           | video RAM (PPU)
+//! 0xA000-0xBFFF | External RAM   | cartridge RAM
+//! 0xC000-0xDFFF | WRAM           | work RAM
+//! 0xE000-0xFDFF | Echo RAM       | mirror of 0xC000-0xDDFF
+//! 0xFE00-0xFE9F | OAM            | object attribute memory (sprites, PPU)
+//! 0xFEA0-0xFEFF | Unusable       | reads 0x00, writes are ignored
+//! 0xFF00-0xFF7F | I/O registers  | routed to the peripheral owning the register
+//! 0xFF80-0xFFFE | HRAM           | high RAM
+//! 0xFFFF        | IE             | interrupt enable register
+//! ```
+//!
+//! While an OAM DMA runs (see the `dma` module) the CPU only gets to the I/O registers and HRAM.
+//!
+//! `read_byte` and `write_byte` are the CPU's accesses, which watchpoints (see the `watch` module)
+//! see. Anything looking at memory from outside the emulated machine, like the debugger, uses
+//! `peek_byte` and `poke_byte` instead.
+
+mod dma;
+mod watch;
+#[cfg(test)]
+mod tests;
+
+use crate::apu::{ self, Apu };
+use crate::cartridge::Cartridge;
+use crate::interrupt::{ InterruptController, IE_ADDRESS, IF_ADDRESS };
+use crate::joypad::{ self, Joypad };
+use crate::ppu::{ self, Ppu };
+use crate::serial::{ self, Serial };
+use crate::timer::{ self, Timer };
+use dma::{ OamDma, DMA_ADDRESS };
+use watch::Watchpoints;
+
+pub use watch::{ Access, WatchHit, WatchKind, Watchpoint };
+
+const ROM_START: u16 = 0x0000;
+const ROM_END: u16 = 0x7FFF;
+const VRAM_START: u16 = 0x8000;
+const VRAM_END: u16 = 0x9FFF;
+const EXTERNAL_RAM_START: u16 = 0xA000;
+const EXTERNAL_RAM_END: u16 = 0xBFFF;
+const WRAM_START: u16 = 0xC000;
+const WRAM_END: u16 = 0xDFFF;
+const ECHO_RAM_START: u16 = 0xE000;
+const ECHO_RAM_END: u16 = 0xFDFF;
+const OAM_START: u16 = 0xFE00;
+const OAM_END: u16 = 0xFE9F;
+const UNUSABLE_START: u16 = 0xFEA0;
+const UNUSABLE_END: u16 = 0xFEFF;
+const IO_START: u16 = 0xFF00;
+const IO_END: u16 = 0xFF7F;
+const HRAM_START: u16 = 0xFF80;
+const HRAM_END: u16 = 0xFFFE;
+
+const WRAM_SIZE: usize = (WRAM_END - WRAM_START) as usize + 1;
+const IO_SIZE: usize = (IO_END - IO_START) as usize + 1;
+const HRAM_SIZE: usize = (HRAM_END - HRAM_START) as usize + 1;
+
+/// The DMA moves a byte per M-cycle
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+
+/// What the DMG returns for reads of 0xFEA0-0xFEFF while OAM is accessible
+const UNUSABLE_READ_VALUE: u8 = 0x00;
+/// What reads of memory that isn't backed by anything return (the data bus is pulled up)
+const OPEN_BUS_READ_VALUE: u8 = 0xFF;
+
+pub struct MemoryBus {
+    /// The cartridge in the slot, if any
+    cartridge: Option<Cartridge>,
+    wram: Box<[u8; WRAM_SIZE]>,
+    /// Backing bytes for the I/O registers that have no peripheral owning them yet
+    io: [u8; IO_SIZE],
+    hram: [u8; HRAM_SIZE],
+    pub interrupts: InterruptController,
+    /// Owns VRAM, OAM and the LCD registers
+    pub ppu: Ppu,
+    /// DIV, TIMA, TMA and TAC
+    pub timer: Timer,
+    /// P1 and the buttons held down
+    pub joypad: Joypad,
+    dma: OamDma,
+    /// The sound registers and wave RAM
+    pub apu: Apu,
+    /// SB, SC and whatever is on the other end of the link cable
+    pub serial: Serial,
+    /// Only there while a debugger has watchpoints set
+    watchpoints: Option<Watchpoints>,
+}
+
+impl MemoryBus {
+    /// A bus with nothing in the cartridge slot (all cartridge reads are open bus)
+    #[cfg(test)] // an emulator always has a cartridge in
+    pub fn new() -> Self {
+        Self::with_optional_cartridge(None)
+    }
+
+    /// A bus with the given cartridge mapped at 0x0000-0x7FFF & 0xA000-0xBFFF
+    pub fn with_cartridge(cartridge: Cartridge) -> Self {
+        Self::with_optional_cartridge(Some(cartridge))
+    }
+
+    fn with_optional_cartridge(cartridge: Option<Cartridge>) -> Self {
+        Self {
+            cartridge,
+            wram: Box::new([0; WRAM_SIZE]),
+            io: [0; IO_SIZE],
+            hram: [0; HRAM_SIZE],
+            interrupts: InterruptController::new(),
+            ppu: Ppu::new(),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            dma: OamDma::new(),
+            apu: Apu::new(),
+            serial: Serial::new(),
+            watchpoints: None,
+        }
+    }
+
+    /// Puts the I/O registers in the state the DMG boot ROM leaves them in
+    pub fn skip_boot_rom(&mut self) {
+        self.write_byte(joypad::P1_ADDRESS, 0xCF);
+        self.write_byte(IF_ADDRESS, 0xE1);
+        self.write_byte(ppu::BGP_ADDRESS, 0xFC);
+        self.write_byte(ppu::LCDC_ADDRESS, 0x91);
+        self.write_byte(timer::TAC_ADDRESS, 0xF8);
+        // the APU has to be on for the rest of its registers to take writes
+        self.write_byte(apu::NR52_ADDRESS, 0xF1);
+        self.write_byte(apu::NR50_ADDRESS, 0x77);
+        self.write_byte(apu::NR51_ADDRESS, 0xF3);
+        self.write_byte(apu::NR10_ADDRESS + 1, 0x80);
+        self.write_byte(apu::NR10_ADDRESS + 2, 0xF3);
+        self.timer.set_divider(0xABCC);
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
+    }
+
+    /// Runs everything hanging off the bus for `t_cycles`
+    pub fn tick(&mut self, t_cycles: u32) {
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.tick(t_cycles);
+        }
+        for _ in 0..t_cycles / T_CYCLES_PER_M_CYCLE {
+            if let Some((source, index)) = self.dma.step() {
+                let value = self.read_unblocked(source);
+                self.ppu.dma_write_oam(index, value);
+            }
+        }
+        self.timer.tick(t_cycles, &mut self.interrupts);
+        self.serial.tick(t_cycles, &mut self.interrupts);
+        self.apu.tick(t_cycles);
+        self.ppu.tick(t_cycles, &mut self.interrupts);
+    }
+
+    /// Whether a running OAM DMA keeps the CPU from `address`. Only HRAM and the I/O registers
+    /// (so the DMA can be restarted) aren't on the buses the DMA holds.
+    fn blocked_by_dma(&self, address: u16) -> bool {
+        self.dma.is_running() && address < IO_START
+    }
+
+    /// Whether an OAM DMA is copying into OAM right now
+    pub fn dma_running(&self) -> bool {
+        self.dma.is_running()
+    }
+
+    /// Replaces the watchpoints, an empty list turning them off
+    pub fn set_watchpoints(&mut self, watchpoints: Vec<Watchpoint>) {
+        self.watchpoints = match watchpoints.is_empty() {
+            true => None,
+            false => Some(Watchpoints::new(watchpoints)),
+        };
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        self.watchpoints.as_ref().map_or(&[], |watchpoints| watchpoints.watchpoints())
+    }
+
+    /// The first access a watchpoint caught since this was last called
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.watchpoints.as_ref().and_then(|watchpoints| watchpoints.take_hit())
+    }
+
+    /// A read by the CPU
+    #[inline]
+    pub fn read_byte(&self, address: u16) -> u8 {
+        let value = self.peek_byte(address);
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.check(Access::Read, address, value);
+        }
+        value
+    }
+
+    /// Reads `address` the way the CPU would, without watchpoints seeing it
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        if self.blocked_by_dma(address) {
+            return OPEN_BUS_READ_VALUE;
+        }
+        self.read_unblocked(address)
+    }
+
+    /// Reads `address` the way the DMA sees it, i.e. without its own blocking
+    fn read_unblocked(&self, address: u16) -> u8 {
+        match address {
+            ROM_START..=ROM_END => {
+                self.cartridge.as_ref().map_or(OPEN_BUS_READ_VALUE, |cartridge| cartridge.read_rom(address - ROM_START))
+            }
+            VRAM_START..=VRAM_END => self.ppu.read_vram(address - VRAM_START),
+            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
+                self.cartridge.as_ref().map_or(OPEN_BUS_READ_VALUE, |cartridge| cartridge.read_ram(address - EXTERNAL_RAM_START))
+            }
+            WRAM_START..=WRAM_END => self.wram[(address - WRAM_START) as usize],
+            ECHO_RAM_START..=ECHO_RAM_END => self.wram[(address - ECHO_RAM_START) as usize],
+            OAM_START..=OAM_END => self.ppu.read_oam(address - OAM_START),
+            UNUSABLE_START..=UNUSABLE_END => UNUSABLE_READ_VALUE,
+            IO_START..=IO_END => self.read_io(address),
+            HRAM_START..=HRAM_END => self.hram[(address - HRAM_START) as usize],
+            IE_ADDRESS => self.interrupts.read_enable(),
+        }
+    }
+
+    /// A write by the CPU
+    #[inline]
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        if let Some(watchpoints) = &self.watchpoints {
+            watchpoints.check(Access::Write, address, value);
+        }
+        self.poke_byte(address, value);
+    }
+
+    /// Writes `address` the way the CPU would, without watchpoints seeing it. ROM can't be
+    /// changed like this, writes there still go to the cartridge's controller.
+    pub fn poke_byte(&mut self, address: u16, value: u8) {
+        if self.blocked_by_dma(address) {
+            return;
+        }
+        match address {
+            ROM_START..=ROM_END => {
+                // never changes ROM, these are commands for the cartridge's memory bank controller
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write_rom(address - ROM_START, value);
+                }
+            }
+            VRAM_START..=VRAM_END => self.ppu.write_vram(address - VRAM_START, value),
+            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write_ram(address - EXTERNAL_RAM_START, value);
+                }
+            }
+            WRAM_START..=WRAM_END => self.wram[(address - WRAM_START) as usize] = value,
+            ECHO_RAM_START..=ECHO_RAM_END => self.wram[(address - ECHO_RAM_START) as usize] = value,
+            OAM_START..=OAM_END => self.ppu.write_oam(address - OAM_START, value),
+            UNUSABLE_START..=UNUSABLE_END => {}
+            IO_START..=IO_END => self.write_io(address, value),
+            HRAM_START..=HRAM_END => self.hram[(address - HRAM_START) as usize] = value,
+            IE_ADDRESS => self.interrupts.write_enable(value),
+        }
+    }
+
+    /// Routes an I/O register read to the peripheral that owns the register
+    fn read_io(&self, address: u16) -> u8 {
+        match address {
+            joypad::P1_ADDRESS => self.joypad.read_register(),
+            serial::SB_ADDRESS..=serial::SC_ADDRESS => self.serial.read_register(address),
+            IF_ADDRESS => self.interrupts.read_flag(),
+            DMA_ADDRESS => self.dma.read_register(),
+            apu::NR10_ADDRESS..=apu::WAVE_RAM_END => self.apu.read_register(address),
+            timer::DIV_ADDRESS..=timer::TAC_ADDRESS => self.timer.read_register(address),
+            ppu::LCDC_ADDRESS..=ppu::LYC_ADDRESS | ppu::BGP_ADDRESS..=ppu::WX_ADDRESS => self.ppu.read_register(address),
+            _ => self.io[(address - IO_START) as usize]
+        }
+    }
+
+    /// Routes an I/O register write to the peripheral that owns the register
+    fn write_io(&mut self, address: u16, value: u8) {
+        match address {
+            joypad::P1_ADDRESS => self.joypad.write_register(value, &mut self.interrupts),
+            serial::SB_ADDRESS..=serial::SC_ADDRESS => self.serial.write_register(address, value),
+            IF_ADDRESS => self.interrupts.write_flag(value),
+            DMA_ADDRESS => self.dma.write_register(value),
+            apu::NR10_ADDRESS..=apu::WAVE_RAM_END => self.apu.write_register(address, value),
+            timer::DIV_ADDRESS..=timer::TAC_ADDRESS => self.timer.write_register(address, value),
+            ppu::LCDC_ADDRESS..=ppu::LYC_ADDRESS | ppu::BGP_ADDRESS..=ppu::WX_ADDRESS => self.ppu.write_register(address, value),
+            _ => self.io[(address - IO_START) as usize] = value
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! OAM DMA: writing a page number XX to 0xFF46 copies 0xXX00-0xXX9F into OAM, one byte per
+//! M-cycle, after one M-cycle of setup.
+//!
+//! While the copy runs the DMA owns the buses, so the CPU can't get at anything below 0xFF00
+//! (it reads 0xFF and its writes go nowhere). That's why games run the DMA routine from HRAM.
+//! Writing 0xFF46 again during a transfer restarts it: the old one keeps going through the
+//! new one's setup M-cycle, so OAM never becomes accessible in between.
+
+use super::{ ECHO_RAM_START, WRAM_START };
+
+pub const DMA_ADDRESS: u16 = 0xFF46;
+
+/// Bytes copied by a transfer, i.e. all of OAM
+const TRANSFER_LENGTH: u8 = 0xA0;
+
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug))]
+struct Transfer {
+    source: u16,
+    /// The next byte to copy
+    index: u8,
+}
+
+pub struct OamDma {
+    /// What was last written to 0xFF46, which is also what it reads
+    register: u8,
+    /// A transfer that was just asked for and is in its setup M-cycle
+    starting: Option<u16>,
+    running: Option<Transfer>,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        Self { register: 0xFF, starting: None, running: None }
+    }
+
+    pub fn read_register(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write_register(&mut self, value: u8) {
+        self.register = value;
+        let source = (value as u16) << 8;
+        // the DMA sees the echo of WRAM all the way up, there's no OAM or I/O to copy from
+        let source = if source >= ECHO_RAM_START { source - (ECHO_RAM_START - WRAM_START) } else { source };
+        self.starting = Some(source);
+    }
+
+    /// Whether the DMA is holding the buses, which blocks the CPU everywhere below 0xFF00
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Runs one M-cycle and returns the (source address, OAM index) of the byte to copy in it
+    pub fn step(&mut self) -> Option<(u16, u8)> {
+        let copy = self.running.as_mut().map(|transfer| {
+            let index = transfer.index;
+            transfer.index += 1;
+            (transfer.source + index as u16, index)
+        });
+        if self.running.is_some_and(|transfer| transfer.index == TRANSFER_LENGTH) {
+            self.running = None;
+        }
+        if let Some(source) = self.starting.take() {
+            self.running = Some(Transfer { source, index: 0 });
+        }
+        copy
+    }
+}
@@ -0,0 +1,255 @@
+use super::*;
+use crate::cartridge::with_valid_header;
+use crate::interrupt::Interrupt;
+
+#[test]
+fn whole_address_space_is_addressable() {
+    let mut bus = MemoryBus::new();
+
+    // notably 0xFFFF, which was one past the end of the old flat array
+    for address in 0x0000..=0xFFFF_u16 {
+        bus.write_byte(address, 0x00);
+        bus.read_byte(address);
+    }
+}
+
+fn bus_with_cartridge(cartridge_type: u8, ram_size_code: u8) -> MemoryBus {
+    let rom = with_valid_header(vec![0x12; 0x8000], cartridge_type, ram_size_code);
+    MemoryBus::with_cartridge(Cartridge::from_bytes(&rom).expect("test ROM should load"))
+}
+
+#[test]
+fn rom_writes_do_not_change_rom() {
+    let mut bus = bus_with_cartridge(0x00, 0x00);
+
+    bus.write_byte(0x0000, 0xAB);
+    bus.write_byte(0x2000, 0x01);
+    bus.write_byte(0x7FFF, 0xAB);
+
+    assert_eq!(0x12, bus.read_byte(0x0000));
+    assert_eq!(0x12, bus.read_byte(0x2000));
+    assert_eq!(0x12, bus.read_byte(0x7FFF));
+}
+
+#[test]
+fn empty_cartridge_slot_reads_open_bus() {
+    let mut bus = MemoryBus::new();
+
+    bus.write_byte(0xA000, 0x12);
+
+    assert_eq!(0xFF, bus.read_byte(0x0000));
+    assert_eq!(0xFF, bus.read_byte(0x7FFF));
+    assert_eq!(0xFF, bus.read_byte(0xA000));
+}
+
+#[test]
+fn cartridge_ram_is_mapped() {
+    // ROM+RAM with 8 KiB of RAM
+    let mut bus = bus_with_cartridge(0x08, 0x02);
+
+    bus.write_byte(0xA000, 0x34);
+    bus.write_byte(0xBFFF, 0x56);
+
+    assert_eq!(0x34, bus.read_byte(0xA000));
+    assert_eq!(0x56, bus.read_byte(0xBFFF));
+
+    // without RAM the region reads open bus
+    let mut bus = bus_with_cartridge(0x00, 0x00);
+    bus.write_byte(0xA000, 0x34);
+    assert_eq!(0xFF, bus.read_byte(0xA000));
+}
+
+#[test]
+fn echo_ram_mirrors_wram() {
+    let mut bus = MemoryBus::new();
+
+    bus.write_byte(0xC000, 0x11);
+    bus.write_byte(0xDDFF, 0x22);
+    assert_eq!(0x11, bus.read_byte(0xE000));
+    assert_eq!(0x22, bus.read_byte(0xFDFF));
+
+    bus.write_byte(0xE123, 0x33);
+    assert_eq!(0x33, bus.read_byte(0xC123));
+
+    // 0xDE00-0xDFFF is past the end of echo RAM, so it has no mirror
+    bus.write_byte(0xDE00, 0x44);
+    assert_eq!(0x44, bus.read_byte(0xDE00));
+}
+
+#[test]
+fn unusable_region_reads_zero_and_ignores_writes() {
+    let mut bus = MemoryBus::new();
+
+    for address in 0xFEA0..=0xFEFF {
+        bus.write_byte(address, 0xAB);
+        assert_eq!(0x00, bus.read_byte(address), "{address:#X} should read 0x00");
+    }
+}
+
+#[test]
+fn ram_regions_keep_their_values() {
+    let mut bus = MemoryBus::new();
+    let addresses = [0x8000, 0x9FFF, 0xC000, 0xDFFF, 0xFE00, 0xFE9F, 0xFF80, 0xFFFE];
+
+    for (value, address) in addresses.into_iter().enumerate() {
+        bus.write_byte(address, value as u8 + 1);
+    }
+    for (value, address) in addresses.into_iter().enumerate() {
+        assert_eq!(value as u8 + 1, bus.read_byte(address), "{address:#X} lost its value");
+    }
+}
+
+#[test]
+fn io_registers_reach_their_owner() {
+    let mut bus = MemoryBus::new();
+
+    bus.write_byte(0xFF0F, 0x01);
+    bus.write_byte(0xFFFF, 0x01);
+
+    assert_eq!(0xE1, bus.read_byte(0xFF0F));
+    assert!(bus.interrupts.has_pending());
+}
+
+#[test]
+fn ppu_owns_video_memory_and_lcd_registers() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(0x8000, 0x12);
+    bus.write_byte(0xFE00, 0x34);
+
+    // LCD on with LY=LYC=0 so STAT shows mode 2 with the coincidence bit
+    bus.write_byte(0xFF40, 0x91);
+    assert_eq!(0x91, bus.read_byte(0xFF40));
+    assert_eq!(0x86, bus.read_byte(0xFF41));
+    assert_eq!(0xFF, bus.read_byte(0xFE00), "OAM is blocked during the OAM scan");
+
+    // one whole line later
+    bus.tick(456);
+    assert_eq!(1, bus.read_byte(0xFF44));
+    bus.tick(80);
+    assert_eq!(0xFF, bus.read_byte(0x8000), "VRAM is blocked while drawing");
+    bus.tick(172);
+    assert_eq!(0x12, bus.read_byte(0x8000));
+    assert_eq!(0x34, bus.read_byte(0xFE00));
+}
+
+#[test]
+fn timer_registers_and_interrupt() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(0xFF06, 0x80);
+    bus.write_byte(0xFF05, 0xFE);
+    bus.write_byte(0xFF07, 0x05);
+    assert_eq!(0xFD, bus.read_byte(0xFF07));
+
+    // 16 T-cycles per count at TAC 01, plus the M-cycle before the reload
+    bus.tick(16 * 2 + 4);
+    assert_eq!(0x80, bus.read_byte(0xFF05));
+    assert_eq!(Interrupt::Timer.mask(), bus.read_byte(0xFF0F) & Interrupt::Timer.mask());
+    assert_eq!(0x00, bus.read_byte(0xFF04));
+}
+
+/// A bus with WRAM page 0xC1 filled with 0x00, 0x01, ..., 0x9F and the LCD off (so OAM is
+/// readable whenever the DMA lets go of it)
+fn bus_with_dma_source() -> MemoryBus {
+    let mut bus = MemoryBus::new();
+    for index in 0..0xA0 {
+        bus.write_byte(0xC100 + index, index as u8);
+    }
+    bus
+}
+
+#[test]
+fn oam_dma_copies_a_page_in_160_m_cycles() {
+    let mut bus = bus_with_dma_source();
+    bus.write_byte(0xFF46, 0xC1);
+    assert_eq!(0xC1, bus.read_byte(0xFF46));
+
+    // the setup M-cycle, then 159 of the 160 bytes
+    bus.tick(4 * 160);
+    assert!(bus.dma_running());
+    bus.tick(4);
+    assert!(!bus.dma_running());
+
+    for index in 0..0xA0 {
+        assert_eq!(index as u8, bus.read_byte(0xFE00 + index), "OAM byte {index:#04X}");
+    }
+}
+
+#[test]
+fn oam_dma_blocks_everything_but_io_and_hram() {
+    let mut bus = bus_with_dma_source();
+    bus.write_byte(0xFF80, 0x12);
+    bus.write_byte(0xFF06, 0x34);
+    bus.write_byte(0xFF46, 0xC1);
+    bus.tick(8);
+
+    assert_eq!(0xFF, bus.read_byte(0xC100));
+    bus.write_byte(0xC000, 0x56);
+    assert_eq!(0x12, bus.read_byte(0xFF80));
+    assert_eq!(0x34, bus.read_byte(0xFF06));
+
+    bus.tick(4 * 160);
+    assert_eq!(0x00, bus.read_byte(0xC000), "the write during the DMA went nowhere");
+    assert_eq!(0x00, bus.read_byte(0xC100));
+}
+
+#[test]
+fn oam_dma_restart_keeps_oam_blocked() {
+    let mut bus = bus_with_dma_source();
+    for index in 0..0xA0 {
+        bus.write_byte(0xC200 + index, 0xA0 - index as u8);
+    }
+    bus.write_byte(0xFF46, 0xC1);
+    bus.tick(4 * 10);
+
+    bus.write_byte(0xFF46, 0xC2);
+    bus.tick(4);
+    assert!(bus.dma_running(), "the first transfer runs through the second one's setup");
+    bus.tick(4 * 160);
+
+    assert!(!bus.dma_running());
+    assert_eq!(0xA0, bus.read_byte(0xFE00));
+    assert_eq!(0x01, bus.read_byte(0xFE9F));
+}
+
+#[test]
+fn oam_dma_from_echo_ram_and_above_reads_wram() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(0xDE00, 0x42);
+    bus.write_byte(0xDF9F, 0x24);
+
+    bus.write_byte(0xFF46, 0xFE);
+    bus.tick(4 * 161);
+    assert_eq!(0x42, bus.read_byte(0xFE00));
+
+    bus.write_byte(0xFF46, 0xFF);
+    bus.tick(4 * 161);
+    assert_eq!(0x24, bus.read_byte(0xFE9F));
+}
+
+#[test]
+fn watchpoints_catch_the_first_matching_cpu_access() {
+    let mut bus = MemoryBus::new();
+    bus.set_watchpoints(vec![
+        Watchpoint { start: 0xC000, end: 0xC00F, kind: WatchKind::Write },
+        Watchpoint { start: 0xC008, end: 0xC008, kind: WatchKind::ReadWrite },
+    ]);
+
+    // looking from outside doesn't count, and neither does a read of a write-only watchpoint
+    bus.poke_byte(0xC000, 0x12);
+    bus.peek_byte(0xC008);
+    bus.read_byte(0xC001);
+    assert_eq!(None, bus.take_watch_hit());
+
+    bus.read_byte(0xC008);
+    bus.write_byte(0xC002, 0x34);
+    assert_eq!(Some(WatchHit { index: 1, access: Access::Read, address: 0xC008, value: 0x00 }), bus.take_watch_hit());
+    assert_eq!(None, bus.take_watch_hit(), "taking the hit clears it");
+
+    bus.write_byte(0xC003, 0x56);
+    assert_eq!(Some(WatchHit { index: 0, access: Access::Write, address: 0xC003, value: 0x56 }), bus.take_watch_hit());
+
+    bus.set_watchpoints(Vec::new());
+    bus.write_byte(0xC003, 0x56);
+    assert_eq!(None, bus.take_watch_hit());
+    assert!(bus.watchpoints().is_empty());
+}
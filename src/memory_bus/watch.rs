@@ -0,0 +1,87 @@
+//! Watchpoints: noting when the CPU reads or writes an address range, for the debugger.
+//!
+//! The bus only has a [`Watchpoints`] while some are set, so without a debugger the accesses
+//! only pay for checking an `Option` that's always `None`.
+
+use std::cell::Cell;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Which accesses a watchpoint stops on
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Watchpoint {
+    /// The first address watched
+    pub start: u16,
+    /// The last address watched
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+/// An access a watchpoint caught
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct WatchHit {
+    /// Which watchpoint, as an index into the ones set
+    pub index: usize,
+    pub access: Access,
+    pub address: u16,
+    /// What was read, or what was written
+    pub value: u8,
+}
+
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    /// The first hit since it was last taken. A cell as reads only borrow the bus.
+    hit: Cell<Option<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn new(watchpoints: Vec<Watchpoint>) -> Self {
+        Self { watchpoints, hit: Cell::new(None) }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Notes the access if a watchpoint covers it and nothing was caught before it
+    pub fn check(&self, access: Access, address: u16, value: u8) {
+        if self.hit.get().is_some() {
+            return;
+        }
+        let index = self.watchpoints.iter().position(|watchpoint| {
+            (watchpoint.start..=watchpoint.end).contains(&address) && watchpoint.kind.matches(access)
+        });
+        if let Some(index) = index {
+            self.hit.set(Some(WatchHit { index, access, address, value }));
+        }
+    }
+
+    pub fn take_hit(&self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
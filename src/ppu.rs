@@ -0,0 +1,490 @@
+//! The pixel processing unit, which draws the 160x144 screen out of VRAM and OAM.
+//!
+//! Every scanline takes 456 dots (T-cycles) and goes through these modes:
+//! ```text
+//! Mode 2 | OAM scan | 80 dots      | picks the (up to 10) sprites on the line, OAM is blocked
+//! Mode 3 | Drawing  | 172-289 dots | pixels go to the screen, VRAM and OAM are blocked
+//! Mode 0 | HBlank   | the rest     | nothing is blocked
+//! ```
+//! Mode 3 always takes 172 dots with the scanline renderer, the pixel FIFO renderer stretches
+//! it like the hardware does (see [`RenderMode`]).
+//! Lines 0-143 are drawn, then lines 144-153 are all mode 1 (VBlank), for 70224 dots per frame.
+//!
+//! Registers:
+//! ```text
+//! 0xFF40 | LCDC | 7: LCD on, 6: window map, 5: window on, 4: BG/window tile data,
+//!        |      | 3: BG map, 2: sprite size, 1: sprites on, 0: BG/window on
+//! 0xFF41 | STAT | 6: LYC=LY int, 5: mode 2 int, 4: mode 1 int, 3: mode 0 int, 2: LYC=LY, 1-0: mode
+//! 0xFF42 | SCY  | BG scroll Y
+//! 0xFF43 | SCX  | BG scroll X
+//! 0xFF44 | LY   | current line (read only)
+//! 0xFF45 | LYC  | line to compare LY against
+//! 0xFF47 | BGP  | BG/window palette
+//! 0xFF48 | OBP0 | sprite palette 0
+//! 0xFF49 | OBP1 | sprite palette 1
+//! 0xFF4A | WY   | window Y
+//! 0xFF4B | WX   | window X + 7
+//! ```
+
+mod scanline;
+mod fifo;
+#[cfg(test)]
+mod tests;
+
+use crate::interrupt::{ Interrupt, InterruptController };
+use fifo::PixelFifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const VRAM_SIZE: usize = 0x2000;
+pub const OAM_SIZE: usize = 0xA0;
+
+pub const LCDC_ADDRESS: u16 = 0xFF40;
+pub const STAT_ADDRESS: u16 = 0xFF41;
+pub const SCY_ADDRESS: u16 = 0xFF42;
+pub const SCX_ADDRESS: u16 = 0xFF43;
+pub const LY_ADDRESS: u16 = 0xFF44;
+pub const LYC_ADDRESS: u16 = 0xFF45;
+pub const BGP_ADDRESS: u16 = 0xFF47;
+pub const OBP0_ADDRESS: u16 = 0xFF48;
+pub const OBP1_ADDRESS: u16 = 0xFF49;
+pub const WY_ADDRESS: u16 = 0xFF4A;
+pub const WX_ADDRESS: u16 = 0xFF4B;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+/// Mode 3 length for the scanline renderer, which is also the shortest it can be with the FIFO
+const DRAWING_DOTS: u16 = 172;
+/// 144 drawn lines and 10 lines of VBlank
+const LINES_PER_FRAME: u8 = 154;
+/// Dots (T-cycles) in a whole frame
+pub const DOTS_PER_FRAME: u32 = DOTS_PER_LINE as u32 * LINES_PER_FRAME as u32;
+
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+// LCDC bits
+const LCDC_LCD_ENABLE: u8 = 1 << 7;
+const LCDC_WINDOW_MAP: u8 = 1 << 6;
+const LCDC_WINDOW_ENABLE: u8 = 1 << 5;
+const LCDC_TILE_DATA: u8 = 1 << 4;
+const LCDC_BG_MAP: u8 = 1 << 3;
+const LCDC_SPRITE_SIZE: u8 = 1 << 2;
+const LCDC_SPRITE_ENABLE: u8 = 1 << 1;
+const LCDC_BG_WINDOW_ENABLE: u8 = 1 << 0;
+
+// STAT bits
+const STAT_LYC_INTERRUPT: u8 = 1 << 6;
+const STAT_MODE_2_INTERRUPT: u8 = 1 << 5;
+const STAT_MODE_1_INTERRUPT: u8 = 1 << 4;
+const STAT_MODE_0_INTERRUPT: u8 = 1 << 3;
+const STAT_COINCIDENCE: u8 = 1 << 2;
+const STAT_WRITABLE_BITS: u8 = STAT_LYC_INTERRUPT | STAT_MODE_2_INTERRUPT | STAT_MODE_1_INTERRUPT | STAT_MODE_0_INTERRUPT;
+/// Bit 7 of STAT doesn't exist and reads as 1
+const STAT_UNUSED_BITS: u8 = 1 << 7;
+
+/// Tile maps are 32x32 tiles
+const TILE_MAP_WIDTH: u16 = 32;
+const TILE_MAP_0: u16 = 0x1800;
+const TILE_MAP_1: u16 = 0x1C00;
+/// Every tile is 8x8 pixels, 2 bytes per row
+const TILE_SIZE: u16 = 16;
+
+// sprite flag bits
+const SPRITE_BEHIND_BG: u8 = 1 << 7;
+const SPRITE_Y_FLIP: u8 = 1 << 6;
+const SPRITE_X_FLIP: u8 = 1 << 5;
+const SPRITE_PALETTE: u8 = 1 << 4;
+
+/// What the CPU reads from VRAM/OAM while the PPU has it blocked
+const BLOCKED_READ_VALUE: u8 = 0xFF;
+
+/// The PPU mode, as shown in STAT bits 0-1
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// How mode 3 gets drawn
+#[derive(Copy, Clone, PartialEq, Default)]
+#[cfg_attr(test, derive(Debug))]
+pub enum RenderMode {
+    /// Draw the whole line at the end of a fixed length mode 3. Fast, but mid-line register
+    /// writes only take effect on the next line.
+    #[default]
+    Scanline,
+    /// Run the pixel FIFO dot by dot: mode 3 varies in length like on hardware and mid-line
+    /// register writes land on the pixel they hit, at a good chunk of speed.
+    Fifo,
+}
+
+/// One OAM entry
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(Debug))]
+struct Sprite {
+    /// Screen Y + 16
+    y: u8,
+    /// Screen X + 8
+    x: u8,
+    tile: u8,
+    /// 7: BG/window colors 1-3 over the sprite, 6: Y flip, 5: X flip, 4: palette (OBP0/OBP1)
+    flags: u8,
+    /// Position in OAM, which breaks ties between sprites at the same X
+    oam_index: u8,
+}
+
+pub struct Ppu {
+    vram: Box<[u8; VRAM_SIZE]>,
+    oam: [u8; OAM_SIZE],
+
+    lcdc: u8,
+    /// Only the interrupt enable bits, the rest of STAT is derived
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    mode: Mode,
+    /// Dots into the current line
+    dot: u16,
+    render_mode: RenderMode,
+    /// The render mode the current line started mode 3 with, so switching mid-line is harmless
+    line_render_mode: RenderMode,
+    /// Mode 3 state for [`RenderMode::Fifo`]
+    fifo: PixelFifo,
+    /// The window has its own line counter, which only moves on lines the window was drawn on
+    window_line: u8,
+    /// Set once LY has matched WY this frame; the window can only show up after that
+    window_y_triggered: bool,
+    /// The OR of every STAT interrupt source, the interrupt fires when it goes from low to high
+    stat_line: bool,
+    /// The sprites the OAM scan picked for the current line
+    line_sprites: Vec<Sprite>,
+
+    /// Shades 0 (white) to 3 (black), row by row
+    framebuffer: Box<[u8; SCREEN_WIDTH * SCREEN_HEIGHT]>,
+    /// Number of frames finished, ticks over when VBlank starts
+    frame_count: u64,
+}
+
+impl Ppu {
+    /// A PPU with the LCD off
+    pub fn new() -> Self {
+        Self {
+            vram: Box::new([0; VRAM_SIZE]),
+            oam: [0; OAM_SIZE],
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            dot: 0,
+            render_mode: RenderMode::default(),
+            line_render_mode: RenderMode::default(),
+            fifo: PixelFifo::new(),
+            window_line: 0,
+            window_y_triggered: false,
+            stat_line: false,
+            line_sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+            framebuffer: Box::new([0; SCREEN_WIDTH * SCREEN_HEIGHT]),
+            frame_count: 0,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    /// Picks the renderer, taking effect from the next line's mode 3
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    /// The last finished frame (or the one being drawn, for lines not reached yet), one shade
+    /// 0-3 per pixel, row by row
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.framebuffer
+    }
+
+    /// How many frames were finished so far
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCDC_LCD_ENABLE != 0
+    }
+
+    /// Runs the PPU for `t_cycles` dots, raising VBlank and STAT interrupts along the way
+    pub fn tick(&mut self, t_cycles: u32, interrupts: &mut InterruptController) {
+        if !self.lcd_enabled() {
+            return;
+        }
+        for _ in 0..t_cycles {
+            self.step_dot(interrupts);
+        }
+    }
+
+    fn step_dot(&mut self, interrupts: &mut InterruptController) {
+        self.dot += 1;
+
+        match self.mode {
+            Mode::OamScan if self.dot == OAM_SCAN_DOTS => {
+                self.scan_oam();
+                self.mode = Mode::Drawing;
+                self.line_render_mode = self.render_mode;
+                if self.line_render_mode == RenderMode::Fifo {
+                    self.start_fifo_line();
+                }
+            }
+            Mode::Drawing => {
+                let line_done = match self.line_render_mode {
+                    RenderMode::Scanline => self.dot == OAM_SCAN_DOTS + DRAWING_DOTS,
+                    RenderMode::Fifo => self.fifo_dot(),
+                };
+                if line_done {
+                    self.finish_line();
+                    self.mode = Mode::HBlank;
+                }
+            }
+            Mode::HBlank | Mode::VBlank if self.dot == DOTS_PER_LINE => self.next_line(interrupts),
+            _ => {}
+        }
+
+        self.update_stat_line(interrupts);
+    }
+
+    /// Wraps up mode 3: the scanline renderer draws the whole line now, the FIFO is already done
+    fn finish_line(&mut self) {
+        match self.line_render_mode {
+            RenderMode::Scanline => self.render_scanline(),
+            RenderMode::Fifo => {
+                if self.fifo_window_drawn() {
+                    self.window_line += 1;
+                }
+            }
+        }
+    }
+
+    fn next_line(&mut self, interrupts: &mut InterruptController) {
+        self.dot = 0;
+        self.ly += 1;
+
+        if self.ly == SCREEN_HEIGHT as u8 {
+            self.mode = Mode::VBlank;
+            self.frame_count += 1;
+            interrupts.request(Interrupt::VBlank);
+        } else if self.ly == LINES_PER_FRAME {
+            self.ly = 0;
+            self.start_frame();
+        } else if self.ly < SCREEN_HEIGHT as u8 {
+            self.start_line();
+        }
+    }
+
+    fn start_frame(&mut self) {
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.start_line();
+    }
+
+    fn start_line(&mut self) {
+        self.mode = Mode::OamScan;
+        if self.ly == self.wy {
+            self.window_y_triggered = true;
+        }
+    }
+
+    /// Picks the first 10 sprites in OAM order that overlap the current line
+    fn scan_oam(&mut self) {
+        let height = self.sprite_height();
+        self.line_sprites.clear();
+        for (oam_index, entry) in self.oam.chunks_exact(4).enumerate() {
+            let sprite = Sprite { y: entry[0], x: entry[1], tile: entry[2], flags: entry[3], oam_index: oam_index as u8 };
+            let line = self.ly as u16 + 16;
+            if (sprite.y as u16..sprite.y as u16 + height as u16).contains(&line) {
+                self.line_sprites.push(sprite);
+                if self.line_sprites.len() == MAX_SPRITES_PER_LINE {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcdc & LCDC_SPRITE_SIZE != 0 { 16 } else { 8 }
+    }
+
+    /// BG/window tiles are either 0-255 from 0x8000, or -128-127 around 0x9000
+    fn bg_tile_address(&self, tile: u8) -> u16 {
+        if self.lcdc & LCDC_TILE_DATA != 0 {
+            tile as u16 * TILE_SIZE
+        } else {
+            (0x1000 + tile as i8 as i16 * TILE_SIZE as i16) as u16
+        }
+    }
+
+    /// The color number (0-3) of pixel (`col`, `row`) of the tile at `tile_address` (relative to 0x8000)
+    fn tile_color(&self, tile_address: u16, row: u8, col: u8) -> u8 {
+        let row_address = (tile_address + row as u16 * 2) as usize;
+        let low = self.vram[row_address];
+        let high = self.vram[row_address + 1];
+        let bit = 7 - col;
+        ((high >> bit) & 1) << 1 | ((low >> bit) & 1)
+    }
+
+    /// The color number of column `col` of `sprite` on the current line (0 is transparent)
+    fn sprite_color(&self, sprite: &Sprite, height: u8, col: u8) -> u8 {
+        let mut row = (self.ly + 16 - sprite.y) % height;
+        if sprite.flags & SPRITE_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+        let col = if sprite.flags & SPRITE_X_FLIP != 0 { 7 - col } else { col };
+
+        // 8x16 sprites ignore bit 0 of the tile number, the top half is the even tile
+        let tile = if height == 16 { sprite.tile & 0xFE } else { sprite.tile };
+        self.tile_color(tile as u16 * TILE_SIZE, row, col)
+    }
+
+    fn update_stat_line(&mut self, interrupts: &mut InterruptController) {
+        let line = (self.stat & STAT_LYC_INTERRUPT != 0 && self.ly == self.lyc)
+            || (self.stat & STAT_MODE_0_INTERRUPT != 0 && self.mode == Mode::HBlank)
+            || (self.stat & STAT_MODE_1_INTERRUPT != 0 && self.mode == Mode::VBlank)
+            || (self.stat & STAT_MODE_2_INTERRUPT != 0 && self.mode == Mode::OamScan);
+
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::LcdStat);
+        }
+        self.stat_line = line;
+    }
+
+    /// CPU read of VRAM, `address` being relative to 0x8000
+    pub fn read_vram(&self, address: u16) -> u8 {
+        if self.lcd_enabled() && self.mode == Mode::Drawing {
+            return BLOCKED_READ_VALUE;
+        }
+        self.vram[address as usize]
+    }
+
+    /// CPU write to VRAM, `address` being relative to 0x8000
+    pub fn write_vram(&mut self, address: u16, value: u8) {
+        if self.lcd_enabled() && self.mode == Mode::Drawing {
+            return;
+        }
+        self.vram[address as usize] = value;
+    }
+
+    fn oam_blocked(&self) -> bool {
+        self.lcd_enabled() && matches!(self.mode, Mode::OamScan | Mode::Drawing)
+    }
+
+    /// CPU read of OAM, `address` being relative to 0xFE00
+    pub fn read_oam(&self, address: u16) -> u8 {
+        if self.oam_blocked() {
+            return BLOCKED_READ_VALUE;
+        }
+        self.oam[address as usize]
+    }
+
+    /// CPU write to OAM, `address` being relative to 0xFE00
+    pub fn write_oam(&mut self, address: u16, value: u8) {
+        if self.oam_blocked() {
+            return;
+        }
+        self.oam[address as usize] = value;
+    }
+
+    /// OAM DMA write, which gets through no matter the mode
+    pub fn dma_write_oam(&mut self, index: u8, value: u8) {
+        self.oam[index as usize] = value;
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            LCDC_ADDRESS => self.lcdc,
+            STAT_ADDRESS => {
+                let coincidence = if self.ly == self.lyc { STAT_COINCIDENCE } else { 0 };
+                // the mode reads as 0 while the LCD is off, which it already is
+                STAT_UNUSED_BITS | self.stat | coincidence | self.mode as u8
+            }
+            SCY_ADDRESS => self.scy,
+            SCX_ADDRESS => self.scx,
+            LY_ADDRESS => self.ly,
+            LYC_ADDRESS => self.lyc,
+            BGP_ADDRESS => self.bgp,
+            OBP0_ADDRESS => self.obp0,
+            OBP1_ADDRESS => self.obp1,
+            WY_ADDRESS => self.wy,
+            WX_ADDRESS => self.wx,
+            _ => unreachable!("{address:#06X} is not a PPU register"),
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            LCDC_ADDRESS => self.write_lcdc(value),
+            STAT_ADDRESS => self.stat = value & STAT_WRITABLE_BITS,
+            SCY_ADDRESS => self.scy = value,
+            SCX_ADDRESS => self.scx = value,
+            // read only
+            LY_ADDRESS => {}
+            LYC_ADDRESS => self.lyc = value,
+            BGP_ADDRESS => self.bgp = value,
+            OBP0_ADDRESS => self.obp0 = value,
+            OBP1_ADDRESS => self.obp1 = value,
+            WY_ADDRESS => self.wy = value,
+            WX_ADDRESS => self.wx = value,
+            _ => unreachable!("{address:#06X} is not a PPU register"),
+        }
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = value;
+
+        match (was_enabled, self.lcd_enabled()) {
+            // switching off parks the PPU at the start of line 0
+            (true, false) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.mode = Mode::HBlank;
+            }
+            (false, true) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.start_frame();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Maps a color number to a shade through a palette register (2 bits per color, color 0 lowest)
+fn apply_palette(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0b11
+}
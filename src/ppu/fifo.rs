@@ -0,0 +1,247 @@
+//! The pixel FIFO renderer: models mode 3 dot by dot the way the hardware draws a line, so its
+//! length varies like the real thing and register writes made mid-line land on the right pixel.
+//!
+//! A background fetcher reads the tile map and tile data (2 dots per step) and pushes 8 pixels
+//! at a time into the background FIFO, which shifts one pixel out to the LCD every dot. Things
+//! that make mode 3 longer than the 172 dot minimum:
+//! - SCX % 8 pixels are shifted out and thrown away at the start of the line
+//! - the window starting throws away the FIFO and restarts the fetcher on the window map
+//! - every sprite stops the FIFO until the fetcher is done with its tile, then for 6 more dots
+//!   while the sprite's row is fetched into the sprite FIFO
+//!
+//! SCY, the tile map and tile data are read when the fetcher gets to them, and the palettes,
+//! LCDC.0 and LCDC.1 when a pixel reaches the LCD.
+
+use std::collections::VecDeque;
+
+use super::*;
+
+/// Each fetcher step takes 2 dots: tile number, tile data low, tile data high
+const FETCH_DOTS: u8 = 6;
+/// The fetcher's first fetch of a line is thrown away
+const DUMMY_FETCH_DOTS: u8 = 6;
+/// Fetching a sprite's row once the background fetcher is out of the way
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+#[derive(Copy, Clone)]
+struct SpritePixel {
+    /// 0 is transparent
+    color: u8,
+    /// The sprite's flags, for its palette and BG priority
+    flags: u8,
+}
+
+/// The state of mode 3 in FIFO mode, reset at the start of every line
+pub(super) struct PixelFifo {
+    background: VecDeque<u8>,
+    sprites: VecDeque<SpritePixel>,
+    /// Dots into the fetcher's current fetch, it pushes once this reaches `FETCH_DOTS`
+    fetch_dot: u8,
+    /// Dots left of the thrown away first fetch
+    dummy_dots: u8,
+    /// The tile column the fetcher is on, counting from the left edge of the BG (or the window)
+    fetcher_x: u8,
+    /// What the fetcher read so far for the tile it's on
+    tile_number: u8,
+    tile_row: u8,
+    tile_low: u8,
+    tile_high: u8,
+    /// Pixels still to be thrown away for SCX fine scrolling
+    discard: u8,
+    /// Pixels sent to the LCD so far this line
+    lcd_x: u8,
+    /// Whether the fetcher switched to the window this line
+    window_active: bool,
+    /// Which of `line_sprites` have been fetched, by index
+    fetched_sprites: u16,
+    /// Dots into the sprite fetch holding up the FIFO, if there's one
+    sprite_fetch: Option<(usize, u8)>,
+}
+
+impl PixelFifo {
+    pub(super) fn new() -> Self {
+        Self {
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            fetch_dot: 0,
+            dummy_dots: 0,
+            fetcher_x: 0,
+            tile_number: 0,
+            tile_row: 0,
+            tile_low: 0,
+            tile_high: 0,
+            discard: 0,
+            lcd_x: 0,
+            window_active: false,
+            fetched_sprites: 0,
+            sprite_fetch: None,
+        }
+    }
+}
+
+impl Ppu {
+    /// Gets the FIFO ready for a new line, when mode 3 starts
+    pub(super) fn start_fifo_line(&mut self) {
+        self.fifo = PixelFifo::new();
+        self.fifo.dummy_dots = DUMMY_FETCH_DOTS;
+        self.fifo.discard = self.scx % 8;
+    }
+
+    /// Runs one dot of mode 3 and returns whether the line is done
+    pub(super) fn fifo_dot(&mut self) -> bool {
+        if self.fifo.dummy_dots > 0 {
+            self.fifo.dummy_dots -= 1;
+            return false;
+        }
+
+        self.start_window_if_reached();
+
+        if self.fifo.sprite_fetch.is_none() {
+            self.fifo.sprite_fetch = self.next_sprite_to_fetch().map(|index| (index, 0));
+        }
+        if let Some((index, dots)) = self.fifo.sprite_fetch {
+            // the background fetcher gets to finish reading its tile first, and only then is the
+            // sprite fetched (the last step of both overlap)
+            if self.fifo.fetch_dot < FETCH_DOTS - 1 {
+                self.step_fetcher();
+                return false;
+            }
+            if dots + 1 < SPRITE_FETCH_DOTS {
+                self.fifo.sprite_fetch = Some((index, dots + 1));
+                return false;
+            }
+            // the FIFO gets going again on the same dot the sprite lands in it
+            self.fetch_sprite(index);
+            self.fifo.sprite_fetch = None;
+        }
+
+        self.step_fetcher();
+        // a push can line the next pixel up with a sprite, which has to be fetched before it goes out
+        if let Some(index) = self.next_sprite_to_fetch() {
+            self.fifo.sprite_fetch = Some((index, 0));
+            return false;
+        }
+        self.shift_out_pixel();
+        self.fifo.lcd_x as usize == SCREEN_WIDTH
+    }
+
+    /// Whether the window showed up on the line just drawn
+    pub(super) fn fifo_window_drawn(&self) -> bool {
+        self.fifo.window_active
+    }
+
+    /// Switches the fetcher to the window once the LCD reaches WX
+    fn start_window_if_reached(&mut self) {
+        let window_enabled = self.lcdc & LCDC_WINDOW_ENABLE != 0 && self.lcdc & LCDC_BG_WINDOW_ENABLE != 0;
+        if self.fifo.window_active || !window_enabled || !self.window_y_triggered {
+            return;
+        }
+        // the fine scroll discard happens before the window can start
+        if self.fifo.discard > 0 || self.fifo.lcd_x as u16 + 7 < self.wx as u16 {
+            return;
+        }
+        self.fifo.window_active = true;
+        self.fifo.background.clear();
+        self.fifo.fetch_dot = 0;
+        self.fifo.fetcher_x = 0;
+    }
+
+    /// The first sprite (in OAM order) that starts at the current pixel and hasn't been fetched
+    fn next_sprite_to_fetch(&self) -> Option<usize> {
+        // sprites only get fetched once there are background pixels to line them up against
+        if self.lcdc & LCDC_SPRITE_ENABLE == 0 || self.fifo.background.is_empty() || self.fifo.discard > 0 {
+            return None;
+        }
+        self.line_sprites.iter().enumerate().position(|(index, sprite)| {
+            self.fifo.fetched_sprites & (1 << index) == 0 && sprite.x as u16 <= self.fifo.lcd_x as u16 + 8
+        })
+    }
+
+    /// Mixes the row of `line_sprites[index]` into the sprite FIFO. Pixels already in there
+    /// came from sprites fetched earlier, which win, so only transparent ones get replaced.
+    fn fetch_sprite(&mut self, index: usize) {
+        self.fifo.fetched_sprites |= 1 << index;
+        let sprite = self.line_sprites[index];
+        let height = self.sprite_height();
+        // columns of sprites hanging off the left edge are already past
+        let first_col = (self.fifo.lcd_x as u16 + 8).saturating_sub(sprite.x as u16) as u8;
+
+        for col in first_col..8 {
+            let pixel = SpritePixel { color: self.sprite_color(&sprite, height, col), flags: sprite.flags };
+            let slot = (col - first_col) as usize;
+            match self.fifo.sprites.get_mut(slot) {
+                Some(existing) if existing.color == 0 => *existing = pixel,
+                Some(_) => {}
+                None => self.fifo.sprites.push_back(pixel),
+            }
+        }
+    }
+
+    /// Runs the background fetcher for a dot, pushing a row of 8 pixels once it's fetched one
+    /// and the FIFO is empty
+    fn step_fetcher(&mut self) {
+        if self.fifo.fetch_dot < FETCH_DOTS {
+            self.fifo.fetch_dot += 1;
+            match self.fifo.fetch_dot {
+                2 => self.fetch_tile_number(),
+                4 => self.fifo.tile_low = self.vram[self.fetch_row_address() as usize],
+                6 => self.fifo.tile_high = self.vram[self.fetch_row_address() as usize + 1],
+                _ => {}
+            }
+            return;
+        }
+        if !self.fifo.background.is_empty() {
+            return;
+        }
+
+        for bit in (0..8).rev() {
+            let color = ((self.fifo.tile_high >> bit) & 1) << 1 | ((self.fifo.tile_low >> bit) & 1);
+            self.fifo.background.push_back(color);
+        }
+        self.fifo.fetcher_x = self.fifo.fetcher_x.wrapping_add(1);
+        self.fifo.fetch_dot = 0;
+    }
+
+    /// Reads the tile number for the fetcher's current column (and picks the row of it to fetch)
+    fn fetch_tile_number(&mut self) {
+        let (map_bit, tile_x, y) = if self.fifo.window_active {
+            (LCDC_WINDOW_MAP, self.fifo.fetcher_x, self.window_line)
+        } else {
+            (LCDC_BG_MAP, (self.scx / 8).wrapping_add(self.fifo.fetcher_x), self.ly.wrapping_add(self.scy))
+        };
+        let map = if self.lcdc & map_bit != 0 { TILE_MAP_1 } else { TILE_MAP_0 };
+
+        let map_index = map + (y as u16 / 8) * TILE_MAP_WIDTH + (tile_x as u16 & 0x1F);
+        self.fifo.tile_number = self.vram[map_index as usize];
+        self.fifo.tile_row = y % 8;
+    }
+
+    /// Where the row being fetched starts, relative to 0x8000 (LCDC.4 is looked at every time)
+    fn fetch_row_address(&self) -> u16 {
+        self.bg_tile_address(self.fifo.tile_number) + self.fifo.tile_row as u16 * 2
+    }
+
+    /// Moves a pixel from the FIFOs to the LCD (or throws it away for fine scrolling)
+    fn shift_out_pixel(&mut self) {
+        let Some(bg_color) = self.fifo.background.pop_front() else {
+            return;
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+        let sprite = self.fifo.sprites.pop_front();
+
+        let bg_color = if self.lcdc & LCDC_BG_WINDOW_ENABLE != 0 { bg_color } else { 0 };
+        let mut shade = apply_palette(self.bgp, bg_color);
+        if let Some(sprite) = sprite.filter(|sprite| sprite.color != 0 && self.lcdc & LCDC_SPRITE_ENABLE != 0) {
+            if sprite.flags & SPRITE_BEHIND_BG == 0 || bg_color == 0 {
+                let palette = if sprite.flags & SPRITE_PALETTE != 0 { self.obp1 } else { self.obp0 };
+                shade = apply_palette(palette, sprite.color);
+            }
+        }
+
+        self.framebuffer[self.ly as usize * SCREEN_WIDTH + self.fifo.lcd_x as usize] = shade;
+        self.fifo.lcd_x += 1;
+    }
+}
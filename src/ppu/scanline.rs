@@ -0,0 +1,84 @@
+//! The scanline renderer: draws a whole line at once when mode 3 ends. Register writes made
+//! during mode 3 only show up from the next line on.
+
+use super::*;
+
+impl Ppu {
+    /// Draws the current line into the framebuffer
+    pub(super) fn render_scanline(&mut self) {
+        let line_start = self.ly as usize * SCREEN_WIDTH;
+        // the BG/window color numbers (before the palette), which decide sprite priority
+        let mut bg_colors = [0u8; SCREEN_WIDTH];
+
+        let bg_window_enabled = self.lcdc & LCDC_BG_WINDOW_ENABLE != 0;
+        let window_visible = bg_window_enabled
+            && self.lcdc & LCDC_WINDOW_ENABLE != 0
+            && self.window_y_triggered
+            && self.wx <= 166;
+
+        if bg_window_enabled {
+            for (x, color) in bg_colors.iter_mut().enumerate() {
+                *color = if window_visible && x as u8 + 7 >= self.wx {
+                    self.window_color(x as u8 + 7 - self.wx)
+                } else {
+                    self.background_color(x as u8)
+                };
+            }
+        }
+        if window_visible {
+            self.window_line += 1;
+        }
+
+        for (x, &color) in bg_colors.iter().enumerate() {
+            self.framebuffer[line_start + x] = apply_palette(self.bgp, color);
+        }
+
+        if self.lcdc & LCDC_SPRITE_ENABLE != 0 {
+            self.render_sprites(&bg_colors);
+        }
+    }
+
+    fn background_color(&self, x: u8) -> u8 {
+        let map = if self.lcdc & LCDC_BG_MAP != 0 { TILE_MAP_1 } else { TILE_MAP_0 };
+        self.tile_map_color(map, x.wrapping_add(self.scx), self.ly.wrapping_add(self.scy))
+    }
+
+    fn window_color(&self, x: u8) -> u8 {
+        let map = if self.lcdc & LCDC_WINDOW_MAP != 0 { TILE_MAP_1 } else { TILE_MAP_0 };
+        self.tile_map_color(map, x, self.window_line)
+    }
+
+    /// The color number of the pixel at (x, y) of the 256x256 picture the tile map at `map` makes
+    fn tile_map_color(&self, map: u16, x: u8, y: u8) -> u8 {
+        let map_index = map + (y as u16 / 8) * TILE_MAP_WIDTH + x as u16 / 8;
+        let tile = self.vram[map_index as usize];
+        self.tile_color(self.bg_tile_address(tile), y % 8, x % 8)
+    }
+
+    fn render_sprites(&mut self, bg_colors: &[u8; SCREEN_WIDTH]) {
+        let line_start = self.ly as usize * SCREEN_WIDTH;
+        let height = self.sprite_height();
+
+        // on DMG the sprite with the lowest X wins, then the one earliest in OAM
+        let mut sprites = self.line_sprites.clone();
+        sprites.sort_by_key(|sprite| (sprite.x, sprite.oam_index));
+
+        for (x, &bg_color) in bg_colors.iter().enumerate() {
+            let screen_x = x as u16 + 8;
+            let Some((sprite, color)) = sprites.iter()
+                .filter(|sprite| (sprite.x as u16..sprite.x as u16 + 8).contains(&screen_x))
+                .map(|sprite| (sprite, self.sprite_color(sprite, height, (screen_x - sprite.x as u16) as u8)))
+                .find(|&(_, color)| color != 0)
+            else {
+                continue;
+            };
+
+            // the winning sprite hides the ones under it even when the BG then covers it
+            if sprite.flags & SPRITE_BEHIND_BG != 0 && bg_color != 0 {
+                continue;
+            }
+            let palette = if sprite.flags & SPRITE_PALETTE != 0 { self.obp1 } else { self.obp0 };
+            self.framebuffer[line_start + x] = apply_palette(palette, color);
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use super::*;
+
+/// A tile whose every row is `low`/`high`, i.e. the same 8 color numbers on every row
+fn solid_rows(low: u8, high: u8) -> [u8; 16] {
+    let mut tile = [0; 16];
+    for row in tile.chunks_exact_mut(2) {
+        row[0] = low;
+        row[1] = high;
+    }
+    tile
+}
+
+/// Every pixel color 3
+const BLACK_TILE: [u8; 16] = [0xFF; 16];
+/// Every pixel color 1
+const LIGHT_TILE: [u8; 16] = [0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+
+/// Identity palette, so shades equal color numbers
+const IDENTITY_PALETTE: u8 = 0b11_10_01_00;
+
+/// A PPU with the LCD off and identity palettes, ready for VRAM/OAM to be filled in
+fn ppu() -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.write_register(BGP_ADDRESS, IDENTITY_PALETTE);
+    ppu.write_register(OBP0_ADDRESS, IDENTITY_PALETTE);
+    ppu.write_register(OBP1_ADDRESS, !IDENTITY_PALETTE);
+    ppu
+}
+
+fn write_tile(ppu: &mut Ppu, tile_address: u16, tile: [u8; 16]) {
+    for (offset, byte) in tile.into_iter().enumerate() {
+        ppu.write_vram(tile_address + offset as u16, byte);
+    }
+}
+
+fn write_sprite(ppu: &mut Ppu, index: u16, y: u8, x: u8, tile: u8, flags: u8) {
+    for (offset, byte) in [y, x, tile, flags].into_iter().enumerate() {
+        ppu.write_oam(index * 4 + offset as u16, byte);
+    }
+}
+
+/// Turns the LCD on with `lcdc` and draws a whole frame
+fn render_frame(ppu: &mut Ppu, lcdc: u8) -> InterruptController {
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | lcdc);
+    ppu.tick(DOTS_PER_FRAME, &mut interrupts);
+    interrupts
+}
+
+fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+    ppu.framebuffer()[y * SCREEN_WIDTH + x]
+}
+
+fn row(ppu: &Ppu, y: usize) -> &[u8] {
+    &ppu.framebuffer()[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]
+}
+
+#[test]
+fn mode_timing_within_a_line() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+
+    assert_eq!(Mode::OamScan, ppu.mode());
+    ppu.tick(79, &mut interrupts);
+    assert_eq!(Mode::OamScan, ppu.mode());
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(Mode::Drawing, ppu.mode());
+    ppu.tick(171, &mut interrupts);
+    assert_eq!(Mode::Drawing, ppu.mode());
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(Mode::HBlank, ppu.mode());
+    assert_eq!(0, ppu.ly());
+
+    ppu.tick(203, &mut interrupts);
+    assert_eq!(0, ppu.ly());
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(1, ppu.ly());
+    assert_eq!(Mode::OamScan, ppu.mode());
+    assert_eq!(0x82, ppu.read_register(STAT_ADDRESS) & 0x83);
+}
+
+#[test]
+fn vblank_and_frame_timing() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+
+    ppu.tick(144 * 456 - 1, &mut interrupts);
+    assert_eq!(143, ppu.ly());
+    assert!(!interrupts.has_pending());
+
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(144, ppu.ly());
+    assert_eq!(Mode::VBlank, ppu.mode());
+    assert_eq!(1, ppu.frame_count());
+    // IE is 0 so nothing is "pending", but the flag is up
+    assert_eq!(0x01, ppu_interrupt_flags(&interrupts));
+
+    ppu.tick(9 * 456, &mut interrupts);
+    assert_eq!(153, ppu.ly());
+    assert_eq!(Mode::VBlank, ppu.mode());
+
+    ppu.tick(456, &mut interrupts);
+    assert_eq!(0, ppu.ly());
+    assert_eq!(Mode::OamScan, ppu.mode());
+    assert_eq!(1, ppu.frame_count());
+}
+
+/// VBlank and STAT bits of IF
+fn ppu_interrupt_flags(interrupts: &InterruptController) -> u8 {
+    interrupts.read_flag() & 0b11
+}
+
+#[test]
+fn lyc_coincidence_and_stat_interrupt() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LYC_ADDRESS, 2);
+    ppu.write_register(STAT_ADDRESS, STAT_LYC_INTERRUPT);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+
+    ppu.tick(2 * 456 - 1, &mut interrupts);
+    assert_eq!(0, ppu.read_register(STAT_ADDRESS) & STAT_COINCIDENCE);
+    assert_eq!(0, ppu_interrupt_flags(&interrupts));
+
+    ppu.tick(1, &mut interrupts);
+    assert_ne!(0, ppu.read_register(STAT_ADDRESS) & STAT_COINCIDENCE);
+    assert_eq!(0b10, ppu_interrupt_flags(&interrupts));
+
+    // the line stays high for the whole line, so it doesn't fire again
+    interrupts.write_flag(0);
+    ppu.tick(455, &mut interrupts);
+    assert_eq!(0, ppu_interrupt_flags(&interrupts));
+
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(0, ppu.read_register(STAT_ADDRESS) & STAT_COINCIDENCE);
+}
+
+#[test]
+fn mode_stat_interrupts() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(STAT_ADDRESS, STAT_MODE_0_INTERRUPT);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+
+    ppu.tick(80 + 172 - 1, &mut interrupts);
+    assert_eq!(0, ppu_interrupt_flags(&interrupts));
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(0b10, ppu_interrupt_flags(&interrupts));
+
+    // mode 0 and mode 2 sources back to back keep the line high, so no second interrupt
+    interrupts.write_flag(0);
+    ppu.write_register(STAT_ADDRESS, STAT_MODE_0_INTERRUPT | STAT_MODE_2_INTERRUPT);
+    ppu.tick(204 + 80, &mut interrupts);
+    assert_eq!(0, ppu_interrupt_flags(&interrupts));
+
+    ppu.write_register(STAT_ADDRESS, STAT_MODE_1_INTERRUPT);
+    ppu.tick(143 * 456, &mut interrupts);
+    assert_eq!(0b11, ppu_interrupt_flags(&interrupts), "VBlank raises both");
+}
+
+#[test]
+fn lcd_off_parks_the_ppu() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+    ppu.tick(10 * 456 + 100, &mut interrupts);
+    assert_eq!(10, ppu.ly());
+
+    ppu.write_register(LCDC_ADDRESS, 0);
+    assert_eq!(0, ppu.ly());
+    assert_eq!(0x80, ppu.read_register(STAT_ADDRESS) & 0x83);
+
+    ppu.tick(DOTS_PER_FRAME, &mut interrupts);
+    assert_eq!(0, ppu.ly());
+    assert_eq!(Mode::HBlank, ppu.mode());
+
+    // LY is read only
+    ppu.write_register(LY_ADDRESS, 50);
+    assert_eq!(0, ppu.ly());
+}
+
+#[test]
+fn vram_and_oam_are_blocked_while_in_use() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.write_vram(0x0000, 0x11);
+    ppu.write_oam(0x0000, 0x22);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE);
+
+    // mode 2: only OAM
+    assert_eq!(0x11, ppu.read_vram(0x0000));
+    assert_eq!(0xFF, ppu.read_oam(0x0000));
+    ppu.write_oam(0x0000, 0x33);
+
+    // mode 3: both
+    ppu.tick(80, &mut interrupts);
+    assert_eq!(0xFF, ppu.read_vram(0x0000));
+    assert_eq!(0xFF, ppu.read_oam(0x0000));
+    ppu.write_vram(0x0000, 0x44);
+
+    // mode 0: neither, and the blocked writes never landed
+    ppu.tick(172, &mut interrupts);
+    assert_eq!(0x11, ppu.read_vram(0x0000));
+    assert_eq!(0x22, ppu.read_oam(0x0000));
+
+    // mode 1: neither
+    ppu.tick(456 * 144, &mut interrupts);
+    assert_eq!(Mode::VBlank, ppu.mode());
+    ppu.write_oam(0x0000, 0x55);
+    assert_eq!(0x55, ppu.read_oam(0x0000));
+}
+
+#[test]
+fn background_with_scrolling() {
+    let mut ppu = ppu();
+    // tile 1 has columns of colors 0,1,2,3,0,1,2,3
+    write_tile(&mut ppu, 0x0010, solid_rows(0b0101_0101, 0b0011_0011));
+    // tile 1 at map position (1, 0)
+    ppu.write_vram(TILE_MAP_0 + 1, 1);
+
+    render_frame(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA);
+
+    assert_eq!([0; 8], row(&ppu, 0)[0..8]);
+    assert_eq!([0, 1, 2, 3, 0, 1, 2, 3], row(&ppu, 0)[8..16]);
+    assert_eq!([0, 1, 2, 3, 0, 1, 2, 3], row(&ppu, 7)[8..16]);
+    assert_eq!([0; 8], row(&ppu, 8)[8..16]);
+
+    // scroll 3 right and 4 down (wrapping around the 256x256 map vertically)
+    ppu.write_register(SCX_ADDRESS, 3);
+    ppu.write_register(SCY_ADDRESS, 252);
+    ppu.tick(DOTS_PER_FRAME, &mut InterruptController::new());
+
+    assert_eq!([0; 8], row(&ppu, 3)[5..13]);
+    assert_eq!([0, 1, 2, 3, 0, 1, 2, 3], row(&ppu, 4)[5..13]);
+    assert_eq!([0, 1, 2, 3, 0, 1, 2, 3], row(&ppu, 11)[5..13]);
+}
+
+#[test]
+fn background_palette_and_enable() {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0000, LIGHT_TILE);
+    ppu.write_register(BGP_ADDRESS, 0b00_00_11_00);
+
+    render_frame(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA);
+    assert_eq!(3, pixel(&ppu, 100, 100));
+
+    // with LCDC bit 0 off the BG is blank (color 0)
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_TILE_DATA);
+    ppu.tick(DOTS_PER_FRAME, &mut InterruptController::new());
+    assert_eq!(0, pixel(&ppu, 100, 100));
+}
+
+#[test]
+fn signed_tile_data_addressing() {
+    let mut ppu = ppu();
+    // tile 0 in 0x8800 mode lives at 0x9000, tile 0xFF at 0x8FF0
+    write_tile(&mut ppu, 0x1000, LIGHT_TILE);
+    write_tile(&mut ppu, 0x0FF0, BLACK_TILE);
+    ppu.write_vram(TILE_MAP_0 + 1, 0xFF);
+
+    render_frame(&mut ppu, LCDC_BG_WINDOW_ENABLE);
+
+    assert_eq!(1, pixel(&ppu, 0, 0));
+    assert_eq!(3, pixel(&ppu, 8, 0));
+}
+
+#[test]
+fn window_covers_the_background_and_keeps_its_own_line_counter() {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0010, BLACK_TILE);
+    // window map at 0x9C00 is all tile 1
+    for index in 0..0x400 {
+        ppu.write_vram(TILE_MAP_1 + index, 1);
+    }
+    ppu.write_register(WY_ADDRESS, 10);
+    ppu.write_register(WX_ADDRESS, 7 + 20);
+
+    render_frame(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_ENABLE | LCDC_WINDOW_MAP);
+
+    assert_eq!(0, pixel(&ppu, 50, 9));
+    assert_eq!(0, pixel(&ppu, 19, 10));
+    assert_eq!(3, pixel(&ppu, 20, 10));
+    assert_eq!(3, pixel(&ppu, 159, 143));
+
+    // window off: no window, and its line counter doesn't move
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_MAP);
+    ppu.tick(DOTS_PER_FRAME, &mut InterruptController::new());
+    assert_eq!(0, pixel(&ppu, 20, 10));
+}
+
+#[test]
+fn window_line_counter_resumes_after_being_hidden() {
+    let mut ppu = ppu();
+    // window tile row 0 is color 3, rows 1-7 color 0, so only the first window line is dark
+    let mut tile = [0; 16];
+    tile[0] = 0xFF;
+    tile[1] = 0xFF;
+    write_tile(&mut ppu, 0x0010, tile);
+    for index in 0..0x400 {
+        ppu.write_vram(TILE_MAP_1 + index, 1);
+    }
+    ppu.write_register(WY_ADDRESS, 0);
+    ppu.write_register(WX_ADDRESS, 7);
+    let lcdc = LCDC_LCD_ENABLE | LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA | LCDC_WINDOW_MAP;
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, lcdc);
+
+    // hide the window for lines 0-4 by moving it off screen
+    ppu.write_register(WX_ADDRESS, 200);
+    ppu.write_register(LCDC_ADDRESS, lcdc | LCDC_WINDOW_ENABLE);
+    ppu.tick(5 * 456, &mut interrupts);
+    ppu.write_register(WX_ADDRESS, 7);
+    ppu.tick(DOTS_PER_FRAME - 5 * 456, &mut interrupts);
+
+    // line 5 is the window's line 0
+    assert_eq!(0, pixel(&ppu, 0, 4));
+    assert_eq!(3, pixel(&ppu, 0, 5));
+    assert_eq!(0, pixel(&ppu, 0, 6));
+    assert_eq!(3, pixel(&ppu, 0, 13));
+}
+
+#[test]
+fn sprites_draw_over_the_background() {
+    let mut ppu = ppu();
+    // tile 1: left half color 1, right half color 0 (transparent)
+    write_tile(&mut ppu, 0x0010, solid_rows(0xF0, 0x00));
+    write_sprite(&mut ppu, 0, 16 + 20, 8 + 30, 1, 0);
+
+    render_frame(&mut ppu, LCDC_SPRITE_ENABLE | LCDC_TILE_DATA);
+
+    assert_eq!(0, pixel(&ppu, 30, 19));
+    assert_eq!([1, 1, 1, 1, 0, 0, 0, 0], row(&ppu, 20)[30..38]);
+    assert_eq!([1, 1, 1, 1, 0, 0, 0, 0], row(&ppu, 27)[30..38]);
+    assert_eq!(0, pixel(&ppu, 30, 28));
+
+    // disabled sprites don't show
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_TILE_DATA);
+    ppu.tick(DOTS_PER_FRAME, &mut InterruptController::new());
+    assert_eq!(0, pixel(&ppu, 30, 20));
+}
+
+#[test]
+fn sprite_flips_palettes_and_tall_sprites() {
+    let mut ppu = ppu();
+    // tile 2: only the top row's leftmost pixel, color 1
+    let mut tile = [0; 16];
+    tile[0] = 0x80;
+    write_tile(&mut ppu, 0x0020, tile);
+    // tile 3 (the bottom half of 8x16 sprite 2): all color 2
+    write_tile(&mut ppu, 0x0030, solid_rows(0x00, 0xFF));
+
+    write_sprite(&mut ppu, 0, 16, 8, 2, SPRITE_X_FLIP);
+    write_sprite(&mut ppu, 1, 16 + 20, 8 + 20, 2, SPRITE_Y_FLIP);
+    write_sprite(&mut ppu, 2, 16 + 40, 8 + 40, 2, SPRITE_PALETTE);
+    render_frame(&mut ppu, LCDC_SPRITE_ENABLE);
+
+    assert_eq!(0, pixel(&ppu, 0, 0));
+    assert_eq!(1, pixel(&ppu, 7, 0));
+    assert_eq!(0, pixel(&ppu, 20, 20));
+    assert_eq!(1, pixel(&ppu, 20, 27));
+    // OBP1 is the inverted identity palette
+    assert_eq!(2, pixel(&ppu, 40, 40));
+
+    // 8x16: tile 3 (or 2, bit 0 is ignored) makes the top half and tile 3 the bottom half
+    ppu.write_register(LCDC_ADDRESS, 0);
+    write_sprite(&mut ppu, 0, 16 + 60, 8 + 60, 3, 0);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_SPRITE_ENABLE | LCDC_SPRITE_SIZE);
+    ppu.tick(DOTS_PER_FRAME, &mut InterruptController::new());
+    assert_eq!(1, pixel(&ppu, 60, 60));
+    assert_eq!(0, pixel(&ppu, 61, 60));
+    assert_eq!([2; 8], row(&ppu, 68)[60..68]);
+    assert_eq!([2; 8], row(&ppu, 75)[60..68]);
+    assert_eq!(0, pixel(&ppu, 60, 76));
+}
+
+#[test]
+fn sprite_priority() {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0010, LIGHT_TILE);
+    write_tile(&mut ppu, 0x0020, BLACK_TILE);
+    // color 2
+    write_tile(&mut ppu, 0x0030, solid_rows(0x00, 0xFF));
+
+    // the lower X wins even though it's later in OAM
+    write_sprite(&mut ppu, 0, 16, 8 + 4, 1, 0);
+    write_sprite(&mut ppu, 1, 16, 8, 2, 0);
+    // same X: earlier in OAM wins
+    write_sprite(&mut ppu, 2, 16 + 20, 8 + 20, 3, 0);
+    write_sprite(&mut ppu, 3, 16 + 20, 8 + 20, 1, 0);
+
+    render_frame(&mut ppu, LCDC_SPRITE_ENABLE);
+
+    assert_eq!([3, 3, 3, 3, 3, 3, 3, 3, 1, 1, 1, 1], row(&ppu, 0)[0..12]);
+    assert_eq!([2; 8], row(&ppu, 20)[20..28]);
+}
+
+#[test]
+fn sprites_behind_the_background() {
+    let mut ppu = ppu();
+    // BG: left tile color 0, everything else color 1
+    write_tile(&mut ppu, 0x0010, LIGHT_TILE);
+    write_tile(&mut ppu, 0x0020, BLACK_TILE);
+    for index in 1..0x400 {
+        ppu.write_vram(TILE_MAP_0 + index, 1);
+    }
+    write_sprite(&mut ppu, 0, 16, 8 + 4, 2, SPRITE_BEHIND_BG);
+
+    render_frame(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_SPRITE_ENABLE | LCDC_TILE_DATA);
+
+    // only shows over BG color 0
+    assert_eq!([0, 0, 0, 0, 3, 3, 3, 3, 1, 1, 1, 1], row(&ppu, 0)[0..12]);
+}
+
+#[test]
+fn ten_sprites_per_line() {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0010, BLACK_TILE);
+    for index in 0..12 {
+        write_sprite(&mut ppu, index, 16, 8 + 10 * index as u8, 1, 0);
+    }
+    // off the line, so it doesn't count towards the limit
+    write_sprite(&mut ppu, 12, 16 + 100, 8, 1, 0);
+
+    render_frame(&mut ppu, LCDC_SPRITE_ENABLE);
+
+    assert_eq!(3, pixel(&ppu, 90, 0));
+    assert_eq!(0, pixel(&ppu, 100, 0));
+    assert_eq!(0, pixel(&ppu, 110, 0));
+    assert_eq!(3, pixel(&ppu, 0, 100));
+}
+
+/// Turns the LCD on with the FIFO renderer and returns how many dots mode 3 of line 0 takes
+fn fifo_mode_3_length(ppu: &mut Ppu, lcdc: u8) -> u16 {
+    let mut interrupts = InterruptController::new();
+    ppu.set_render_mode(RenderMode::Fifo);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | lcdc);
+    ppu.tick(OAM_SCAN_DOTS as u32, &mut interrupts);
+    assert_eq!(Mode::Drawing, ppu.mode());
+
+    let mut dots = 0;
+    while ppu.mode() == Mode::Drawing {
+        ppu.tick(1, &mut interrupts);
+        dots += 1;
+    }
+    dots
+}
+
+/// A scene with scrolling, the window and overlapping sprites of every kind
+fn busy_scene() -> Ppu {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0010, solid_rows(0b0101_0101, 0b0011_0011));
+    write_tile(&mut ppu, 0x0020, LIGHT_TILE);
+    write_tile(&mut ppu, 0x0030, BLACK_TILE);
+    let mut diagonal = [0; 16];
+    for row in 0..8 {
+        diagonal[row * 2] = 0x80 >> row;
+        diagonal[row * 2 + 1] = 0xFF >> row;
+    }
+    write_tile(&mut ppu, 0x0040, diagonal);
+    for index in 0..0x400u16 {
+        ppu.write_vram(TILE_MAP_0 + index, (index % 5) as u8);
+        ppu.write_vram(TILE_MAP_1 + index, 4 - (index % 3) as u8);
+    }
+    ppu.write_register(SCX_ADDRESS, 13);
+    ppu.write_register(SCY_ADDRESS, 7);
+    ppu.write_register(WY_ADDRESS, 60);
+    ppu.write_register(WX_ADDRESS, 90);
+
+    let flags = [0, SPRITE_X_FLIP, SPRITE_Y_FLIP | SPRITE_PALETTE, SPRITE_BEHIND_BG, SPRITE_BEHIND_BG | SPRITE_X_FLIP];
+    for index in 0..40u16 {
+        let y = (index * 13 % 150) as u8 + 8;
+        let x = (index * 29 % 176) as u8;
+        write_sprite(&mut ppu, index, y, x, 1 + (index % 4) as u8, flags[index as usize % flags.len()]);
+    }
+    ppu
+}
+
+const BUSY_SCENE_LCDC: u8 = LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA | LCDC_SPRITE_ENABLE | LCDC_WINDOW_ENABLE | LCDC_WINDOW_MAP;
+
+#[test]
+fn fifo_draws_the_same_picture_as_the_scanline_renderer() {
+    let mut scanline = busy_scene();
+    render_frame(&mut scanline, BUSY_SCENE_LCDC);
+
+    let mut fifo = busy_scene();
+    fifo.set_render_mode(RenderMode::Fifo);
+    render_frame(&mut fifo, BUSY_SCENE_LCDC);
+
+    for y in 0..SCREEN_HEIGHT {
+        assert_eq!(row(&scanline, y), row(&fifo, y), "line {y} differs");
+    }
+}
+
+#[test]
+fn fifo_mode_3_is_172_dots_at_minimum() {
+    let mut ppu = ppu();
+
+    assert_eq!(DRAWING_DOTS, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE));
+}
+
+#[test]
+fn fifo_fine_scroll_penalty() {
+    for scx in [0, 1, 5, 7, 8, 13] {
+        let mut ppu = ppu();
+        ppu.write_register(SCX_ADDRESS, scx);
+
+        assert_eq!(DRAWING_DOTS + (scx % 8) as u16, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE), "SCX = {scx}");
+    }
+}
+
+#[test]
+fn fifo_sprite_penalty() {
+    // a sprite lined up with a tile waits for the whole fetch, so it's the worst case for one sprite
+    let mut ppu = ppu();
+    write_sprite(&mut ppu, 0, 16, 8, 0, 0);
+    assert_eq!(DRAWING_DOTS + 11, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_SPRITE_ENABLE));
+
+    // further into the tile the fetcher is closer to done
+    let mut ppu = self::ppu();
+    write_sprite(&mut ppu, 0, 16, 8 + 5, 0, 0);
+    assert_eq!(DRAWING_DOTS + 6, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_SPRITE_ENABLE));
+
+    // every sprite costs, even ones hanging off the left edge
+    let mut ppu = self::ppu();
+    write_sprite(&mut ppu, 0, 16, 0, 0, 0);
+    write_sprite(&mut ppu, 1, 16, 8 + 5, 0, 0);
+    let two_sprites = fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_SPRITE_ENABLE);
+    assert!(two_sprites >= DRAWING_DOTS + 12, "two sprites only took {two_sprites} dots");
+
+    // the same sprites with sprites off cost nothing
+    let mut ppu = self::ppu();
+    write_sprite(&mut ppu, 0, 16, 8, 0, 0);
+    assert_eq!(DRAWING_DOTS, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE));
+
+    // the 10 sprite limit caps it
+    let mut ppu = self::ppu();
+    for index in 0..20 {
+        write_sprite(&mut ppu, index, 16, 8, 0, 0);
+    }
+    let ten_sprites = fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_SPRITE_ENABLE);
+    assert!(ten_sprites <= DRAWING_DOTS + 10 * 11, "ten sprites took {ten_sprites} dots");
+}
+
+#[test]
+fn fifo_window_restart_penalty() {
+    let mut ppu = ppu();
+    ppu.write_register(WX_ADDRESS, 7 + 80);
+
+    assert_eq!(DRAWING_DOTS + 6, fifo_mode_3_length(&mut ppu, LCDC_BG_WINDOW_ENABLE | LCDC_WINDOW_ENABLE));
+}
+
+#[test]
+fn fifo_hblank_interrupt_moves_with_mode_3() {
+    let mut ppu = ppu();
+    let mut interrupts = InterruptController::new();
+    ppu.set_render_mode(RenderMode::Fifo);
+    ppu.write_register(SCX_ADDRESS, 3);
+    ppu.write_register(STAT_ADDRESS, STAT_MODE_0_INTERRUPT);
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_BG_WINDOW_ENABLE);
+
+    ppu.tick((OAM_SCAN_DOTS + DRAWING_DOTS + 2) as u32, &mut interrupts);
+    assert_eq!(0, ppu_interrupt_flags(&interrupts));
+    ppu.tick(1, &mut interrupts);
+    assert_eq!(0b10, ppu_interrupt_flags(&interrupts));
+}
+
+#[test]
+fn fifo_shows_mid_line_palette_writes() {
+    let mut ppu = ppu();
+    write_tile(&mut ppu, 0x0000, LIGHT_TILE);
+    ppu.set_render_mode(RenderMode::Fifo);
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA);
+
+    // the first pixel goes out on mode 3's 13th dot, so 50 dots later 50 pixels are out
+    ppu.tick(OAM_SCAN_DOTS as u32 + 12 + 50, &mut interrupts);
+    ppu.write_register(BGP_ADDRESS, 0b00_00_11_00);
+    ppu.tick(DOTS_PER_FRAME - (OAM_SCAN_DOTS as u32 + 12 + 50), &mut interrupts);
+
+    // line 0 got the split, every line after it only saw the new palette
+    assert_eq!([1; 50], row(&ppu, 0)[..50]);
+    assert_eq!([3; 110], row(&ppu, 0)[50..]);
+    assert_eq!([3; 160], row(&ppu, 1));
+
+    // the scanline renderer can only do it a line at a time
+    ppu.set_render_mode(RenderMode::Scanline);
+    ppu.write_register(BGP_ADDRESS, IDENTITY_PALETTE);
+    ppu.tick(OAM_SCAN_DOTS as u32 + 12 + 50, &mut interrupts);
+    ppu.write_register(BGP_ADDRESS, 0b00_00_11_00);
+    ppu.tick(DOTS_PER_FRAME - (OAM_SCAN_DOTS as u32 + 12 + 50), &mut interrupts);
+    assert_eq!([3; 160], row(&ppu, 0));
+}
+
+#[test]
+fn fifo_shows_mid_line_scroll_writes() {
+    let mut ppu = ppu();
+    // BG row 0 is tiles 0 (color 0), row 1 is tiles 1 (color 3), so SCY = 8 turns the line dark
+    write_tile(&mut ppu, 0x0010, BLACK_TILE);
+    for index in 32..64 {
+        ppu.write_vram(TILE_MAP_0 + index, 1);
+    }
+    ppu.set_render_mode(RenderMode::Fifo);
+    let mut interrupts = InterruptController::new();
+    ppu.write_register(LCDC_ADDRESS, LCDC_LCD_ENABLE | LCDC_BG_WINDOW_ENABLE | LCDC_TILE_DATA);
+
+    ppu.tick(OAM_SCAN_DOTS as u32 + 12 + 80, &mut interrupts);
+    ppu.write_register(SCY_ADDRESS, 8);
+    ppu.tick(456, &mut interrupts);
+
+    // SCY is read by the fetcher, which runs a tile ahead of the LCD
+    let line = row(&ppu, 0);
+    assert_eq!([0; 80], line[..80]);
+    assert_eq!(3, line[159]);
+    let first_dark = line.iter().position(|&shade| shade == 3).unwrap();
+    assert!((80..=96).contains(&first_dark), "SCY took effect at {first_dark}");
+}
@@ -0,0 +1,193 @@
+//! Running a ROM headless until something happens, and getting the results out of it: what CI
+//! uses to run test ROMs (see the `gameboy_emulator` binary).
+
+mod png;
+#[cfg(test)]
+mod tests;
+
+use std::io;
+
+use crate::cpu::InstructionBuildError;
+use crate::emulator::Emulator;
+use crate::ppu::{ SCREEN_WIDTH, SCREEN_HEIGHT };
+use crate::serial::{ CaptureEndpoint, SerialEndpoint };
+
+/// `LD B,B`, which does nothing, so test ROMs (and debuggers of other emulators) use it as a
+/// breakpoint
+const LD_B_B_OPCODE: u8 = 0x40;
+
+/// The gray each shade (0 white to 3 black) gets in screenshots
+const SHADE_GRAYS: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+/// When to stop running. Whichever comes first wins; with none of them it runs forever.
+#[derive(Clone, Default)]
+pub struct StopConditions {
+    /// Frames to run for. The PPU doesn't finish any while the LCD is off, so a ROM that keeps
+    /// it off needs `cycles` to stop.
+    pub frames: Option<u64>,
+    /// T-cycles to run for
+    pub cycles: Option<u64>,
+    /// Stop when PC gets here, before the instruction there runs
+    pub breakpoint: Option<u16>,
+    /// Stop once the serial output contains this
+    pub serial_pattern: Option<String>,
+    /// Stop at `LD B,B`, before it runs
+    pub software_breakpoint: bool,
+}
+
+impl StopConditions {
+    /// Whether anything but running out of frames or cycles would stop the run
+    pub fn has_targets(&self) -> bool {
+        self.breakpoint.is_some() || self.serial_pattern.is_some() || self.software_breakpoint
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum StopReason {
+    Frames,
+    Cycles,
+    Breakpoint,
+    SerialPattern,
+    SoftwareBreakpoint,
+}
+
+impl StopReason {
+    /// Whether the run stopped on a breakpoint or pattern rather than running out of time
+    pub fn hit_target(self) -> bool {
+        matches!(self, StopReason::Breakpoint | StopReason::SerialPattern | StopReason::SoftwareBreakpoint)
+    }
+}
+
+/// An emulator with everything it sends over serial captured
+pub struct Runner {
+    emulator: Emulator,
+    serial: CaptureEndpoint,
+}
+
+impl Runner {
+    pub fn new(mut emulator: Emulator) -> Self {
+        let serial = CaptureEndpoint::new();
+        emulator.set_serial_endpoint(serial.clone());
+        Self { emulator, serial }
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    /// Plugs `endpoint` into the other end of the link cable, with what this side sends still
+    /// captured for [`Runner::serial_output`] and the serial stop condition
+    pub fn set_link(&mut self, endpoint: impl SerialEndpoint + 'static) {
+        self.emulator.set_serial_endpoint(CapturedLink { capture: self.serial.clone(), link: Box::new(endpoint) });
+    }
+
+    /// Everything sent over serial so far
+    pub fn serial_output(&self) -> String {
+        self.serial.text()
+    }
+
+    /// Runs until one of `conditions` is met. Frames and cycles count from where this starts.
+    pub fn run(&mut self, conditions: &StopConditions) -> Result<StopReason, InstructionBuildError> {
+        let start_frame = self.emulator.frame_count();
+        let start_cycles = self.emulator.cpu().cycles();
+        let serial_pattern = conditions.serial_pattern.as_ref().map(String::as_bytes);
+        let mut serial_checked = self.serial.len();
+
+        loop {
+            if conditions.breakpoint == Some(self.emulator.cpu().pc()) {
+                return Ok(StopReason::Breakpoint);
+            }
+            if conditions.software_breakpoint && self.emulator.read_byte(self.emulator.cpu().pc()) == LD_B_B_OPCODE {
+                return Ok(StopReason::SoftwareBreakpoint);
+            }
+            if conditions.frames.is_some_and(|frames| self.emulator.frame_count() - start_frame >= frames) {
+                return Ok(StopReason::Frames);
+            }
+            if conditions.cycles.is_some_and(|cycles| self.emulator.cpu().cycles() - start_cycles >= cycles) {
+                return Ok(StopReason::Cycles);
+            }
+
+            self.emulator.step()?;
+
+            if let Some(pattern) = serial_pattern {
+                let found = self.serial.with_bytes(|output| {
+                    // only the new bytes can make a match, along with the end of what was checked
+                    let start = serial_checked.saturating_sub(pattern.len().saturating_sub(1));
+                    let new = output.len() != serial_checked;
+                    serial_checked = output.len();
+                    new && contains(&output[start..], pattern)
+                });
+                if found {
+                    return Ok(StopReason::SerialPattern);
+                }
+            }
+        }
+    }
+
+    /// The screen as a grayscale PNG
+    pub fn screenshot_png(&self) -> Vec<u8> {
+        let pixels: Vec<u8> = self.emulator.framebuffer().iter().map(|&shade| SHADE_GRAYS[shade as usize & 0b11]).collect();
+        png::encode_grayscale(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &pixels)
+    }
+
+    /// The CPU registers (and where the run is at) as a JSON object
+    pub fn registers_json(&self) -> String {
+        let cpu = self.emulator.cpu();
+        let registers = cpu.registers();
+        let bytes = [
+            ("a", registers.a), ("f", u8::from(registers.f)),
+            ("b", registers.b), ("c", registers.c),
+            ("d", registers.d), ("e", registers.e),
+            ("h", registers.h), ("l", registers.l),
+        ];
+
+        let mut fields: Vec<String> = bytes.iter().map(|(name, value)| format!("\"{name}\": {value}")).collect();
+        fields.push(format!("\"sp\": {}", cpu.sp()));
+        fields.push(format!("\"pc\": {}", cpu.pc()));
+        fields.push(format!("\"ime\": {}", cpu.ime()));
+        fields.push(format!("\"halted\": {}", cpu.is_halted()));
+        fields.push(format!("\"cycles\": {}", cpu.cycles()));
+        fields.push(format!("\"frames\": {}", self.emulator.frame_count()));
+        format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+    }
+}
+
+/// Whether `needle` is somewhere in `haystack`
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// A link cable with a capture tapped into it, noting every byte this side sends
+struct CapturedLink {
+    capture: CaptureEndpoint,
+    link: Box<dyn SerialEndpoint>,
+}
+
+impl SerialEndpoint for CapturedLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.capture.exchange(byte);
+        self.link.exchange(byte)
+    }
+
+    fn poll_external(&mut self, byte: u8) -> Option<u8> {
+        let received = self.link.poll_external(byte);
+        // the other side drove a transfer, which took SB along with it
+        if received.is_some() {
+            self.capture.exchange(byte);
+        }
+        received
+    }
+
+    fn tick(&mut self, t_cycles: u32) {
+        self.link.tick(t_cycles);
+    }
+
+    fn disconnected(&self) -> Option<&io::Error> {
+        self.link.disconnected()
+    }
+}
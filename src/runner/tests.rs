@@ -0,0 +1,129 @@
+use super::*;
+use crate::cartridge::{ with_valid_header, Cartridge };
+use crate::serial::LinkEndpoint;
+
+/// A ROM running `program` from 0x0100
+fn runner(program: &[u8]) -> Runner {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0100 + program.len()].copy_from_slice(program);
+    let rom = with_valid_header(rom, 0x00, 0x00);
+    Runner::new(Emulator::new(Cartridge::from_bytes(&rom).unwrap()))
+}
+
+/// `JP 0x0100`
+const SPIN: [u8; 3] = [0xC3, 0x00, 0x01];
+
+#[test]
+fn stops_after_frames_or_cycles() {
+    let mut runner = runner(&SPIN);
+
+    let reason = runner.run(&StopConditions { frames: Some(3), ..Default::default() }).unwrap();
+    assert_eq!(StopReason::Frames, reason);
+    assert_eq!(3, runner.emulator().frame_count());
+
+    let cycles = runner.emulator().cpu().cycles();
+    let reason = runner.run(&StopConditions { cycles: Some(1000), frames: Some(100), ..Default::default() }).unwrap();
+    assert_eq!(StopReason::Cycles, reason);
+    // JP takes 16 T-cycles
+    assert!((1000..1016).contains(&(runner.emulator().cpu().cycles() - cycles)));
+}
+
+#[test]
+fn stops_at_a_breakpoint() {
+    // LD A,0x42 ; JP 0x0100
+    let mut runner = runner(&[0x3E, 0x42, 0xC3, 0x00, 0x01]);
+
+    let reason = runner.run(&StopConditions { breakpoint: Some(0x0102), frames: Some(1), ..Default::default() }).unwrap();
+    assert_eq!(StopReason::Breakpoint, reason);
+    assert_eq!(0x0102, runner.emulator().cpu().pc());
+    assert_eq!(0x42, runner.emulator().cpu().registers().a);
+}
+
+#[test]
+fn stops_at_ld_b_b() {
+    // INC B ; LD B,B ; JP 0x0100
+    let mut runner = runner(&[0x04, 0x40, 0xC3, 0x00, 0x01]);
+
+    let reason = runner.run(&StopConditions { software_breakpoint: true, frames: Some(1), ..Default::default() }).unwrap();
+    assert_eq!(StopReason::SoftwareBreakpoint, reason);
+    assert_eq!(0x0101, runner.emulator().cpu().pc());
+    assert_eq!(0x01, runner.emulator().cpu().registers().b);
+}
+
+#[test]
+fn stops_on_serial_output() {
+    // LD A,'!' ; LDH (0x01),A ; LD A,0x81 ; LDH (0x02),A ; JP 0x0108
+    let mut runner = runner(&[0x3E, b'!', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xC3, 0x08, 0x01]);
+
+    let conditions = StopConditions { serial_pattern: Some("!".into()), frames: Some(10), ..Default::default() };
+    assert_eq!(StopReason::SerialPattern, runner.run(&conditions).unwrap());
+    assert_eq!("!", runner.serial_output());
+}
+
+#[test]
+fn serial_patterns_can_straddle_what_was_already_sent() {
+    // LD A,'K' ; LDH (0x01),A ; LD A,0x81 ; LDH (0x02),A ; JP 0x0108
+    let mut runner = runner(&[0x3E, b'K', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xC3, 0x08, 0x01]);
+    runner.serial.clone().exchange(b'O');
+
+    let conditions = StopConditions { serial_pattern: Some("OK".into()), frames: Some(10), ..Default::default() };
+    assert_eq!(StopReason::SerialPattern, runner.run(&conditions).unwrap());
+    assert_eq!("OK", runner.serial_output());
+}
+
+#[test]
+fn linked_runners_still_capture_what_they_send() {
+    // LD C,Cs so the other side is listening by then ; LD A,'!' ; LDH (0x01),A ; LD A,0x81 ; LDH (0x02),A ; JP 0x0110
+    let mut driving = runner(&[0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x49, 0x3E, b'!', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xC3, 0x10, 0x01]);
+    // LD A,'?' ; LDH (0x01),A ; LD A,0x80 ; LDH (0x02),A ; JP 0x0108
+    let mut listening = runner(&[0x3E, b'?', 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02, 0xC3, 0x08, 0x01]);
+    let (driving_end, listening_end) = LinkEndpoint::pair();
+    driving.set_link(driving_end);
+    listening.set_link(listening_end);
+
+    let step = StopConditions { cycles: Some(4), ..Default::default() };
+    for _ in 0..2000 {
+        driving.run(&step).unwrap();
+        listening.run(&step).unwrap();
+    }
+    assert_eq!("!", driving.serial_output());
+    assert_eq!("?", listening.serial_output());
+    assert_eq!(b'?', driving.emulator().cpu().bus().read_byte(0xFF01));
+    assert_eq!(b'!', listening.emulator().cpu().bus().read_byte(0xFF01));
+}
+
+#[test]
+fn registers_json_has_every_register() {
+    let runner = runner(&SPIN);
+    let json = runner.registers_json();
+
+    for field in ["\"a\": 1,", "\"f\": 176,", "\"c\": 19,", "\"e\": 216,", "\"l\": 77,", "\"sp\": 65534,", "\"pc\": 256,", "\"ime\": false,", "\"frames\": 0"] {
+        assert!(json.contains(field), "{field} missing from {json}");
+    }
+    assert!(json.starts_with('{') && json.trim_end().ends_with('}'));
+}
+
+#[test]
+fn screenshot_is_a_well_formed_png() {
+    let png = runner(&SPIN).screenshot_png();
+
+    assert_eq!(b"\x89PNG\r\n\x1A\n", &png[..8]);
+    // IHDR: 160x144, 8-bit grayscale
+    assert_eq!(b"IHDR", &png[12..16]);
+    assert_eq!([0, 0, 0, 160, 0, 0, 0, 144, 8, 0, 0, 0, 0], png[16..29]);
+    assert_eq!(png::crc32(&png[12..29]).to_be_bytes(), png[29..33]);
+    assert_eq!(b"IEND", &png[png.len() - 8..png.len() - 4]);
+
+    // the image data is stored as is, so the rows are right there: a filter byte and 160 pixels
+    let idat_length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+    let zlib = &png[41..41 + idat_length];
+    let raw = &zlib[2 + 5..zlib.len() - 4];
+    assert_eq!(144 * 161, raw.len());
+    assert_eq!(png::adler32(raw).to_be_bytes(), zlib[zlib.len() - 4..]);
+}
+
+#[test]
+fn checksums_match_known_values() {
+    assert_eq!(0xCBF4_3926, png::crc32(b"123456789"));
+    assert_eq!(0x11E6_0398, png::adler32(b"Wikipedia"));
+}
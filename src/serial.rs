@@ -0,0 +1,131 @@
+//! The serial port, i.e. the link cable: SB (0xFF01) and SC (0xFF02).
+//!
+//! ```text
+//! 0xFF01 | SB | the byte being shifted out, with the incoming one shifted in bit by bit
+//! 0xFF02 | SC | 7: transfer start/running, 0: clock (1: this side's 8192 Hz clock, 0: the other side's)
+//! ```
+//!
+//! With the internal clock a transfer takes 8 bits of 512 T-cycles each, and then the serial
+//! interrupt is requested and SC.7 goes back to 0. With the external clock it waits (forever if
+//! need be) for the other side to drive the transfer.
+//!
+//! Whatever is on the other end of the cable is a [`SerialEndpoint`]. Bytes are handed over
+//! whole at the start of a transfer, the bit by bit shifting only matters for timing and for
+//! what SB reads in the middle of a transfer. Another emulator can be on the other end, in
+//! the same process ([`LinkEndpoint`]) or over a socket ([`SocketEndpoint`]).
+
+mod endpoint;
+mod socket;
+#[cfg(test)]
+mod tests;
+
+pub use endpoint::{ CaptureEndpoint, LinkEndpoint, NullEndpoint, SerialEndpoint };
+pub use socket::SocketEndpoint;
+
+use std::io;
+
+use crate::interrupt::{ Interrupt, InterruptController };
+
+pub const SB_ADDRESS: u16 = 0xFF01;
+pub const SC_ADDRESS: u16 = 0xFF02;
+
+const SC_TRANSFER: u8 = 1 << 7;
+const SC_INTERNAL_CLOCK: u8 = 1 << 0;
+/// Bits 6-1 of SC aren't wired to anything
+const SC_UNUSED_BITS: u8 = 0b0111_1110;
+
+/// The internal clock runs at 8192 Hz
+const T_CYCLES_PER_BIT: u32 = 512;
+
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+    /// What the other side sent, shifted into SB as the transfer goes
+    incoming: u8,
+    bits_left: u8,
+    /// T-cycles into the current bit
+    bit_cycles: u32,
+    endpoint: Box<dyn SerialEndpoint>,
+}
+
+impl Serial {
+    /// A serial port with nothing plugged in
+    pub fn new() -> Self {
+        Self { sb: 0, sc: 0, incoming: 0, bits_left: 0, bit_cycles: 0, endpoint: Box::new(NullEndpoint) }
+    }
+
+    /// Plugs `endpoint` into the other end of the cable
+    pub fn set_endpoint(&mut self, endpoint: impl SerialEndpoint + 'static) {
+        self.endpoint = Box::new(endpoint);
+    }
+
+    /// What unplugged the cable, see [`SerialEndpoint::disconnected`]
+    pub fn link_error(&self) -> Option<&io::Error> {
+        self.endpoint.disconnected()
+    }
+
+    fn transferring(&self) -> bool {
+        self.sc & SC_TRANSFER != 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.sc & SC_INTERNAL_CLOCK != 0
+    }
+
+    pub fn tick(&mut self, t_cycles: u32, interrupts: &mut InterruptController) {
+        self.step_transfer(t_cycles, interrupts);
+        self.endpoint.tick(t_cycles);
+    }
+
+    fn step_transfer(&mut self, t_cycles: u32, interrupts: &mut InterruptController) {
+        if !self.transferring() {
+            return;
+        }
+        if !self.internal_clock() {
+            if let Some(byte) = self.endpoint.poll_external(self.sb) {
+                self.sb = byte;
+                self.finish_transfer(interrupts);
+            }
+            return;
+        }
+
+        self.bit_cycles += t_cycles;
+        while self.bit_cycles >= T_CYCLES_PER_BIT && self.transferring() {
+            self.bit_cycles -= T_CYCLES_PER_BIT;
+            self.sb = self.sb << 1 | self.incoming >> 7;
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+            if self.bits_left == 0 {
+                self.finish_transfer(interrupts);
+            }
+        }
+    }
+
+    fn finish_transfer(&mut self, interrupts: &mut InterruptController) {
+        self.sc &= !SC_TRANSFER;
+        interrupts.request(Interrupt::Serial);
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            SB_ADDRESS => self.sb,
+            SC_ADDRESS => self.sc | SC_UNUSED_BITS,
+            _ => unreachable!("{address:#06X} is not a serial register"),
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            SB_ADDRESS => self.sb = value,
+            SC_ADDRESS => {
+                self.sc = value & !SC_UNUSED_BITS;
+                if self.transferring() && self.internal_clock() {
+                    self.incoming = self.endpoint.exchange(self.sb);
+                    self.bits_left = 8;
+                    self.bit_cycles = 0;
+                }
+            }
+            _ => unreachable!("{address:#06X} is not a serial register"),
+        }
+    }
+}
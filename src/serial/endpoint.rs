@@ -0,0 +1,130 @@
+//! What can be on the other end of the link cable.
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+/// What a disconnected cable reads, the line is pulled up
+pub(super) const DISCONNECTED_BYTE: u8 = 0xFF;
+
+/// The device on the other end of the link cable
+pub trait SerialEndpoint {
+    /// This side drives the clock and sends `byte`. Returns the byte the other side sends back.
+    fn exchange(&mut self, byte: u8) -> u8;
+
+    /// This side waits on the other side's clock with `byte` in SB. Returns what the other side
+    /// sent once it drove a transfer, which also takes `byte`.
+    fn poll_external(&mut self, byte: u8) -> Option<u8> {
+        let _ = byte;
+        None
+    }
+
+    /// Called every tick with the emulated time that passed (after `poll_external`), for
+    /// endpoints that keep in step with something outside the emulator
+    fn tick(&mut self, t_cycles: u32) {
+        let _ = t_cycles;
+    }
+
+    /// What unplugged the cable, for endpoints that can lose the other side
+    fn disconnected(&self) -> Option<&io::Error> {
+        None
+    }
+}
+
+/// No cable plugged in: every byte comes back as 0xFF, and nothing ever drives the clock
+pub struct NullEndpoint;
+
+impl SerialEndpoint for NullEndpoint {
+    fn exchange(&mut self, _byte: u8) -> u8 {
+        DISCONNECTED_BYTE
+    }
+}
+
+/// Collects everything sent to it, e.g. the results test ROMs print over serial. Clones share
+/// the output, so keep one around to read it after plugging the other in.
+#[derive(Clone, Default)]
+pub struct CaptureEndpoint {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CaptureEndpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+
+    /// Runs `f` on the output so far without copying it
+    pub fn with_bytes<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(&self.output.borrow())
+    }
+
+    pub fn len(&self) -> usize {
+        self.output.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.output.borrow().is_empty()
+    }
+
+    /// The output so far as text, with anything that isn't UTF-8 replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.output.borrow()).into_owned()
+    }
+}
+
+impl SerialEndpoint for CaptureEndpoint {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.output.borrow_mut().push(byte);
+        DISCONNECTED_BYTE
+    }
+}
+
+/// One side of a cable between two emulators in the same process
+#[derive(Default)]
+struct LinkSide {
+    /// SB of this side while it waits on the other side's clock
+    waiting: Option<u8>,
+    /// What the other side sent in a transfer it drove, not picked up yet
+    received: Option<u8>,
+}
+
+/// One end of a link cable between two emulator instances, see [`LinkEndpoint::pair`]
+pub struct LinkEndpoint {
+    sides: Rc<RefCell<[LinkSide; 2]>>,
+    /// Which of `sides` this end is
+    side: usize,
+}
+
+impl LinkEndpoint {
+    /// Both ends of a new cable, one for each emulator
+    pub fn pair() -> (Self, Self) {
+        let sides = Rc::new(RefCell::new([LinkSide::default(), LinkSide::default()]));
+        (Self { sides: Rc::clone(&sides), side: 0 }, Self { sides, side: 1 })
+    }
+}
+
+impl SerialEndpoint for LinkEndpoint {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut sides = self.sides.borrow_mut();
+        let other = &mut sides[1 - self.side];
+        match other.waiting.take() {
+            Some(reply) => {
+                other.received = Some(byte);
+                reply
+            }
+            // the other side isn't listening, so it doesn't shift anything out either
+            None => DISCONNECTED_BYTE,
+        }
+    }
+
+    fn poll_external(&mut self, byte: u8) -> Option<u8> {
+        let mut sides = self.sides.borrow_mut();
+        let this = &mut sides[self.side];
+        let received = this.received.take();
+        this.waiting = if received.is_some() { None } else { Some(byte) };
+        received
+    }
+}
@@ -0,0 +1,170 @@
+//! A link cable over a TCP or Unix domain socket, between two emulator processes (or two
+//! instances in one process, on their own threads).
+//!
+//! To keep transfers deterministic no matter how fast either side runs in real time, the two
+//! sides run in lockstep: every `QUANTUM` T-cycles of emulated time both send a sync message and
+//! wait for the other's. A sync message says whether this side is listening on the external
+//! clock (and with what in SB), and what byte it sent in a transfer it drove in the quantum that
+//! just ended, if any:
+//! ```text
+//! byte 0 | flags: bit 0 a transfer was driven, bit 1 listening
+//! byte 1 | the byte the transfer sent (0xFF without one)
+//! byte 2 | SB while listening (0xFF when not)
+//! ```
+//! A transfer driven in quantum N gets back what the other side was listening with at the sync
+//! before it, and lands on the other side at the sync after it (only if that side is still
+//! listening then). Both only depend on the emulated state at sync points, never on timing.
+//!
+//! The connection starts with both sides sending `HANDSHAKE`, and if anything goes wrong with
+//! the socket later the cable counts as unplugged from then on (see `disconnected`).
+
+use std::io::{ self, Read, Write };
+use std::net::{ TcpListener, TcpStream, ToSocketAddrs };
+#[cfg(unix)]
+use std::os::unix::net::{ UnixListener, UnixStream };
+#[cfg(unix)]
+use std::path::Path;
+
+use super::endpoint::{ SerialEndpoint, DISCONNECTED_BYTE };
+
+/// Magic and protocol version both sides send when connecting
+const HANDSHAKE: [u8; 5] = *b"GBLK\x01";
+/// Emulated T-cycles between syncs, about a thousand a second
+const QUANTUM: u32 = 4096;
+const SYNC_MESSAGE_SIZE: usize = 3;
+const FLAG_TRANSFER: u8 = 1 << 0;
+const FLAG_LISTENING: u8 = 1 << 1;
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// One end of a link cable over a socket
+pub struct SocketEndpoint {
+    /// `None` once the connection broke
+    stream: Option<Box<dyn Stream>>,
+    /// What broke it
+    disconnected: Option<io::Error>,
+    /// Emulated T-cycles since the last sync
+    cycles: u32,
+    /// SB if this side was listening on the external clock this tick
+    listening: Option<u8>,
+    /// What the other side was listening with at the last sync, for a transfer driven from here
+    peer_listening: Option<u8>,
+    /// The byte of a transfer driven from here since the last sync
+    sent: Option<u8>,
+    /// A byte the other side sent, waiting for `poll_external` to pick it up
+    received: Option<u8>,
+}
+
+impl SocketEndpoint {
+    /// Links over an already connected stream, after the handshake with the other side
+    pub fn new(stream: impl Read + Write + 'static) -> io::Result<Self> {
+        let mut stream = stream;
+        stream.write_all(&HANDSHAKE)?;
+        stream.flush()?;
+        let mut handshake = [0; HANDSHAKE.len()];
+        stream.read_exact(&mut handshake)?;
+        if handshake != HANDSHAKE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the other side isn't a link cable (or speaks another version)"));
+        }
+
+        Ok(Self {
+            stream: Some(Box::new(stream)),
+            disconnected: None,
+            cycles: 0,
+            listening: None,
+            peer_listening: None,
+            sent: None,
+            received: None,
+        })
+    }
+
+    /// Connects to the other side listening at `address`
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        // sync messages are tiny and each one waits for an answer
+        stream.set_nodelay(true)?;
+        Self::new(stream)
+    }
+
+    /// Waits for the other side to connect on `listener`
+    pub fn accept_tcp(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Self::new(stream)
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(UnixStream::connect(path)?)
+    }
+
+    #[cfg(unix)]
+    pub fn accept_unix(listener: &UnixListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Swaps sync messages with the other side, waiting for its one
+    fn sync(&mut self) {
+        let message = [
+            if self.sent.is_some() { FLAG_TRANSFER } else { 0 } | if self.listening.is_some() { FLAG_LISTENING } else { 0 },
+            self.sent.unwrap_or(DISCONNECTED_BYTE),
+            self.listening.unwrap_or(DISCONNECTED_BYTE),
+        ];
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+
+        let mut reply = [0; SYNC_MESSAGE_SIZE];
+        let result = stream.write_all(&message)
+            .and_then(|()| stream.flush())
+            .and_then(|()| stream.read_exact(&mut reply));
+        if let Err(err) = result {
+            self.stream = None;
+            self.disconnected = Some(err);
+            self.peer_listening = None;
+            return;
+        }
+
+        // a transfer we sent just now uses up what the other side was listening with
+        self.peer_listening = (reply[0] & FLAG_LISTENING != 0 && self.sent.is_none()).then_some(reply[2]);
+        if reply[0] & FLAG_TRANSFER != 0 && self.listening.is_some() {
+            self.received = Some(reply[1]);
+        }
+        self.sent = None;
+    }
+}
+
+impl SerialEndpoint for SocketEndpoint {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        // two transfers in one quantum: the second one finds nobody listening
+        if self.sent.is_some() {
+            return DISCONNECTED_BYTE;
+        }
+        self.sent = Some(byte);
+        self.peer_listening.take().unwrap_or(DISCONNECTED_BYTE)
+    }
+
+    fn poll_external(&mut self, byte: u8) -> Option<u8> {
+        self.listening = Some(byte);
+        self.received.take()
+    }
+
+    fn tick(&mut self, t_cycles: u32) {
+        self.cycles += t_cycles;
+        if self.cycles >= QUANTUM {
+            self.cycles -= QUANTUM;
+            self.sync();
+        }
+        self.listening = None;
+    }
+
+    fn disconnected(&self) -> Option<&io::Error> {
+        self.disconnected.as_ref()
+    }
+}
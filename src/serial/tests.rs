@@ -0,0 +1,171 @@
+use super::*;
+
+/// Starts a transfer of `byte` like a game would
+fn send(serial: &mut Serial, byte: u8, control: u8) {
+    serial.write_register(SB_ADDRESS, byte);
+    serial.write_register(SC_ADDRESS, control);
+}
+
+#[test]
+fn internal_clock_transfer_takes_8_bits_then_interrupts() {
+    let mut serial = Serial::new();
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    send(&mut serial, 0x0F, 0x81);
+    assert_eq!(0xFF, serial.read_register(SC_ADDRESS));
+
+    serial.tick(512 * 4, &mut interrupts);
+    assert_eq!(0xF0 | 0x0F, serial.read_register(SB_ADDRESS), "half of the 0xFF coming in is shifted in");
+    serial.tick(512 * 4 - 4, &mut interrupts);
+    assert!(!interrupts.has_pending());
+
+    serial.tick(4, &mut interrupts);
+    assert_eq!(0xFF, serial.read_register(SB_ADDRESS), "nothing plugged in reads 0xFF");
+    assert_eq!(0x7F, serial.read_register(SC_ADDRESS));
+    assert_eq!(Some(Interrupt::Serial), interrupts.highest_pending());
+}
+
+#[test]
+fn capture_endpoint_collects_the_output() {
+    let mut serial = Serial::new();
+    let mut interrupts = InterruptController::new();
+    let capture = CaptureEndpoint::new();
+    serial.set_endpoint(capture.clone());
+
+    for byte in b"Passed" {
+        send(&mut serial, *byte, 0x81);
+        serial.tick(512 * 8, &mut interrupts);
+    }
+    assert_eq!("Passed", capture.text());
+    assert_eq!(6, capture.len());
+    assert!(capture.with_bytes(|output| output.ends_with(b"ed")));
+}
+
+#[test]
+fn external_clock_waits_for_the_other_side() {
+    let mut serial = Serial::new();
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    send(&mut serial, 0x42, 0x80);
+
+    serial.tick(512 * 100, &mut interrupts);
+    assert_eq!(0xFE, serial.read_register(SC_ADDRESS), "still waiting, nobody drives the clock");
+    assert_eq!(0x42, serial.read_register(SB_ADDRESS));
+    assert!(!interrupts.has_pending());
+}
+
+#[test]
+fn linked_serial_ports_swap_bytes() {
+    let (cable_a, cable_b) = LinkEndpoint::pair();
+    let (mut a, mut b) = (Serial::new(), Serial::new());
+    a.set_endpoint(cable_a);
+    b.set_endpoint(cable_b);
+    let (mut interrupts_a, mut interrupts_b) = (InterruptController::new(), InterruptController::new());
+    interrupts_a.write_enable(0xFF);
+    interrupts_b.write_enable(0xFF);
+
+    // B listens on the external clock first, then A drives the transfer
+    send(&mut b, 0xB0, 0x80);
+    b.tick(4, &mut interrupts_b);
+    send(&mut a, 0xA0, 0x81);
+    for _ in 0..512 * 8 / 4 {
+        a.tick(4, &mut interrupts_a);
+        b.tick(4, &mut interrupts_b);
+    }
+
+    assert_eq!(0xB0, a.read_register(SB_ADDRESS));
+    assert_eq!(0xA0, b.read_register(SB_ADDRESS));
+    assert_eq!(Some(Interrupt::Serial), interrupts_a.highest_pending());
+    assert_eq!(Some(Interrupt::Serial), interrupts_b.highest_pending());
+}
+
+#[test]
+fn link_with_nobody_listening_reads_0xff() {
+    let (cable_a, _cable_b) = LinkEndpoint::pair();
+    let mut a = Serial::new();
+    a.set_endpoint(cable_a);
+    let mut interrupts = InterruptController::new();
+
+    send(&mut a, 0xA0, 0x81);
+    a.tick(512 * 8, &mut interrupts);
+    assert_eq!(0xFF, a.read_register(SB_ADDRESS));
+}
+
+/// What one side of a socket link does: `control` is written to SC after `start_after` T-cycles,
+/// with `byte` in SB, and then the whole session runs for 16 syncs. Returns SB and SC at the end
+/// and whether the serial interrupt fired.
+fn socket_session(endpoint: SocketEndpoint, byte: u8, control: u8, start_after: u32) -> (u8, u8, bool) {
+    let mut serial = Serial::new();
+    serial.set_endpoint(endpoint);
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+
+    serial.write_register(SB_ADDRESS, byte);
+    for cycle in (0..4096 * 16).step_by(4) {
+        if cycle == start_after {
+            serial.write_register(SC_ADDRESS, control);
+        }
+        serial.tick(4, &mut interrupts);
+    }
+    (serial.read_register(SB_ADDRESS), serial.read_register(SC_ADDRESS), interrupts.has_pending())
+}
+
+#[test]
+fn tcp_link_swaps_bytes_over_loopback() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // each emulator on its own thread, like two processes would be
+    let listening_side = std::thread::spawn(move || {
+        socket_session(SocketEndpoint::accept_tcp(&listener).unwrap(), 0xB0, 0x80, 0)
+    });
+    let driving_side = std::thread::spawn(move || {
+        socket_session(SocketEndpoint::connect_tcp(address).unwrap(), 0xA0, 0x81, 4096 * 3)
+    });
+
+    assert_eq!((0xB0, 0x7F, true), driving_side.join().unwrap());
+    assert_eq!((0xA0, 0x7E, true), listening_side.join().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_link_before_the_other_side_listens_reads_0xff() {
+    let (stream_a, stream_b) = std::os::unix::net::UnixStream::pair().unwrap();
+
+    // A drives a transfer before B starts listening, so nobody answers, every run
+    for _ in 0..3 {
+        let (stream_a, stream_b) = (stream_a.try_clone().unwrap(), stream_b.try_clone().unwrap());
+        let listening_side = std::thread::spawn(move || {
+            socket_session(SocketEndpoint::new(stream_b).unwrap(), 0xB0, 0x80, 4096 * 4)
+        });
+        let driving_side = std::thread::spawn(move || {
+            socket_session(SocketEndpoint::new(stream_a).unwrap(), 0xA0, 0x81, 4096 * 2)
+        });
+
+        assert_eq!((0xFF, 0x7F, true), driving_side.join().unwrap());
+        assert_eq!((0xB0, 0xFE, false), listening_side.join().unwrap(), "still waiting for a clock");
+    }
+}
+
+#[test]
+fn socket_link_without_a_handshake_fails() {
+    let stream = std::io::Cursor::new(b"HTTP/1.1 200 OK".to_vec());
+    assert!(SocketEndpoint::new(stream).is_err());
+}
+
+#[test]
+fn socket_link_keeps_what_unplugged_it() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // the other side hangs up right after the handshake
+    let other_side = std::thread::spawn(move || drop(SocketEndpoint::accept_tcp(&listener).unwrap()));
+    let mut endpoint = SocketEndpoint::connect_tcp(address).unwrap();
+    other_side.join().unwrap();
+    assert!(endpoint.disconnected().is_none());
+
+    endpoint.tick(4096);
+    assert!(!endpoint.is_connected());
+    assert!(endpoint.disconnected().is_some());
+    assert_eq!(0xFF, endpoint.exchange(0xA0));
+}
@@ -0,0 +1,155 @@
+//! The timer: a 16-bit divider counting T-cycles, and TIMA, a counter clocked off one of the
+//! divider's bits that raises the timer interrupt when it overflows.
+//!
+//! Registers:
+//! ```text
+//! 0xFF04 | DIV  | upper 8 bits of the divider, any write resets the whole divider
+//! 0xFF05 | TIMA | the counter, reloaded from TMA after it overflows
+//! 0xFF06 | TMA  | what TIMA gets reloaded with
+//! 0xFF07 | TAC  | 2: TIMA on, 1-0: frequency (00: 4096 Hz, 01: 262144 Hz, 10: 65536 Hz, 11: 16384 Hz)
+//! ```
+//!
+//! TIMA doesn't really count at a frequency, it counts falling edges of (TAC on AND the divider
+//! bit TAC picks). So anything that drops that signal from 1 to 0 counts too, like resetting the
+//! divider through DIV or switching TAC to another bit or off.
+//!
+//! When TIMA overflows it reads 0x00 for one M-cycle, and only then gets TMA and requests the
+//! interrupt. Writing TIMA in that M-cycle cancels both. In the M-cycle of the reload, TIMA writes
+//! are lost and TMA writes go through to TIMA as well.
+
+#[cfg(test)]
+mod tests;
+
+use crate::interrupt::{ Interrupt, InterruptController };
+
+pub const DIV_ADDRESS: u16 = 0xFF04;
+pub const TIMA_ADDRESS: u16 = 0xFF05;
+pub const TMA_ADDRESS: u16 = 0xFF06;
+pub const TAC_ADDRESS: u16 = 0xFF07;
+
+/// The timer (like the rest of the hardware) moves a whole M-cycle at a time
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+
+const TAC_ENABLE: u8 = 1 << 2;
+const TAC_FREQUENCY: u8 = 0b11;
+/// Only the lower 3 bits of TAC exist
+const TAC_UNUSED_BITS: u8 = 0b1111_1000;
+
+/// Where TIMA is in getting reloaded after an overflow
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+enum Reload {
+    Idle,
+    /// TIMA overflowed last M-cycle and reads 0x00, it gets TMA on the next one
+    Pending,
+    /// TIMA got TMA this M-cycle
+    Reloading,
+}
+
+pub struct Timer {
+    /// Counts T-cycles, DIV is the upper 8 bits
+    divider: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    reload: Reload,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self { divider: 0, tima: 0, tma: 0, tac: 0, reload: Reload::Idle }
+    }
+
+    /// Sets the whole internal divider, for starting off where the boot ROM leaves it
+    pub fn set_divider(&mut self, divider: u16) {
+        self.divider = divider;
+    }
+
+    /// Runs the timer for `t_cycles`, requesting the timer interrupt when TIMA gets reloaded
+    pub fn tick(&mut self, t_cycles: u32, interrupts: &mut InterruptController) {
+        for _ in 0..t_cycles / T_CYCLES_PER_M_CYCLE {
+            self.step_m_cycle(interrupts);
+        }
+    }
+
+    fn step_m_cycle(&mut self, interrupts: &mut InterruptController) {
+        match self.reload {
+            Reload::Pending => {
+                self.tima = self.tma;
+                interrupts.request(Interrupt::Timer);
+                self.reload = Reload::Reloading;
+            }
+            Reload::Reloading => self.reload = Reload::Idle,
+            Reload::Idle => {}
+        }
+
+        let signal = self.signal();
+        self.divider = self.divider.wrapping_add(T_CYCLES_PER_M_CYCLE as u16);
+        self.count_if_fell(signal);
+    }
+
+    /// TAC on AND the divider bit TAC picks, TIMA counts every time this goes from 1 to 0
+    fn signal(&self) -> bool {
+        let bit = match self.tac & TAC_FREQUENCY {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & TAC_ENABLE != 0 && self.divider & (1 << bit) != 0
+    }
+
+    /// Counts TIMA up if the signal was 1 before whatever just happened and now is 0
+    fn count_if_fell(&mut self, signal_before: bool) {
+        if !signal_before || self.signal() {
+            return;
+        }
+        let (tima, overflowed) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflowed {
+            self.reload = Reload::Pending;
+        }
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            DIV_ADDRESS => (self.divider >> 8) as u8,
+            TIMA_ADDRESS => self.tima,
+            TMA_ADDRESS => self.tma,
+            TAC_ADDRESS => self.tac | TAC_UNUSED_BITS,
+            _ => unreachable!("{address:#06X} is not a timer register"),
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            DIV_ADDRESS => {
+                let signal = self.signal();
+                self.divider = 0;
+                self.count_if_fell(signal);
+            }
+            TIMA_ADDRESS => match self.reload {
+                // TMA is about to overwrite it
+                Reload::Reloading => {}
+                // the write wins, there'll be no reload and no interrupt
+                Reload::Pending => {
+                    self.tima = value;
+                    self.reload = Reload::Idle;
+                }
+                Reload::Idle => self.tima = value,
+            },
+            TMA_ADDRESS => {
+                self.tma = value;
+                if self.reload == Reload::Reloading {
+                    self.tima = value;
+                }
+            }
+            TAC_ADDRESS => {
+                let signal = self.signal();
+                self.tac = value & !TAC_UNUSED_BITS;
+                self.count_if_fell(signal);
+            }
+            _ => unreachable!("{address:#06X} is not a timer register"),
+        }
+    }
+}
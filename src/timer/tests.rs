@@ -0,0 +1,138 @@
+use super::*;
+
+/// A timer with TIMA counting at the given TAC frequency
+fn running(frequency: u8) -> Timer {
+    let mut timer = Timer::new();
+    timer.write_register(TAC_ADDRESS, TAC_ENABLE | frequency);
+    timer
+}
+
+#[test]
+fn div_is_the_upper_byte_of_the_divider() {
+    let mut timer = Timer::new();
+    let mut interrupts = InterruptController::new();
+
+    timer.tick(255, &mut interrupts);
+    assert_eq!(0x00, timer.read_register(DIV_ADDRESS));
+    timer.tick(256 * 3, &mut interrupts);
+    assert_eq!(0x03, timer.read_register(DIV_ADDRESS));
+
+    timer.write_register(DIV_ADDRESS, 0xAB);
+    assert_eq!(0x00, timer.read_register(DIV_ADDRESS), "any DIV write resets the divider");
+}
+
+#[test]
+fn tima_follows_the_tac_frequency() {
+    for (frequency, t_cycles_per_count) in [(0b00, 1024), (0b01, 16), (0b10, 64), (0b11, 256)] {
+        let mut timer = running(frequency);
+        let mut interrupts = InterruptController::new();
+
+        timer.tick(t_cycles_per_count * 10 - 4, &mut interrupts);
+        assert_eq!(9, timer.read_register(TIMA_ADDRESS), "TAC frequency {frequency:#04b}");
+        timer.tick(4, &mut interrupts);
+        assert_eq!(10, timer.read_register(TIMA_ADDRESS), "TAC frequency {frequency:#04b}");
+    }
+}
+
+#[test]
+fn tima_stops_with_tac_off() {
+    let mut timer = Timer::new();
+    let mut interrupts = InterruptController::new();
+    timer.write_register(TAC_ADDRESS, 0b01);
+
+    timer.tick(1024, &mut interrupts);
+    assert_eq!(0, timer.read_register(TIMA_ADDRESS));
+    assert_eq!(0xF9, timer.read_register(TAC_ADDRESS), "unused TAC bits read as 1");
+}
+
+#[test]
+fn div_write_with_the_bit_set_counts_tima() {
+    let mut timer = running(0b01);
+    let mut interrupts = InterruptController::new();
+
+    // bit 3 of the divider is set from 8 to 15
+    timer.tick(8, &mut interrupts);
+    assert_eq!(0, timer.read_register(TIMA_ADDRESS));
+    timer.write_register(DIV_ADDRESS, 0);
+    assert_eq!(1, timer.read_register(TIMA_ADDRESS));
+
+    // with the bit clear nothing falls
+    timer.tick(4, &mut interrupts);
+    timer.write_register(DIV_ADDRESS, 0);
+    assert_eq!(1, timer.read_register(TIMA_ADDRESS));
+}
+
+#[test]
+fn tac_writes_that_drop_the_signal_count_tima() {
+    let mut timer = running(0b01);
+    let mut interrupts = InterruptController::new();
+    timer.tick(8, &mut interrupts);
+
+    // turning TIMA off while the bit is set
+    timer.write_register(TAC_ADDRESS, 0b01);
+    assert_eq!(1, timer.read_register(TIMA_ADDRESS));
+
+    // switching from a set bit (3) to a clear one (9)
+    timer.write_register(TAC_ADDRESS, TAC_ENABLE | 0b01);
+    timer.write_register(TAC_ADDRESS, TAC_ENABLE); // frequency 00
+    assert_eq!(2, timer.read_register(TIMA_ADDRESS));
+
+    // switching to another set bit doesn't
+    let mut timer = running(0b01);
+    timer.tick(0b1010_1000, &mut interrupts);
+    let tima = timer.read_register(TIMA_ADDRESS);
+    timer.write_register(TAC_ADDRESS, TAC_ENABLE | 0b10);
+    assert_eq!(tima, timer.read_register(TIMA_ADDRESS));
+}
+
+#[test]
+fn overflow_reloads_from_tma_one_m_cycle_late() {
+    let mut timer = running(0b01);
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    timer.write_register(TMA_ADDRESS, 0x42);
+    timer.write_register(TIMA_ADDRESS, 0xFF);
+
+    timer.tick(16, &mut interrupts);
+    assert_eq!(0x00, timer.read_register(TIMA_ADDRESS), "TIMA reads 0 for an M-cycle");
+    assert!(!interrupts.has_pending());
+
+    timer.tick(4, &mut interrupts);
+    assert_eq!(0x42, timer.read_register(TIMA_ADDRESS));
+    assert_eq!(Some(Interrupt::Timer), interrupts.highest_pending());
+}
+
+#[test]
+fn tima_write_before_the_reload_cancels_it() {
+    let mut timer = running(0b01);
+    let mut interrupts = InterruptController::new();
+    interrupts.write_enable(0xFF);
+    timer.write_register(TMA_ADDRESS, 0x42);
+    timer.write_register(TIMA_ADDRESS, 0xFF);
+
+    timer.tick(16, &mut interrupts);
+    timer.write_register(TIMA_ADDRESS, 0x10);
+    timer.tick(4, &mut interrupts);
+
+    assert_eq!(0x10, timer.read_register(TIMA_ADDRESS));
+    assert!(!interrupts.has_pending(), "the interrupt is cancelled too");
+}
+
+#[test]
+fn writes_during_the_reload() {
+    let mut timer = running(0b01);
+    let mut interrupts = InterruptController::new();
+    timer.write_register(TMA_ADDRESS, 0x42);
+    timer.write_register(TIMA_ADDRESS, 0xFF);
+    timer.tick(20, &mut interrupts);
+
+    timer.write_register(TIMA_ADDRESS, 0x10);
+    assert_eq!(0x42, timer.read_register(TIMA_ADDRESS), "TIMA writes are lost to the reload");
+
+    timer.write_register(TMA_ADDRESS, 0x24);
+    assert_eq!(0x24, timer.read_register(TIMA_ADDRESS), "TMA writes go through to TIMA");
+
+    timer.tick(4, &mut interrupts);
+    timer.write_register(TMA_ADDRESS, 0x99);
+    assert_eq!(0x24, timer.read_register(TIMA_ADDRESS), "only during the reload M-cycle");
+}
@@ -0,0 +1,92 @@
+//! Comparing an execution trace (see [`Tracer`](crate::emulator::Tracer)) against one from a
+//! reference emulator, what `gameboy_emulator trace-diff` runs. Only the first divergence is
+//! reported, as everything after it usually follows from it.
+//!
+//! Lines are compared field by field (`A:01`, `PC:0100`, ...), ignoring trailing whitespace and
+//! case, so a log with CRLF line endings or lowercase hex still matches.
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt::{ self, Display };
+use std::io::{ self, BufRead };
+
+/// Where two traces first differ
+#[derive(PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct Divergence {
+    /// Counting from 1
+    pub line: usize,
+    /// Our line, `None` when our trace ended first
+    pub ours: Option<String>,
+    /// The reference's line, `None` when it ended first
+    pub reference: Option<String>,
+}
+
+impl Divergence {
+    /// The names of the fields that differ, e.g. `["F", "PC"]`. Empty when a trace ended.
+    pub fn fields(&self) -> Vec<String> {
+        let (Some(ours), Some(reference)) = (&self.ours, &self.reference) else {
+            return Vec::new();
+        };
+        let ours = fields(ours);
+        let reference = fields(reference);
+        let value = |fields: &[(String, String)], name: &str| {
+            fields.iter().find(|(other, _)| other == name).map(|(_, value)| value.clone())
+        };
+
+        let mut differing: Vec<String> = Vec::new();
+        for (name, _) in ours.iter().chain(&reference) {
+            if value(&ours, name) != value(&reference, name) && !differing.contains(name) {
+                differing.push(name.clone());
+            }
+        }
+        differing
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.ours, &self.reference) {
+            (Some(ours), Some(reference)) => {
+                writeln!(f, "The traces diverge at line {}, in {}:", self.line, self.fields().join(", "))?;
+                writeln!(f, "  ours:      {ours}")?;
+                write!(f, "  reference: {reference}")
+            }
+            (None, Some(reference)) => write!(f, "Our trace ends before line {}, where the reference has\n  {reference}", self.line),
+            (Some(ours), None) => write!(f, "The reference ends before line {}, where ours has\n  {ours}", self.line),
+            (None, None) => write!(f, "Both traces end before line {}", self.line),
+        }
+    }
+}
+
+/// The first line where `ours` and `reference` differ, `None` if they're the same
+pub fn first_divergence(ours: impl BufRead, reference: impl BufRead) -> io::Result<Option<Divergence>> {
+    let mut ours = ours.lines();
+    let mut reference = reference.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        let our_line = ours.next().transpose()?;
+        let reference_line = reference.next().transpose()?;
+        let same = match (&our_line, &reference_line) {
+            (Some(our_line), Some(reference_line)) => fields(our_line) == fields(reference_line),
+            (None, None) => return Ok(None),
+            _ => false,
+        };
+        if !same {
+            let trim = |text: String| text.trim_end().to_string();
+            return Ok(Some(Divergence { line, ours: our_line.map(trim), reference: reference_line.map(trim) }));
+        }
+    }
+}
+
+/// A line's `NAME:VALUE` fields, upper cased
+fn fields(line: &str) -> Vec<(String, String)> {
+    line.split_whitespace()
+        .map(|field| {
+            let (name, value) = field.split_once(':').unwrap_or((field, ""));
+            (name.to_ascii_uppercase(), value.to_ascii_uppercase())
+        })
+        .collect()
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+const START: &str = "\
+A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01
+A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,CE
+A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:F3,31,FE,FF
+";
+
+#[test]
+fn the_same_trace_doesnt_diverge() {
+    assert_eq!(None, first_divergence(START.as_bytes(), START.as_bytes()).unwrap());
+
+    // line endings and case don't count
+    let reference = START.replace('\n', "\r\n").replace("PCMEM:C3", "PCMEM:c3");
+    assert_eq!(None, first_divergence(START.as_bytes(), reference.as_bytes()).unwrap());
+}
+
+#[test]
+fn finds_the_first_differing_line_and_fields() {
+    let reference = START.replacen("F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150", "F:80 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0151", 1);
+    let divergence = first_divergence(START.as_bytes(), reference.as_bytes()).unwrap().unwrap();
+
+    assert_eq!(3, divergence.line);
+    assert_eq!(Some(START.lines().nth(2).unwrap().to_string()), divergence.ours);
+    assert_eq!(vec!["F", "PC"], divergence.fields());
+    assert!(divergence.to_string().starts_with("The traces diverge at line 3, in F, PC:\n  ours:      A:01"), "{divergence}");
+}
+
+#[test]
+fn a_trace_ending_early_diverges() {
+    let short: String = START.lines().take(2).map(|line| format!("{line}\n")).collect();
+
+    let divergence = first_divergence(short.as_bytes(), START.as_bytes()).unwrap().unwrap();
+    assert_eq!(3, divergence.line);
+    assert_eq!(None, divergence.ours);
+    assert!(divergence.fields().is_empty());
+    assert!(divergence.to_string().starts_with("Our trace ends before line 3"), "{divergence}");
+
+    let divergence = first_divergence(START.as_bytes(), short.as_bytes()).unwrap().unwrap();
+    assert_eq!(None, divergence.reference);
+}
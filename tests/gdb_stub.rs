@@ -0,0 +1,232 @@
+//! Drives the gdb stub over a real local TCP connection with a scripted client speaking the
+//! remote serial protocol, the way gdb would (no gdb needed).
+
+use std::io::{ Read, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::thread::{ self, JoinHandle };
+use std::time::Duration;
+
+use gameboy_emulator::disassembler::assemble;
+use gameboy_emulator::emulator::{ Cartridge, Emulator };
+use gameboy_emulator::gdb::GdbStub;
+
+/// Fills 0xC000-0xC003 with 1, 2, 3, 4 (A starts at 1 after the boot ROM) then spins
+const FILL: &str = "
+            LD HL,$C000
+            LD B,4
+    loop:   LD (HL+),A
+            INC A
+            DEC B
+            JR NZ,loop
+    done:   JR done
+";
+
+/// A 32 KiB ROM running `source` from 0x0100, with a header that passes the checksum
+fn rom(source: &str) -> Vec<u8> {
+    let program = assemble(source, 0x0100).expect("the test program should assemble");
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+    rom[0x014D] = rom[0x0134..0x014D].iter().fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1));
+    rom
+}
+
+/// The client side of a session, with the stub serving it on its own thread
+struct Client {
+    stream: TcpStream,
+    server: JoinHandle<()>,
+}
+
+impl Client {
+    fn connect(source: &str) -> Self {
+        let rom = rom(source);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stub = GdbStub::new(Emulator::new(Cartridge::from_bytes(&rom).unwrap()));
+            stub.serve(stream).unwrap();
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        stream.set_nodelay(true).unwrap();
+        // a hung stub should fail the test, not hang it
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        Self { stream, server }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn send_packet(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${data}#{checksum:02x}").unwrap();
+    }
+
+    /// Reads a reply packet, checks its checksum and acks it
+    fn read_reply(&mut self) -> String {
+        assert_eq!(b'$', self.read_byte(), "a reply should start with $");
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum = String::from_utf8(vec![self.read_byte(), self.read_byte()]).unwrap();
+        assert_eq!(data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)), u8::from_str_radix(&checksum, 16).unwrap());
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    /// Sends a packet and returns the reply, after checking the stub acked it
+    fn command(&mut self, data: &str) -> String {
+        self.send_packet(data);
+        assert_eq!(b'+', self.read_byte(), "{data} wasn't acked");
+        self.read_reply()
+    }
+
+    /// PC out of a `g` reply, the last two bytes
+    fn pc(&mut self) -> u16 {
+        let registers = self.command("g");
+        let pc = u16::from_str_radix(&registers[registers.len() - 4..], 16).unwrap();
+        pc.swap_bytes()
+    }
+
+    fn detach(mut self) {
+        assert_eq!("OK", self.command("D"));
+        self.server.join().unwrap();
+    }
+}
+
+#[test]
+fn handshakes_like_gdb() {
+    let mut client = Client::connect(FILL);
+
+    let supported = client.command("qSupported:multiprocess+;swbreak+;xmlRegisters=i386");
+    assert!(supported.contains("qXfer:features:read+"), "{supported}");
+    assert_eq!("S05", client.command("?"));
+    assert_eq!("1", client.command("qAttached"));
+    assert_eq!("OK", client.command("Hg0"));
+    assert_eq!("", client.command("vMustReplyEmpty"));
+
+    let mut xml = String::new();
+    loop {
+        let chunk = client.command(&format!("qXfer:features:read:target.xml:{:x},80", xml.len()));
+        xml.push_str(&chunk[1..]);
+        if chunk.starts_with('l') {
+            break;
+        }
+        assert!(chunk.starts_with('m'), "{chunk}");
+    }
+    assert!(xml.contains(r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#), "{xml}");
+    client.detach();
+}
+
+#[test]
+fn reads_and_writes_registers() {
+    let mut client = Client::connect(FILL);
+
+    // A F B C D E H L, then SP and PC little endian, as the boot ROM leaves them
+    assert_eq!("01b0001300d8014dfeff0001", client.command("g"));
+    assert_eq!("0001", client.command("p9"));
+    assert_eq!("b0", client.command("p1"));
+
+    assert_eq!("OK", client.command("P8=f0df"));
+    assert_eq!("OK", client.command("P0=7f"));
+    assert_eq!("7fb0001300d8014df0df0001", client.command("g"));
+
+    assert_eq!("OK", client.command("G0100020304050607feff5001"));
+    assert_eq!("0100020304050607feff5001", client.command("g"));
+    assert_eq!(0x0150, client.pc());
+
+    assert!(client.command("pa").starts_with('E'));
+    assert!(client.command("P0=1234").starts_with('E'));
+    client.detach();
+}
+
+#[test]
+fn reads_and_writes_memory() {
+    let mut client = Client::connect(FILL);
+
+    // the program, from the ROM
+    assert_eq!("2100c00604", client.command("m100,5"));
+
+    assert_eq!("OK", client.command("Mc000,3:abcdef"));
+    assert_eq!("abcdef00", client.command("mc000,4"));
+
+    // gdb probes X with an empty write first, then sends binary data with $ # } * escaped
+    assert_eq!("OK", client.command("Xc010,0:"));
+    let mut packet = b"Xc010,3:A}\x04".to_vec();
+    packet.push(b'B');
+    let checksum = packet.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+    client.stream.write_all(b"$").unwrap();
+    client.stream.write_all(&packet).unwrap();
+    write!(client.stream, "#{checksum:02x}").unwrap();
+    assert_eq!(b'+', client.read_byte());
+    assert_eq!("OK", client.read_reply());
+    assert_eq!("412442", client.command("mc010,3"));
+
+    assert!(client.command("Mc000,2:ab").starts_with('E'));
+    client.detach();
+}
+
+#[test]
+fn steps_and_stops_at_breakpoints() {
+    let mut client = Client::connect(FILL);
+
+    assert_eq!("S05", client.command("s"));
+    assert_eq!(0x0103, client.pc());
+
+    assert_eq!("OK", client.command("Z0,108,1"));
+    assert_eq!("S05", client.command("c"));
+    assert_eq!(0x0108, client.pc());
+    assert_eq!("01000000", client.command("mc000,4"));
+
+    // continuing from a breakpoint runs past it and round the loop to it again
+    assert_eq!("S05", client.command("c"));
+    assert_eq!(0x0108, client.pc());
+    assert_eq!("01020000", client.command("mc000,4"));
+
+    assert_eq!("OK", client.command("z0,108,1"));
+    assert_eq!("OK", client.command("Z0,10a,1"));
+    assert_eq!("S05", client.command("c"));
+    assert_eq!(0x010A, client.pc());
+    assert_eq!("01020304", client.command("mc000,4"));
+
+    // only software breakpoints
+    assert_eq!("", client.command("Z2,c000,1"));
+    client.detach();
+}
+
+#[test]
+fn interrupts_a_continue() {
+    let mut client = Client::connect(FILL);
+
+    client.send_packet("c");
+    assert_eq!(b'+', client.read_byte());
+    thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!("S02", client.read_reply());
+    assert_eq!(0x010A, client.pc(), "it should be in the spin loop at the end");
+    client.detach();
+}
+
+#[test]
+fn resends_after_a_bad_checksum_and_stops_acking_when_asked() {
+    let mut client = Client::connect(FILL);
+
+    client.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(b'-', client.read_byte());
+    assert_eq!("0001", client.command("p9"));
+
+    assert_eq!("OK", client.command("QStartNoAckMode"));
+    client.send_packet("p9");
+    assert_eq!("0001", client.read_reply());
+
+    // kill ends the session without a reply
+    client.send_packet("k");
+    client.server.join().unwrap();
+}
@@ -0,0 +1,131 @@
+//! Two emulators with a link cable between them over loopback TCP, each on its own thread like
+//! two processes would be, going through the public API only.
+
+use std::cell::RefCell;
+use std::io;
+use std::net::TcpListener;
+use std::rc::Rc;
+use std::thread;
+
+use gameboy_emulator::emulator::{ Cartridge, Emulator, SerialEndpoint, SocketEndpoint };
+
+/// Waits out a few sync quanta so the other side is listening, then sends '!' on its own clock:
+/// ```text
+///         LD BC,$0400
+/// delay:  DEC BC
+///         LD A,B
+///         OR C
+///         JP NZ,delay
+///         LD A,$21
+///         LDH ($01),A
+///         LD A,$81
+///         LDH ($02),A
+/// wait:   LDH A,($02)
+///         BIT 7,A
+///         JP NZ,wait
+/// done:   JP done
+/// ```
+const DRIVING: &[u8] = &[
+    0x01, 0x00, 0x04,
+    0x0B,
+    0x78,
+    0xB1,
+    0xC2, 0x03, 0x01,
+    0x3E, 0x21,
+    0xE0, 0x01,
+    0x3E, 0x81,
+    0xE0, 0x02,
+    0xF0, 0x02,
+    0xCB, 0x7F,
+    0xC2, 0x11, 0x01,
+    0xC3, 0x18, 0x01,
+];
+
+/// Listens on the other side's clock with '?' in SB:
+/// ```text
+///         LD A,$3F
+///         LDH ($01),A
+///         LD A,$80
+///         LDH ($02),A
+/// wait:   LDH A,($02)
+///         BIT 7,A
+///         JP NZ,wait
+/// done:   JP done
+/// ```
+const LISTENING: &[u8] = &[
+    0x3E, 0x3F,
+    0xE0, 0x01,
+    0x3E, 0x80,
+    0xE0, 0x02,
+    0xF0, 0x02,
+    0xCB, 0x7F,
+    0xC2, 0x08, 0x01,
+    0xC3, 0x0F, 0x01,
+];
+
+/// Both sides run for the same emulated time, as they're in lockstep anyway
+const CYCLES: u64 = 200_000;
+
+/// A 32 KiB ROM running `program` from 0x0100, with a header that passes the checksum
+fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0100 + program.len()].copy_from_slice(program);
+    rom[0x014D] = rom[0x0134..0x014D].iter().fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1));
+    rom
+}
+
+/// The cable, noting every transfer as (sent, received)
+struct Recorded {
+    link: SocketEndpoint,
+    transfers: Rc<RefCell<Vec<(u8, u8)>>>,
+}
+
+impl SerialEndpoint for Recorded {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let received = self.link.exchange(byte);
+        self.transfers.borrow_mut().push((byte, received));
+        received
+    }
+
+    fn poll_external(&mut self, byte: u8) -> Option<u8> {
+        let received = self.link.poll_external(byte);
+        if let Some(received) = received {
+            self.transfers.borrow_mut().push((byte, received));
+        }
+        received
+    }
+
+    fn tick(&mut self, t_cycles: u32) {
+        self.link.tick(t_cycles);
+    }
+
+    fn disconnected(&self) -> Option<&io::Error> {
+        self.link.disconnected()
+    }
+}
+
+/// Runs `program` with `link` plugged in, and returns the transfers it made
+fn run_linked(program: &[u8], link: SocketEndpoint) -> Vec<(u8, u8)> {
+    let transfers = Rc::new(RefCell::new(Vec::new()));
+    let mut emulator = Emulator::new(Cartridge::from_bytes(&rom(program)).unwrap());
+    emulator.set_serial_endpoint(Recorded { link, transfers: transfers.clone() });
+
+    let mut cycles = 0;
+    while cycles < CYCLES {
+        cycles += emulator.step().unwrap() as u64;
+    }
+    assert!(emulator.link_error().is_none());
+    transfers.take()
+}
+
+#[test]
+fn two_emulators_swap_bytes_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let listening = thread::spawn(move || run_linked(LISTENING, SocketEndpoint::accept_tcp(&listener).unwrap()));
+    let driving = thread::spawn(move || run_linked(DRIVING, SocketEndpoint::connect_tcp(address).unwrap()));
+
+    assert_eq!(vec![(b'!', b'?')], driving.join().unwrap());
+    assert_eq!(vec![(b'?', b'!')], listening.join().unwrap());
+}
@@ -0,0 +1,251 @@
+//! Runs the public test ROM suites and reports pass/fail per ROM. The ROMs aren't in the repo:
+//! put them in `tests/roms` (or point `GB_TEST_ROMS` somewhere else) laid out like their
+//! upstream repos,
+//! ```text
+//! tests/roms/blargg/cpu_instrs/individual/01-special.gb
+//! tests/roms/blargg/instr_timing/instr_timing.gb
+//! tests/roms/blargg/mem_timing/individual/01-read_timing.gb
+//! tests/roms/mooneye/acceptance/add_sp_e_timing.gb
+//! ```
+//! and run `cargo test --release --test test_roms -- --nocapture` to see every result. Any ROM
+//! that isn't there is skipped.
+
+use std::env;
+use std::fs;
+use std::panic::{ self, AssertUnwindSafe };
+use std::path::{ Path, PathBuf };
+
+use gameboy_emulator::emulator::{ Cartridge, Emulator };
+use gameboy_emulator::runner::{ Runner, StopConditions, StopReason };
+
+/// T-cycles in a frame, how often the blargg results get checked
+const CYCLES_PER_FRAME: u64 = 70_224;
+/// T-cycles in a second of emulated time
+const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+const BLARGG_CPU_INSTRS: [&str; 11] = [
+    "01-special.gb",
+    "02-interrupts.gb",
+    "03-op sp,hl.gb",
+    "04-op r,imm.gb",
+    "05-op rp.gb",
+    "06-ld r,r.gb",
+    "07-jr,jp,call,ret,rst.gb",
+    "08-misc instrs.gb",
+    "09-op r,r.gb",
+    "10-bit ops.gb",
+    "11-op a,(hl).gb",
+];
+const BLARGG_MEM_TIMING: [&str; 3] = ["01-read_timing.gb", "02-write_timing.gb", "03-modify_timing.gb"];
+
+/// Where blargg's ROMs put their result in cartridge RAM: a status byte (0x80 while running,
+/// then 0 for passed), this signature, then the text they print as a C string
+const BLARGG_STATUS_ADDRESS: u16 = 0xA000;
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_RUNNING: u8 = 0x80;
+
+/// What mooneye's ROMs leave in B, C, D, E, H and L at the final `LD B,B`
+const MOONEYE_PASSED: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAILED: [u8; 6] = [0x42; 6];
+
+enum Outcome {
+    Passed,
+    Failed(String),
+    TimedOut,
+    /// The emulator errored or panicked
+    Crashed(String),
+}
+
+fn roms_dir() -> PathBuf {
+    env::var_os("GB_TEST_ROMS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms"))
+}
+
+fn runner(rom: &Path) -> Result<Runner, String> {
+    let cartridge = Cartridge::from_path(rom).map_err(|err| err.to_string())?;
+    Ok(Runner::new(Emulator::new(cartridge)))
+}
+
+/// Runs every ROM in `roms` that exists through `run`, printing how each went, and fails if any
+/// of them didn't pass
+fn run_suite(name: &str, roms: &[PathBuf], run: fn(&Path) -> Outcome) {
+    let mut failures = Vec::new();
+    let mut skipped = 0;
+
+    for rom in roms {
+        if !rom.is_file() {
+            skipped += 1;
+            continue;
+        }
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| run(rom)))
+            .unwrap_or_else(|panic| {
+                let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Outcome::Crashed(format!("panicked: {message}"))
+            });
+
+        let result = match &outcome {
+            Outcome::Passed => "passed".to_string(),
+            Outcome::Failed(details) => format!("FAILED {}", details.trim()),
+            Outcome::TimedOut => "TIMED OUT".to_string(),
+            Outcome::Crashed(details) => format!("CRASHED {details}"),
+        };
+        println!("{name}: {} {result}", rom.display());
+        if !matches!(outcome, Outcome::Passed) {
+            failures.push(format!("{} {result}", rom.display()));
+        }
+    }
+
+    if skipped > 0 {
+        println!("{name}: skipped {skipped} of {} ROMs that aren't in {}", roms.len(), roms_dir().display());
+    }
+    assert!(failures.is_empty(), "{name}: {} of {} ROMs didn't pass:\n{}", failures.len(), roms.len(), failures.join("\n"));
+}
+
+/// Blargg's ROMs print "Passed" or "Failed" over serial, and the ones with cartridge RAM also
+/// leave their result there
+fn run_blargg(rom: &Path, seconds: u64) -> Outcome {
+    let mut runner = match runner(rom) {
+        Ok(runner) => runner,
+        Err(err) => return Outcome::Crashed(err),
+    };
+
+    // checked every frame's worth of cycles, the LCD may well be off
+    let one_frame = StopConditions { cycles: Some(CYCLES_PER_FRAME), ..Default::default() };
+    for _ in 0..seconds * CYCLES_PER_SECOND / CYCLES_PER_FRAME {
+        if let Err(err) = runner.run(&one_frame) {
+            return Outcome::Crashed(err.to_string());
+        }
+
+        let serial = runner.serial_output();
+        if serial.contains("Passed") {
+            return Outcome::Passed;
+        }
+        if serial.contains("Failed") {
+            return Outcome::Failed(serial);
+        }
+
+        let emulator = runner.emulator();
+        let signature = [1, 2, 3].map(|offset| emulator.read_byte(BLARGG_STATUS_ADDRESS + offset));
+        let status = emulator.read_byte(BLARGG_STATUS_ADDRESS);
+        if signature == BLARGG_SIGNATURE && status != BLARGG_RUNNING {
+            return match status {
+                0 => Outcome::Passed,
+                _ => Outcome::Failed(format!("status {status:#04X}: {}", blargg_text(emulator))),
+            };
+        }
+    }
+    Outcome::TimedOut
+}
+
+fn blargg_text(emulator: &Emulator) -> String {
+    let bytes: Vec<u8> = (BLARGG_STATUS_ADDRESS + 4..0xC000)
+        .map(|address| emulator.read_byte(address))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Mooneye's ROMs end on `LD B,B` with a Fibonacci signature in the registers
+fn run_mooneye(rom: &Path) -> Outcome {
+    let mut runner = match runner(rom) {
+        Ok(runner) => runner,
+        Err(err) => return Outcome::Crashed(err),
+    };
+
+    let conditions = StopConditions { cycles: Some(20 * CYCLES_PER_SECOND), software_breakpoint: true, ..Default::default() };
+    match runner.run(&conditions) {
+        Ok(StopReason::SoftwareBreakpoint) => (),
+        Ok(_) => return Outcome::TimedOut,
+        Err(err) => return Outcome::Crashed(err.to_string()),
+    }
+
+    let registers = runner.emulator().cpu().registers();
+    let signature = [registers.b, registers.c, registers.d, registers.e, registers.h, registers.l];
+    match signature {
+        MOONEYE_PASSED => Outcome::Passed,
+        MOONEYE_FAILED => Outcome::Failed("with the failure signature".to_string()),
+        _ => Outcome::Failed(format!("with registers {signature:02X?}")),
+    }
+}
+
+/// Whether a mooneye ROM runs on the original DMG, going by the models in its name (`-dmgABC`,
+/// `-GS`, ...), with nothing there meaning all of them
+fn runs_on_dmg(rom: &Path) -> bool {
+    let stem = rom.file_stem().unwrap_or_default().to_string_lossy();
+    match stem.rsplit_once('-') {
+        None => true,
+        // `dmgABC` and combinations like `dmgABCmgb`
+        Some((_, models)) if models.starts_with("dmg") => models.starts_with("dmgABC"),
+        // groups like `GS`, where G is the DMG family
+        Some((_, models)) if models.chars().all(|model| model.is_ascii_uppercase()) => models.contains('G'),
+        Some(_) => false,
+    }
+}
+
+/// Every ROM under `dir` (and its subdirectories), sorted
+fn roms_in(dir: &Path) -> Vec<PathBuf> {
+    let mut roms = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return roms;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            roms.extend(roms_in(&path));
+        } else if path.extension().is_some_and(|extension| extension == "gb") {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+    roms
+}
+
+#[test]
+fn blargg_cpu_instrs() {
+    let dir = roms_dir().join("blargg").join("cpu_instrs").join("individual");
+    let roms: Vec<PathBuf> = BLARGG_CPU_INSTRS.iter().map(|rom| dir.join(rom)).collect();
+    run_suite("cpu_instrs", &roms, |rom| run_blargg(rom, 30));
+}
+
+#[test]
+fn blargg_instr_timing() {
+    let rom = roms_dir().join("blargg").join("instr_timing").join("instr_timing.gb");
+    run_suite("instr_timing", &[rom], |rom| run_blargg(rom, 10));
+}
+
+#[test]
+fn blargg_mem_timing() {
+    let dir = roms_dir().join("blargg").join("mem_timing").join("individual");
+    let roms: Vec<PathBuf> = BLARGG_MEM_TIMING.iter().map(|rom| dir.join(rom)).collect();
+    run_suite("mem_timing", &roms, |rom| run_blargg(rom, 10));
+}
+
+#[test]
+fn mooneye_acceptance() {
+    let roms: Vec<PathBuf> = roms_in(&roms_dir().join("mooneye").join("acceptance"))
+        .into_iter()
+        .filter(|rom| runs_on_dmg(rom))
+        .collect();
+    if roms.is_empty() {
+        println!("mooneye acceptance: skipped, no ROMs in {}", roms_dir().display());
+    }
+    run_suite("mooneye acceptance", &roms, run_mooneye);
+}
+
+#[test]
+fn mooneye_model_filter() {
+    for (rom, expected) in [
+        ("add_sp_e_timing.gb", true),
+        ("boot_regs-dmgABC.gb", true),
+        ("boot_hwio-dmgABCmgb.gb", true),
+        ("di_timing-GS.gb", true),
+        ("boot_regs-dmg0.gb", false),
+        ("boot_regs-mgb.gb", false),
+        ("boot_div-S.gb", false),
+        ("boot_regs-sgb2.gb", false),
+    ] {
+        assert_eq!(expected, runs_on_dmg(Path::new(rom)), "{rom}");
+    }
+}